@@ -0,0 +1,259 @@
+//! Reading message heads from `futures-io` readers.
+//!
+//! `read_request` and `read_response` return futures, independent of any
+//! runtime, which read from an `AsyncBufRead` until a head is parsed. Bytes
+//! after the head are left in the reader, so it is positioned at the start
+//! of the body when returned with the head.
+//!
+//! ## Example
+//!
+//! ```
+//! extern crate futures;
+//! extern crate thhp;
+//!
+//! use futures::executor::block_on;
+//! use futures::io::{AsyncReadExt, Cursor};
+//! use thhp::body::Limits;
+//!
+//! let reader = Cursor::new(&b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello"[..]);
+//! let (req, mut reader) = block_on(thhp::async_io::read_request(reader, Limits::default())).unwrap();
+//! assert_eq!(req.method(), "POST");
+//!
+//! let mut body = Vec::new();
+//! block_on(reader.read_to_end(&mut body)).unwrap();
+//! assert_eq!(body, b"hello");
+//! ```
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::result;
+use std::task::{Context, Poll};
+
+use futures_io::AsyncBufRead;
+
+use crate::body::Limits;
+use crate::errors::*;
+use crate::io::HeadScanner;
+use crate::{
+    Complete, HeaderField, Incomplete, OwnedRequest, OwnedResponse, Request, Response, Status,
+};
+
+// Reading state shared by the futures.
+#[derive(Debug)]
+struct ReadHead<R> {
+    reader: Option<R>,
+    buf: Vec<u8>,
+    scanner: HeadScanner,
+    limits: Limits,
+}
+
+impl<R: AsyncBufRead + Unpin> ReadHead<R> {
+    fn new(reader: R, limits: Limits) -> ReadHead<R> {
+        ReadHead {
+            reader: Some(reader),
+            buf: Vec::new(),
+            scanner: HeadScanner::default(),
+            limits,
+        }
+    }
+
+    fn poll_head<T, F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        parse: F,
+    ) -> Poll<result::Result<(T, R), StreamError>>
+    where
+        F: for<'b> Fn(&'b [u8], &mut Vec<HeaderField<'b>>) -> Result<Status<(T, usize)>>,
+    {
+        loop {
+            let reader = self.reader.as_mut().expect("polled after completion");
+            let available = match Pin::new(&mut *reader).poll_fill_buf(cx) {
+                Poll::Ready(Ok(available)) => available,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+            if available.is_empty() {
+                return Poll::Ready(Err(StreamError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed before a message head",
+                ))));
+            }
+
+            let buffered = self.buf.len();
+            let len = available.len().min(self.limits.max_head_len - buffered);
+            self.buf.extend_from_slice(&available[..len]);
+
+            // The head is parsed once its end is found, so the header fields
+            // are allocated once.
+            if let Some(head_len) = self.scanner.scan(&self.buf) {
+                let mut headers = Vec::with_capacity(self.limits.max_headers);
+                return match parse(&self.buf[..head_len], &mut headers).map_err(head_error)? {
+                    Complete((head, _)) => {
+                        Pin::new(&mut *reader).consume(head_len - buffered);
+                        let reader = self.reader.take().expect("polled after completion");
+                        Poll::Ready(Ok((head, reader)))
+                    }
+                    Incomplete => Poll::Ready(Err(StreamError::Parse(InvalidNewLine))),
+                };
+            }
+            Pin::new(&mut *reader).consume(len);
+            if self.buf.len() >= self.limits.max_head_len {
+                return Poll::Ready(Err(StreamError::HeadTooLarge));
+            }
+        }
+    }
+}
+
+/// A future reading a request head.
+///
+/// It is returned by `read_request`.
+#[derive(Debug)]
+pub struct ReadRequest<R> {
+    inner: ReadHead<R>,
+}
+
+impl<R: AsyncBufRead + Unpin> Future for ReadRequest<R> {
+    type Output = result::Result<(OwnedRequest, R), StreamError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.poll_head(cx, |buf, headers| {
+            Ok(match Request::parse(buf, headers)? {
+                Complete((req, len)) => Complete((req.to_owned(), len)),
+                Incomplete => Incomplete,
+            })
+        })
+    }
+}
+
+/// A future reading a response head.
+///
+/// It is returned by `read_response`.
+#[derive(Debug)]
+pub struct ReadResponse<R> {
+    inner: ReadHead<R>,
+}
+
+impl<R: AsyncBufRead + Unpin> Future for ReadResponse<R> {
+    type Output = result::Result<(OwnedResponse, R), StreamError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.poll_head(cx, |buf, headers| {
+            Ok(match Response::parse(buf, headers)? {
+                Complete((res, len)) => Complete((res.to_owned(), len)),
+                Incomplete => Incomplete,
+            })
+        })
+    }
+}
+
+/// Reads a request head from `reader`.
+///
+/// The future resolves to the request with the reader positioned at the
+/// start of the body. A head longer than `limits.max_head_len` or with more
+/// than `limits.max_headers` header fields is an error. Pass `&mut reader`
+/// to keep using the reader otherwise.
+pub fn read_request<R: AsyncBufRead + Unpin>(reader: R, limits: Limits) -> ReadRequest<R> {
+    ReadRequest {
+        inner: ReadHead::new(reader, limits),
+    }
+}
+
+/// Reads a response head from `reader`.
+///
+/// The future resolves to the response with the reader positioned at the
+/// start of the body. A head longer than `limits.max_head_len` or with more
+/// than `limits.max_headers` header fields is an error.
+pub fn read_response<R: AsyncBufRead + Unpin>(reader: R, limits: Limits) -> ReadResponse<R> {
+    ReadResponse {
+        inner: ReadHead::new(reader, limits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use futures::io::{AsyncReadExt, BufReader, Cursor};
+
+    use crate::async_io::*;
+
+    fn limits(max_head_len: usize, max_headers: usize) -> Limits {
+        Limits {
+            max_head_len,
+            max_headers,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn test_read_request() {
+        let input = &b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\nGET /b HTTP/1.1\r\n\r\nrest"[..];
+        // Small buffers make the head span multiple reads.
+        for &capacity in [1, 7, 64].iter() {
+            let mut reader = BufReader::with_capacity(capacity, Cursor::new(input));
+            let (req, _) = block_on(read_request(&mut reader, Limits::default())).unwrap();
+            assert_eq!(req.target(), "/a");
+            assert_eq!(req.headers().next().unwrap().value, "example.com");
+
+            let (req, mut reader) = block_on(read_request(reader, Limits::default())).unwrap();
+            assert_eq!(req.target(), "/b");
+            assert_eq!(req.headers().len(), 0);
+
+            let mut rest = Vec::new();
+            block_on(reader.read_to_end(&mut rest)).unwrap();
+            assert_eq!(rest, b"rest");
+        }
+    }
+
+    #[test]
+    fn test_read_response() {
+        let reader = Cursor::new(&b"HTTP/1.1 204 No Content\r\n\r\n"[..]);
+        let (res, _) = block_on(read_response(reader, Limits::default())).unwrap();
+        assert_eq!(res.status(), 204);
+        assert_eq!(res.reason(), "No Content");
+    }
+
+    #[test]
+    fn test_read_request_after_empty_lines() {
+        let input = &b"\r\n\nGET / HTTP/1.1\r\nA: b\n\r\nrest"[..];
+        for &capacity in [1, 3, 1024].iter() {
+            let reader = BufReader::with_capacity(capacity, Cursor::new(input));
+            let (req, mut reader) = block_on(read_request(reader, Limits::default())).unwrap();
+            assert_eq!(req.headers().len(), 1);
+
+            let mut rest = Vec::new();
+            block_on(reader.read_to_end(&mut rest)).unwrap();
+            assert_eq!(rest, b"rest");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let reader = Cursor::new(&b"GET / HTTP/1.1\r\nHost: a"[..]);
+        match block_on(read_request(reader, Limits::default())) {
+            Err(StreamError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!(),
+        }
+
+        let reader = Cursor::new(&b"GET / HTTP/1.1\r\nHo st: a\r\n\r\n"[..]);
+        match block_on(read_request(reader, Limits::default())) {
+            Err(StreamError::Parse(InvalidFieldName)) => (),
+            _ => panic!(),
+        }
+
+        let reader = Cursor::new(&b"HTTP/1.1 200 OK\r\nA: b\r\nC: d\r\n\r\n"[..]);
+        match block_on(read_response(reader, limits(64, 1))) {
+            Err(StreamError::TooManyHeaders) => (),
+            _ => panic!(),
+        }
+
+        // The limit is the length of the head, so it is read exactly.
+        let input = &b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"[..];
+        let reader = Cursor::new(input);
+        assert!(block_on(read_request(reader, limits(input.len(), 1))).is_ok());
+        let reader = Cursor::new(input);
+        match block_on(read_request(reader, limits(input.len() - 1, 1))) {
+            Err(StreamError::HeadTooLarge) => (),
+            _ => panic!(),
+        }
+    }
+}
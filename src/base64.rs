@@ -0,0 +1,212 @@
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[inline]
+fn value_of(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[inline]
+fn url_value_of(c: u8) -> Option<u8> {
+    match c {
+        b'-' => Some(62),
+        b'_' => Some(63),
+        b'+' | b'/' => None,
+        c => value_of(c),
+    }
+}
+
+/// Returns the number of padded base64 characters encoding `len` bytes.
+#[inline]
+pub fn encoded_len(len: usize) -> usize {
+    (len + 2) / 3 * 4
+}
+
+/// Encodes `input` into `out` with padding, returns the number of written
+/// characters.
+///
+/// Returns `None` if `out` is too small.
+pub fn encode(input: &[u8], out: &mut [u8]) -> Option<usize> {
+    let len = encoded_len(input.len());
+    let out = out.get_mut(..len)?;
+    for (src, dst) in input.chunks(3).zip(out.chunks_mut(4)) {
+        let b = [
+            src[0],
+            src.get(1).cloned().unwrap_or(0),
+            src.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for (i, c) in dst.iter_mut().enumerate() {
+            *c = if i <= src.len() {
+                STANDARD[(n >> (18 - 6 * i) & 0x3F) as usize]
+            } else {
+                b'='
+            };
+        }
+    }
+    Some(len)
+}
+
+/// Returns the maximum number of bytes decoded from `len` characters.
+#[inline]
+pub fn decoded_len(len: usize) -> usize {
+    len / 4 * 3 + (len % 4) * 3 / 4
+}
+
+/// Decodes padded base64 `input` into `out`, returns the number of decoded
+/// bytes.
+///
+/// Returns `None` if `input` is invalid or `out` is too small.
+pub fn decode(input: &[u8], out: &mut [u8]) -> Option<usize> {
+    if input.len() % 4 != 0 {
+        return None;
+    }
+    let data = input
+        .strip_suffix(b"==")
+        .or_else(|| input.strip_suffix(b"="))
+        .unwrap_or(input);
+    decode_unpadded(data, out, value_of)
+}
+
+/// Decodes base64 `input` into `out`, returns the number of decoded bytes.
+/// Padding is optional.
+///
+/// Returns `None` if `input` is invalid or `out` is too small.
+#[cfg(feature = "alloc")]
+pub fn decode_lenient(input: &[u8], out: &mut [u8]) -> Option<usize> {
+    decode_unpadded(strip_padding(input)?, out, value_of)
+}
+
+/// Decodes base64url `input` (RFC 4648 section 5) into `out`, returns the
+/// number of decoded bytes. Padding is optional.
+///
+/// Returns `None` if `input` is invalid or `out` is too small.
+pub fn decode_url(input: &[u8], out: &mut [u8]) -> Option<usize> {
+    decode_unpadded(strip_padding(input)?, out, url_value_of)
+}
+
+// Strips optional padding, which must fill up the last group if present.
+fn strip_padding(input: &[u8]) -> Option<&[u8]> {
+    let data = input
+        .strip_suffix(b"==")
+        .or_else(|| input.strip_suffix(b"="))
+        .unwrap_or(input);
+    if data.len() != input.len() && input.len() % 4 != 0 {
+        return None;
+    }
+    Some(data)
+}
+
+fn decode_unpadded<F>(data: &[u8], out: &mut [u8], value_of: F) -> Option<usize>
+where
+    F: Fn(u8) -> Option<u8>,
+{
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let len = decoded_len(data.len());
+    let out = out.get_mut(..len)?;
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut i = 0;
+    for c in data {
+        acc = (acc << 6) | u32::from(value_of(*c)?);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out[i] = (acc >> bits) as u8;
+            i += 1;
+        }
+    }
+    Some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base64::*;
+
+    macro_rules! check {
+        ($input:expr, $expect:expr) => {{
+            let mut out = [0u8; 64];
+            let r = decode($input, &mut out).map(|n| &out[..n]);
+            assert_eq!(r, $expect);
+        }};
+    }
+
+    #[test]
+    fn test_encode() {
+        macro_rules! check_encode {
+            ($input:expr, $expect:expr) => {{
+                let mut out = [0u8; 64];
+                let n = encode($input, &mut out).unwrap();
+                assert_eq!(&out[..n], $expect.as_ref());
+            }};
+        }
+
+        check_encode!(b"", b"");
+        check_encode!(b"f", b"Zg==");
+        check_encode!(b"fo", b"Zm8=");
+        check_encode!(b"foo", b"Zm9v");
+        check_encode!(b"foobar", b"Zm9vYmFy");
+        check_encode!(b"\xfb\xff\xbf", b"+/+/");
+        assert_eq!(encode(b"foo", &mut [0u8; 3]), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        check!(b"", Some(&b""[..]));
+        check!(b"Zg==", Some(&b"f"[..]));
+        check!(b"Zm8=", Some(&b"fo"[..]));
+        check!(b"Zm9v", Some(&b"foo"[..]));
+        check!(b"Zm9vYmFy", Some(&b"foobar"[..]));
+        check!(b"+/+/", Some(&b"\xfb\xff\xbf"[..]));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        check!(b"Zg", None);
+        check!(b"Zg=", None);
+        check!(b"Z===", None);
+        check!(b"Zm9v!A==", None);
+        check!(b"-_-_", None);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_lenient() {
+        let mut out = [0u8; 64];
+        let mut check = |input: &[u8]| decode_lenient(input, &mut out).map(|n| out[..n].to_vec());
+        assert_eq!(check(b"Zm8="), Some(b"fo".to_vec()));
+        assert_eq!(check(b"Zm8"), Some(b"fo".to_vec()));
+        assert_eq!(check(b"Zg"), Some(b"f".to_vec()));
+        assert_eq!(check(b"Zg="), None);
+        assert_eq!(check(b"Z"), None);
+        assert_eq!(check(b"-_-_"), None);
+    }
+
+    #[test]
+    fn test_decode_url() {
+        let mut out = [0u8; 64];
+        let mut check = |input: &[u8]| decode_url(input, &mut out).map(|n| out[..n].to_vec());
+        assert_eq!(check(b"-_-_"), Some(b"\xfb\xff\xbf".to_vec()));
+        assert_eq!(check(b"Zg"), Some(b"f".to_vec()));
+        assert_eq!(check(b"Zg=="), Some(b"f".to_vec()));
+        assert_eq!(check(b"Zm8"), Some(b"fo".to_vec()));
+        assert_eq!(check(b"Zg="), None);
+        assert_eq!(check(b"Z"), None);
+        assert_eq!(check(b"+/+/"), None);
+    }
+
+    #[test]
+    fn test_decode_into_small_buffer() {
+        let mut out = [0u8; 2];
+        assert_eq!(decode(b"Zm9v", &mut out), None);
+    }
+}
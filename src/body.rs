@@ -0,0 +1,778 @@
+//! Message body framing.
+//!
+//! `BodyLength` determines how a message body is delimited from the parsed
+//! header fields, and `BodyDecoder` finds the body data in a buffer without
+//! doing any I/O, decoding the chunked transfer coding if necessary.
+//! `BodyEncoder` frames outgoing body data in the same way.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::body::{BodyDecoder, BodyLength, Chunk};
+//!
+//! let headers = [thhp::HeaderField { name: "Transfer-Encoding", value: "chunked" }];
+//! let length = BodyLength::of_request(&headers).unwrap();
+//! assert_eq!(length, BodyLength::Chunked);
+//!
+//! let buf = b"5\r\nhello\r\n0\r\n\r\n";
+//! let mut decoder = BodyDecoder::new(length);
+//! let (len, chunk) = decoder.decode(buf).unwrap().unwrap();
+//! assert_eq!(chunk, Chunk::Data(3..8));
+//! let (_, chunk) = decoder.decode(&buf[len..]).unwrap().unwrap();
+//! assert_eq!(chunk, Chunk::End(5..7));
+//! assert!(decoder.is_done());
+//! ```
+use std::ops::Range;
+
+use crate::chunked;
+use crate::errors::*;
+use crate::fields::{find_all, parse_u64, trim_ows, ListIter};
+use crate::{
+    Complete, Discard, HeaderField, HeaderFieldCollection, HttpPartParser, Incomplete, Status,
+};
+
+/// Limits on the size of a message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The maximum length of a message head, or of a chunk size line with
+    /// trailer fields.
+    pub max_head_len: usize,
+    /// The maximum number of header fields.
+    pub max_headers: usize,
+    /// The maximum length of a message body.
+    pub max_body_len: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_head_len: 8 * 1024,
+            max_headers: 100,
+            max_body_len: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// How a message body is delimited.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum BodyLength {
+    /// The body has a fixed length. There is no body if it is zero.
+    Length(u64),
+    /// The body is in the chunked transfer coding.
+    Chunked,
+    /// The body continues until the connection is closed.
+    CloseDelimited,
+}
+
+impl BodyLength {
+    /// Determines the body length of a request.
+    ///
+    /// A request with both `Transfer-Encoding` and `Content-Length` is
+    /// rejected with `InvalidContentLength`, since recipients may disagree
+    /// on where it ends.
+    pub fn of_request(headers: &[HeaderField]) -> Result<BodyLength> {
+        let chunked = transfer_encoding(headers)?;
+        if chunked.is_some() && find_all(headers, "Content-Length").next().is_some() {
+            return Err(InvalidContentLength);
+        }
+        match chunked {
+            Some(true) => Ok(BodyLength::Chunked),
+            Some(false) => Err(InvalidTransferEncoding),
+            None => Ok(BodyLength::Length(content_length(headers)?.unwrap_or(0))),
+        }
+    }
+
+    /// Determines the body length of a response to a request with `method`.
+    pub fn of_response(method: &str, status: u16, headers: &[HeaderField]) -> Result<BodyLength> {
+        if method == "HEAD"
+            || (100..200).contains(&status)
+            || status == 204
+            || status == 304
+            || (method == "CONNECT" && (200..300).contains(&status))
+        {
+            return Ok(BodyLength::Length(0));
+        }
+        match transfer_encoding(headers)? {
+            Some(true) => Ok(BodyLength::Chunked),
+            Some(false) => Ok(BodyLength::CloseDelimited),
+            None => {
+                Ok(content_length(headers)?.map_or(BodyLength::CloseDelimited, BodyLength::Length))
+            }
+        }
+    }
+}
+
+// Returns whether the final transfer coding is chunked, or `None` without
+// `Transfer-Encoding`.
+fn transfer_encoding(headers: &[HeaderField]) -> Result<Option<bool>> {
+    let mut chunked = None;
+    for value in find_all(headers, "Transfer-Encoding") {
+        for coding in ListIter::new(value) {
+            if chunked == Some(true) {
+                // chunked must not be applied more than once or followed
+                // by another coding.
+                return Err(InvalidTransferEncoding);
+            }
+            let name = trim_ows(coding.split(';').next().unwrap_or(""));
+            chunked = Some(name.eq_ignore_ascii_case("chunked"));
+        }
+    }
+    Ok(chunked)
+}
+
+// Returns the content length, allowing repeated identical values.
+fn content_length(headers: &[HeaderField]) -> Result<Option<u64>> {
+    let mut length = None;
+    for value in find_all(headers, "Content-Length") {
+        let mut empty = true;
+        for elem in ListIter::new(value) {
+            let v = parse_u64(elem).ok_or(InvalidContentLength)?;
+            if length.is_some() && length != Some(v) {
+                return Err(InvalidContentLength);
+            }
+            length = Some(v);
+            empty = false;
+        }
+        if empty {
+            return Err(InvalidContentLength);
+        }
+    }
+    Ok(length)
+}
+
+/// A part of a body found by a decoder.
+///
+/// Ranges are relative to the buffer passed to the decoder.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Chunk {
+    /// Body data.
+    Data(Range<usize>),
+    /// The end of the body with the trailer section, which is empty unless
+    /// the body is chunked. It is parsed by `parse_trailers`.
+    End(Range<usize>),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ChunkedState {
+    Size,
+    Data(u64),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// A decoder of the chunked transfer coding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkedDecoder {
+    state: ChunkedState,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> ChunkedDecoder {
+        ChunkedDecoder::new()
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> ChunkedDecoder {
+        ChunkedDecoder {
+            state: ChunkedState::Size,
+        }
+    }
+
+    /// Returns `true` if the last chunk and trailer section are decoded.
+    pub fn is_done(&self) -> bool {
+        self.state == ChunkedState::Done
+    }
+
+    /// Decodes the next part from the start of `buf`.
+    ///
+    /// Returns the number of bytes consumed with the part found, or
+    /// `Incomplete` if more data is needed, in which case nothing is
+    /// consumed. Data of a chunk may be returned in multiple parts.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<Status<(usize, Chunk)>> {
+        self.decode_part(buf, false)
+    }
+
+    // Decodes like `decode`, but returns framing consumed before more data is
+    // needed as an empty data part, so the framing does not have to be kept
+    // in `buf`.
+    #[cfg(feature = "std")]
+    pub(crate) fn decode_partial(&mut self, buf: &[u8]) -> Result<Status<(usize, Chunk)>> {
+        self.decode_part(buf, true)
+    }
+
+    fn decode_part(&mut self, buf: &[u8], partial: bool) -> Result<Status<(usize, Chunk)>> {
+        let mut pos = 0;
+        let mut state = self.state;
+        loop {
+            match state {
+                ChunkedState::Size => {
+                    let (len, size) = match parse_chunk_size(&buf[pos..])? {
+                        Complete(v) => v,
+                        Incomplete => return Ok(self.progress(pos, state, partial)),
+                    };
+                    pos += len;
+                    state = if size == 0 {
+                        ChunkedState::Trailers
+                    } else {
+                        ChunkedState::Data(size)
+                    };
+                }
+                ChunkedState::Data(remaining) => {
+                    let available = buf.len() - pos;
+                    if available == 0 {
+                        return Ok(self.progress(pos, state, partial));
+                    }
+                    let len = remaining.min(available as u64) as usize;
+                    self.state = if len as u64 == remaining {
+                        ChunkedState::DataEnd
+                    } else {
+                        ChunkedState::Data(remaining - len as u64)
+                    };
+                    return Ok(Complete((pos + len, Chunk::Data(pos..pos + len))));
+                }
+                ChunkedState::DataEnd => {
+                    match (buf.get(pos), buf.get(pos + 1)) {
+                        (Some(&b'\r'), Some(&b'\n')) => pos += 2,
+                        (Some(&b'\n'), _) => pos += 1,
+                        (Some(&b'\r'), None) | (None, _) => {
+                            return Ok(self.progress(pos, state, partial))
+                        }
+                        _ => return Err(InvalidChunkSize),
+                    }
+                    state = ChunkedState::Size;
+                }
+                ChunkedState::Trailers => {
+                    let mut trailers = Discard::new();
+                    let len = match parse_trailers(&buf[pos..], &mut trailers)? {
+                        Complete((_, len)) => len,
+                        Incomplete => return Ok(self.progress(pos, state, partial)),
+                    };
+                    self.state = ChunkedState::Done;
+                    return Ok(Complete((pos + len, Chunk::End(pos..pos + len))));
+                }
+                ChunkedState::Done => return Ok(Complete((0, Chunk::End(0..0)))),
+            }
+        }
+    }
+
+    // Commits the framing consumed before more data is needed if `partial`.
+    fn progress(
+        &mut self,
+        pos: usize,
+        state: ChunkedState,
+        partial: bool,
+    ) -> Status<(usize, Chunk)> {
+        if pos == 0 || !partial {
+            Incomplete
+        } else {
+            self.state = state;
+            Complete((pos, Chunk::Data(pos..pos)))
+        }
+    }
+}
+
+// Parses a chunk size line, returning its length and the chunk size.
+fn parse_chunk_size(buf: &[u8]) -> Result<Status<(usize, u64)>> {
+    let mut size: u64 = 0;
+    let mut i = 0;
+    while i < buf.len() {
+        let d = match buf[i] {
+            c @ b'0'..=b'9' => c - b'0',
+            c @ b'a'..=b'f' => c - b'a' + 10,
+            c @ b'A'..=b'F' => c - b'A' + 10,
+            _ => break,
+        };
+        if size >> 60 != 0 {
+            return Err(InvalidChunkSize);
+        }
+        size = size << 4 | u64::from(d);
+        i += 1;
+    }
+    if i == buf.len() {
+        return Ok(Incomplete);
+    }
+    if i == 0 {
+        return Err(InvalidChunkSize);
+    }
+    match buf[i] {
+        b';' | b' ' | b'\t' | b'\r' | b'\n' => (),
+        _ => return Err(InvalidChunkSize),
+    }
+    // Chunk extensions are ignored.
+    while i < buf.len() {
+        match buf[i] {
+            b'\r' => {
+                return match buf.get(i + 1) {
+                    Some(&b'\n') => Ok(Complete((i + 2, size))),
+                    Some(_) => Err(InvalidNewLine),
+                    None => Ok(Incomplete),
+                };
+            }
+            b'\n' => return Ok(Complete((i + 1, size))),
+            c if crate::is_field_value_char(c) => i += 1,
+            _ => return Err(InvalidChunkExtension),
+        }
+    }
+    Ok(Incomplete)
+}
+
+/// Parses a trailer section ending with an empty line, pushing trailer
+/// fields into `headers`.
+///
+/// Returns the trailer fields and the length of the section.
+pub fn parse_trailers<'headers, 'buffer, Headers>(
+    buf: &'buffer [u8],
+    headers: &'headers mut Headers,
+) -> Result<Status<(&'headers [HeaderField<'buffer>], usize)>>
+where
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    let mut parser = HttpPartParser::new(buf);
+    let headers = match parser.parse_headers(headers)? {
+        Complete(headers) => headers,
+        Incomplete => return Ok(Incomplete),
+    };
+    Ok(Complete((headers, buf.len() - parser.len())))
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum DecoderKind {
+    Length(u64),
+    Chunked(ChunkedDecoder),
+    CloseDelimited,
+    Done,
+}
+
+/// A decoder of a message body.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BodyDecoder {
+    kind: DecoderKind,
+}
+
+impl BodyDecoder {
+    pub fn new(length: BodyLength) -> BodyDecoder {
+        let kind = match length {
+            BodyLength::Length(len) => DecoderKind::Length(len),
+            BodyLength::Chunked => DecoderKind::Chunked(ChunkedDecoder::new()),
+            BodyLength::CloseDelimited => DecoderKind::CloseDelimited,
+        };
+        BodyDecoder { kind }
+    }
+
+    /// Returns `true` if the end of the body is decoded.
+    pub fn is_done(&self) -> bool {
+        match self.kind {
+            DecoderKind::Chunked(ref decoder) => decoder.is_done(),
+            DecoderKind::Done => true,
+            _ => false,
+        }
+    }
+
+    /// Decodes the next part from the start of `buf`.
+    ///
+    /// Returns the number of bytes consumed with the part found, or
+    /// `Incomplete` if more data is needed, in which case nothing is
+    /// consumed. Once the end is decoded, `Chunk::End` is returned without
+    /// consuming anything.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<Status<(usize, Chunk)>> {
+        match self.kind {
+            DecoderKind::Length(0) => {
+                self.kind = DecoderKind::Done;
+                Ok(Complete((0, Chunk::End(0..0))))
+            }
+            DecoderKind::Length(remaining) => {
+                if buf.is_empty() {
+                    return Ok(Incomplete);
+                }
+                let len = remaining.min(buf.len() as u64) as usize;
+                self.kind = DecoderKind::Length(remaining - len as u64);
+                Ok(Complete((len, Chunk::Data(0..len))))
+            }
+            DecoderKind::Chunked(ref mut decoder) => decoder.decode(buf),
+            DecoderKind::CloseDelimited => {
+                if buf.is_empty() {
+                    Ok(Incomplete)
+                } else {
+                    Ok(Complete((buf.len(), Chunk::Data(0..buf.len()))))
+                }
+            }
+            DecoderKind::Done => Ok(Complete((0, Chunk::End(0..0)))),
+        }
+    }
+
+    // Decodes like `decode`, but returns framing of the chunked transfer
+    // coding consumed before more data is needed as an empty data part.
+    #[cfg(feature = "std")]
+    pub(crate) fn decode_partial(&mut self, buf: &[u8]) -> Result<Status<(usize, Chunk)>> {
+        match self.kind {
+            DecoderKind::Chunked(ref mut decoder) => decoder.decode_partial(buf),
+            _ => self.decode(buf),
+        }
+    }
+
+    /// Notifies the decoder that the connection is closed.
+    ///
+    /// Returns `true` if the body ends there, that is, it is close-delimited
+    /// or already done. Otherwise the body is truncated.
+    pub fn eof(&mut self) -> bool {
+        if self.kind == DecoderKind::CloseDelimited {
+            self.kind = DecoderKind::Done;
+        }
+        self.is_done()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum EncoderKind {
+    Length(u64),
+    Chunked,
+    CloseDelimited,
+}
+
+/// An encoder of a message body.
+///
+/// Data is framed as given by the `BodyLength` of the message, and the
+/// framed bytes are passed to a function writing them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BodyEncoder {
+    kind: EncoderKind,
+}
+
+impl BodyEncoder {
+    pub fn new(length: BodyLength) -> BodyEncoder {
+        let kind = match length {
+            BodyLength::Length(len) => EncoderKind::Length(len),
+            BodyLength::Chunked => EncoderKind::Chunked,
+            BodyLength::CloseDelimited => EncoderKind::CloseDelimited,
+        };
+        BodyEncoder { kind }
+    }
+
+    /// Encodes body data, passing the bytes to write to `put`.
+    ///
+    /// Returns `InvalidContentLength` if the data exceeds the length of the
+    /// body, in which case nothing is written.
+    pub fn encode_data<F>(&mut self, data: &[u8], mut put: F) -> Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        match self.kind {
+            EncoderKind::Length(ref mut remaining) => {
+                if data.len() as u64 > *remaining {
+                    return Err(InvalidContentLength);
+                }
+                *remaining -= data.len() as u64;
+                put(data);
+            }
+            // An empty chunk would be the last chunk.
+            EncoderKind::Chunked if data.is_empty() => (),
+            EncoderKind::Chunked => {
+                let mut header = [0u8; 18];
+                let len = chunked::encode_chunk_header(data.len(), &[], &mut header)?;
+                put(&header[..len]);
+                put(data);
+                put(b"\r\n");
+            }
+            EncoderKind::CloseDelimited => put(data),
+        }
+        Ok(())
+    }
+
+    /// Encodes the end of the body, passing the bytes to write to `put`.
+    ///
+    /// Returns `InvalidContentLength` if less data than the length of the
+    /// body is encoded.
+    pub fn encode_end<F>(self, mut put: F) -> Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        match self.kind {
+            EncoderKind::Length(0) | EncoderKind::CloseDelimited => Ok(()),
+            EncoderKind::Length(_) => Err(InvalidContentLength),
+            EncoderKind::Chunked => {
+                put(b"0\r\n\r\n");
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::body::*;
+    use crate::field;
+
+    #[test]
+    fn test_request_length() {
+        assert_eq!(BodyLength::of_request(&[]), Ok(BodyLength::Length(0)));
+        assert_eq!(
+            BodyLength::of_request(&[field("content-length", "10")]),
+            Ok(BodyLength::Length(10))
+        );
+        assert_eq!(
+            BodyLength::of_request(&[
+                field("Content-Length", "10, 10"),
+                field("Content-Length", "10")
+            ]),
+            Ok(BodyLength::Length(10))
+        );
+        assert_eq!(
+            BodyLength::of_request(&[field("Transfer-Encoding", "gzip, Chunked")]),
+            Ok(BodyLength::Chunked)
+        );
+    }
+
+    #[test]
+    fn test_request_length_errors() {
+        let invalid = [
+            ("Content-Length", "10, 11"),
+            ("Content-Length", "-1"),
+            ("Content-Length", ""),
+            ("Content-Length", "99999999999999999999"),
+        ];
+        for &(name, value) in invalid.iter() {
+            assert_eq!(
+                BodyLength::of_request(&[field(name, value)]),
+                Err(InvalidContentLength)
+            );
+        }
+        let invalid = ["gzip", "chunked, gzip", "chunked, chunked"];
+        for value in invalid.iter() {
+            assert_eq!(
+                BodyLength::of_request(&[field("Transfer-Encoding", value)]),
+                Err(InvalidTransferEncoding)
+            );
+        }
+        assert_eq!(
+            BodyLength::of_request(&[
+                field("Transfer-Encoding", "chunked"),
+                field("Transfer-Encoding", "chunked")
+            ]),
+            Err(InvalidTransferEncoding)
+        );
+        assert_eq!(
+            BodyLength::of_request(&[
+                field("Content-Length", "10"),
+                field("Transfer-Encoding", "chunked")
+            ]),
+            Err(InvalidContentLength)
+        );
+    }
+
+    #[test]
+    fn test_response_length() {
+        let cl = [field("Content-Length", "10")];
+        assert_eq!(
+            BodyLength::of_response("GET", 200, &cl),
+            Ok(BodyLength::Length(10))
+        );
+        assert_eq!(
+            BodyLength::of_response("HEAD", 200, &cl),
+            Ok(BodyLength::Length(0))
+        );
+        for &status in [100, 101, 204, 304].iter() {
+            assert_eq!(
+                BodyLength::of_response("GET", status, &cl),
+                Ok(BodyLength::Length(0))
+            );
+        }
+        assert_eq!(
+            BodyLength::of_response("CONNECT", 200, &[]),
+            Ok(BodyLength::Length(0))
+        );
+        assert_eq!(
+            BodyLength::of_response("GET", 200, &[]),
+            Ok(BodyLength::CloseDelimited)
+        );
+        assert_eq!(
+            BodyLength::of_response("GET", 200, &[field("Transfer-Encoding", "gzip")]),
+            Ok(BodyLength::CloseDelimited)
+        );
+        assert_eq!(
+            BodyLength::of_response("GET", 200, &[field("Transfer-Encoding", "chunked")]),
+            Ok(BodyLength::Chunked)
+        );
+    }
+
+    fn decode_all(decoder: &mut BodyDecoder, buf: &[u8]) -> Result<(Vec<u8>, usize)> {
+        let mut data = Vec::new();
+        let mut pos = 0;
+        loop {
+            match decoder.decode(&buf[pos..])? {
+                Complete((len, Chunk::Data(range))) => {
+                    data.extend_from_slice(&buf[pos + range.start..pos + range.end]);
+                    pos += len;
+                }
+                Complete((len, Chunk::End(_))) => return Ok((data, pos + len)),
+                Incomplete => return Err(BufferTooSmall),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_length() {
+        let mut decoder = BodyDecoder::new(BodyLength::Length(5));
+        assert_eq!(decoder.decode(b""), Ok(Incomplete));
+        assert_eq!(decoder.decode(b"hel"), Ok(Complete((3, Chunk::Data(0..3)))));
+        assert_eq!(
+            decoder.decode(b"loGET"),
+            Ok(Complete((2, Chunk::Data(0..2))))
+        );
+        assert!(!decoder.is_done());
+        assert_eq!(decoder.decode(b"GET"), Ok(Complete((0, Chunk::End(0..0)))));
+        assert!(decoder.is_done());
+
+        let mut decoder = BodyDecoder::new(BodyLength::Length(0));
+        assert_eq!(decoder.decode(b""), Ok(Complete((0, Chunk::End(0..0)))));
+        assert!(!BodyDecoder::new(BodyLength::Length(1)).eof());
+    }
+
+    #[test]
+    fn test_decode_close_delimited() {
+        let mut decoder = BodyDecoder::new(BodyLength::CloseDelimited);
+        assert_eq!(decoder.decode(b"abc"), Ok(Complete((3, Chunk::Data(0..3)))));
+        assert_eq!(decoder.decode(b""), Ok(Incomplete));
+        assert!(decoder.eof());
+        assert_eq!(decoder.decode(b""), Ok(Complete((0, Chunk::End(0..0)))));
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let buf = b"5;a=b\r\nhello\r\nA\r\n, world!!!\r\n0\r\nExpires: 0\r\n\r\nGET";
+        let mut decoder = BodyDecoder::new(BodyLength::Chunked);
+        let (data, len) = decode_all(&mut decoder, buf).unwrap();
+        assert_eq!(data, b"hello, world!!!");
+        assert_eq!(&buf[len..], b"GET");
+        assert!(decoder.is_done());
+
+        // Every split point works.
+        for i in 0..buf.len() - 3 {
+            let mut decoder = BodyDecoder::new(BodyLength::Chunked);
+            let mut data = Vec::new();
+            let mut pos = 0;
+            let mut end = i;
+            loop {
+                match decoder.decode(&buf[pos..end]).unwrap() {
+                    Complete((len, Chunk::Data(range))) => {
+                        data.extend_from_slice(&buf[pos + range.start..pos + range.end]);
+                        pos += len;
+                    }
+                    Complete((len, Chunk::End(_))) => {
+                        pos += len;
+                        break;
+                    }
+                    Incomplete => end += 1,
+                }
+            }
+            assert_eq!(data, b"hello, world!!!");
+            assert_eq!(&buf[pos..], b"GET");
+        }
+    }
+
+    #[test]
+    fn test_decode_chunked_trailers() {
+        let buf = b"0\nA: b\r\nC: d\n\n";
+        let mut decoder = ChunkedDecoder::new();
+        let (len, chunk) = decoder.decode(buf).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        let range = match chunk {
+            Chunk::End(range) => range,
+            Chunk::Data(_) => panic!(),
+        };
+
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let (trailers, len) = parse_trailers(&buf[range.clone()], &mut headers)
+            .unwrap()
+            .unwrap();
+        assert_eq!(len, range.len());
+        assert_eq!(trailers, [field("A", "b"), field("C", "d")]);
+    }
+
+    #[test]
+    fn test_decode_chunked_errors() {
+        let invalid: [(&[u8], Error); 6] = [
+            (b"\r\n", InvalidChunkSize),
+            (b"x\r\n", InvalidChunkSize),
+            (b"1x\r\n", InvalidChunkSize),
+            (b"10000000000000000\r\n", InvalidChunkSize),
+            (b"1;\x01\r\n", InvalidChunkExtension),
+            (b"1\r\nab", InvalidChunkSize),
+        ];
+        for &(buf, err) in invalid.iter() {
+            let mut decoder = ChunkedDecoder::new();
+            let result = decoder.decode(buf).and_then(|status| match status {
+                Complete((len, _)) => decoder.decode(&buf[len..]),
+                Incomplete => Ok(Incomplete),
+            });
+            assert_eq!(result, Err(err));
+        }
+
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(decoder.decode(b"0\r\nA b\r\n\r\n"), Err(InvalidFieldName));
+        assert_eq!(decoder.decode(b"ffffffffffffffff\r\n"), Ok(Incomplete));
+    }
+
+    fn encode(length: BodyLength, parts: &[&[u8]]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut encoder = BodyEncoder::new(length);
+        for part in parts {
+            encoder.encode_data(part, |b| out.extend_from_slice(b))?;
+        }
+        encoder.encode_end(|b| out.extend_from_slice(b))?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode(BodyLength::Length(5), &[b"hel", b"", b"lo"]),
+            Ok(b"hello".to_vec())
+        );
+        assert_eq!(
+            encode(BodyLength::Chunked, &[b"hello", b"", b", world!!!"]),
+            Ok(b"5\r\nhello\r\na\r\n, world!!!\r\n0\r\n\r\n".to_vec())
+        );
+        assert_eq!(
+            encode(BodyLength::CloseDelimited, &[b"hello"]),
+            Ok(b"hello".to_vec())
+        );
+        assert_eq!(
+            encode(BodyLength::Length(2), &[b"abc"]),
+            Err(InvalidContentLength)
+        );
+        assert_eq!(
+            encode(BodyLength::Length(2), &[b"a"]),
+            Err(InvalidContentLength)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_decode_chunked_partial() {
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(decoder.decode_partial(b"5\r"), Ok(Incomplete));
+        assert_eq!(
+            decoder.decode_partial(b"5\r\n"),
+            Ok(Complete((3, Chunk::Data(3..3))))
+        );
+        assert_eq!(
+            decoder.decode_partial(b"hello\r"),
+            Ok(Complete((5, Chunk::Data(0..5))))
+        );
+        assert_eq!(decoder.decode_partial(b"\r"), Ok(Incomplete));
+        assert_eq!(
+            decoder.decode_partial(b"\r\n0\r\n"),
+            Ok(Complete((5, Chunk::Data(5..5))))
+        );
+        assert_eq!(
+            decoder.decode_partial(b"\r\n"),
+            Ok(Complete((2, Chunk::End(0..2))))
+        );
+        assert!(decoder.is_done());
+    }
+}
@@ -0,0 +1,353 @@
+//! Parsing over `bytes::BytesMut`.
+//!
+//! The parsed header block is split off the buffer and frozen, and the
+//! method, target, reason phrase and header fields are `Bytes` sharing that
+//! allocation. Parsed messages own their data without copying, so they can
+//! outlive the buffer and be sent across threads. The rest of the message
+//! stays in the `BytesMut`.
+//!
+//! ## Example
+//!
+//! ```
+//! extern crate bytes;
+//! extern crate thhp;
+//!
+//! use bytes::BytesMut;
+//! use thhp::bytes_compat::BytesRequest;
+//!
+//! let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\nbody"[..]);
+//! let req = BytesRequest::parse(&mut buf, 16).unwrap().unwrap();
+//! assert_eq!(req.method(), "GET");
+//! assert_eq!(req.headers()[0].value(), "example.com");
+//! assert_eq!(&buf[..], b"body");
+//! ```
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use std::ops::Range;
+use std::str;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::errors::*;
+use crate::spans::{HeaderFieldSpan, RequestSpans, ResponseSpans};
+use crate::{Complete, HeaderField, HeaderFieldCollection, Incomplete, Request, Response, Status};
+
+#[inline]
+fn slice(buf: &Bytes, span: &Range<u32>) -> Bytes {
+    buf.slice(span.start as usize..span.end as usize)
+}
+
+// The parser has validated every part, so they are valid UTF-8.
+#[inline]
+fn as_str(bytes: &Bytes) -> &str {
+    unsafe { str::from_utf8_unchecked(bytes) }
+}
+
+fn to_fields(buf: &Bytes, spans: &[HeaderFieldSpan]) -> Vec<BytesHeaderField> {
+    spans
+        .iter()
+        .map(|span| BytesHeaderField {
+            name: slice(buf, &span.name),
+            value: slice(buf, &span.value),
+        })
+        .collect()
+}
+
+fn push_fields<'buffer, Headers>(
+    fields: &'buffer [BytesHeaderField],
+    headers: &mut Headers,
+) -> Result<()>
+where
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    for field in fields {
+        headers.push(field.as_header_field())?;
+    }
+    Ok(())
+}
+
+/// A header field sharing the parsed buffer.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BytesHeaderField {
+    name: Bytes,
+    value: Bytes,
+}
+
+impl BytesHeaderField {
+    /// Returns the field name.
+    pub fn name(&self) -> &str {
+        as_str(&self.name)
+    }
+
+    /// Returns the field value.
+    pub fn value(&self) -> &str {
+        as_str(&self.value)
+    }
+
+    /// Returns the field name as `Bytes`.
+    pub fn name_bytes(&self) -> &Bytes {
+        &self.name
+    }
+
+    /// Returns the field value as `Bytes`.
+    pub fn value_bytes(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// Returns a borrowed header field.
+    pub fn as_header_field(&self) -> HeaderField<'_> {
+        HeaderField {
+            name: self.name(),
+            value: self.value(),
+        }
+    }
+}
+
+/// A request sharing the parsed buffer.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BytesRequest {
+    method: Bytes,
+    target: Bytes,
+    minor_version: u8,
+    headers: Vec<BytesHeaderField>,
+}
+
+impl BytesRequest {
+    /// Parse the buffer as http request.
+    ///
+    /// On success, the request line and header fields are removed from
+    /// `buf`. Otherwise `buf` is left untouched. At most `max_headers`
+    /// header fields are accepted.
+    pub fn parse(buf: &mut BytesMut, max_headers: usize) -> Result<Status<BytesRequest>> {
+        let mut spans = Vec::<HeaderFieldSpan>::with_capacity(max_headers);
+        let (req, len) = match RequestSpans::parse(buf, &mut spans)? {
+            Complete(v) => v,
+            Incomplete => return Ok(Incomplete),
+        };
+        let head = buf.split_to(len).freeze();
+        Ok(Complete(BytesRequest {
+            method: slice(&head, &req.method),
+            target: slice(&head, &req.target),
+            minor_version: req.minor_version,
+            headers: to_fields(&head, req.headers),
+        }))
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &str {
+        as_str(&self.method)
+    }
+
+    /// Returns the request target.
+    pub fn target(&self) -> &str {
+        as_str(&self.target)
+    }
+
+    /// Returns the http minor version.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns the header fields.
+    pub fn headers(&self) -> &[BytesHeaderField] {
+        &self.headers
+    }
+
+    /// Returns the request method as `Bytes`.
+    pub fn method_bytes(&self) -> &Bytes {
+        &self.method
+    }
+
+    /// Returns the request target as `Bytes`.
+    pub fn target_bytes(&self) -> &Bytes {
+        &self.target
+    }
+
+    /// Returns a borrowed request, pushing header fields into `headers`.
+    pub fn as_request<'headers, 'buffer, Headers>(
+        &'buffer self,
+        headers: &'headers mut Headers,
+    ) -> Result<Request<'headers, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        push_fields(&self.headers, headers)?;
+        Ok(Request {
+            method: self.method(),
+            target: self.target(),
+            minor_version: self.minor_version,
+            headers,
+        })
+    }
+}
+
+/// A response sharing the parsed buffer.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BytesResponse {
+    minor_version: u8,
+    status: u16,
+    reason: Bytes,
+    headers: Vec<BytesHeaderField>,
+}
+
+impl BytesResponse {
+    /// Parse the buffer as http response.
+    ///
+    /// On success, the status line and header fields are removed from
+    /// `buf`. Otherwise `buf` is left untouched. At most `max_headers`
+    /// header fields are accepted.
+    pub fn parse(buf: &mut BytesMut, max_headers: usize) -> Result<Status<BytesResponse>> {
+        let mut spans = Vec::<HeaderFieldSpan>::with_capacity(max_headers);
+        let (res, len) = match ResponseSpans::parse(buf, &mut spans)? {
+            Complete(v) => v,
+            Incomplete => return Ok(Incomplete),
+        };
+        let head = buf.split_to(len).freeze();
+        Ok(Complete(BytesResponse {
+            minor_version: res.minor_version,
+            status: res.status,
+            reason: slice(&head, &res.reason),
+            headers: to_fields(&head, res.headers),
+        }))
+    }
+
+    /// Returns the http minor version.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase.
+    pub fn reason(&self) -> &str {
+        as_str(&self.reason)
+    }
+
+    /// Returns the header fields.
+    pub fn headers(&self) -> &[BytesHeaderField] {
+        &self.headers
+    }
+
+    /// Returns the reason phrase as `Bytes`.
+    pub fn reason_bytes(&self) -> &Bytes {
+        &self.reason
+    }
+
+    /// Returns a borrowed response, pushing header fields into `headers`.
+    pub fn as_response<'headers, 'buffer, Headers>(
+        &'buffer self,
+        headers: &'headers mut Headers,
+    ) -> Result<Response<'headers, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        push_fields(&self.headers, headers)?;
+        Ok(Response {
+            minor_version: self.minor_version,
+            status: self.status,
+            reason: self.reason(),
+            headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use bytes::BytesMut;
+
+    use crate::bytes_compat::*;
+
+    #[test]
+    fn test_parse_request() {
+        let mut buf = BytesMut::from(
+            &b"POST /a HTTP/1.1\r\nHost: example.com\r\nContent-Length: 4\r\n\r\nbodyGET"[..],
+        );
+        let start = buf.as_ptr() as usize;
+        let req = BytesRequest::parse(&mut buf, 4).unwrap().unwrap();
+
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.target(), "/a");
+        assert_eq!(req.minor_version(), 1);
+        assert_eq!(req.headers().len(), 2);
+        assert_eq!(req.headers()[1].name(), "Content-Length");
+        assert_eq!(req.headers()[1].value(), "4");
+        assert_eq!(&buf[..], b"bodyGET");
+
+        // Parts are sub-slices of the original allocation.
+        assert_eq!(req.method_bytes().as_ptr() as usize, start);
+        assert_eq!(req.target_bytes().as_ptr() as usize, start + 5);
+        assert_eq!(req.headers()[0].value_bytes().as_ptr() as usize, start + 24);
+
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = req.as_request(&mut headers).unwrap();
+        assert_eq!(borrowed.method, "POST");
+        assert_eq!(borrowed.headers[0].value, "example.com");
+
+        let mut headers = Vec::<HeaderField>::with_capacity(1);
+        assert_eq!(req.as_request(&mut headers), Err(OutOfCapacity));
+    }
+
+    #[test]
+    fn test_parse_request_keeps_buffer() {
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nHost: a\r\n"[..]);
+        assert_eq!(BytesRequest::parse(&mut buf, 4), Ok(Incomplete));
+        assert_eq!(buf.len(), 25);
+
+        buf.extend_from_slice(b"A: b\r\n\r\n");
+        assert_eq!(BytesRequest::parse(&mut buf, 1), Err(OutOfCapacity));
+        assert_eq!(buf.len(), 33);
+
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+        let req = BytesRequest::parse(&mut buf, 0).unwrap().unwrap();
+        assert!(req.headers().is_empty());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut buf =
+            BytesMut::from(&b"HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\nHTTP/1.0"[..]);
+        let res = BytesResponse::parse(&mut buf, 4).unwrap().unwrap();
+
+        assert_eq!(res.minor_version(), 0);
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.reason(), "Not Found");
+        assert_eq!(res.reason_bytes(), "Not Found");
+        assert_eq!(res.headers()[0].name_bytes(), "Content-Length");
+        assert_eq!(&buf[..], b"HTTP/1.0");
+
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = res.as_response(&mut headers).unwrap();
+        assert_eq!(borrowed.reason, "Not Found");
+        assert_eq!(borrowed.headers[0].value, "0");
+
+        assert_eq!(BytesResponse::parse(&mut buf, 4), Ok(Incomplete));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nHo st: a\r\n\r\n"[..]);
+        assert_eq!(BytesRequest::parse(&mut buf, 4), Err(InvalidFieldName));
+        assert_eq!(buf.len(), 28);
+    }
+
+    #[test]
+    fn test_outlives_buffer() {
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<BytesRequest>();
+        assert_send::<BytesResponse>();
+
+        let req = {
+            let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"[..]);
+            BytesRequest::parse(&mut buf, 4).unwrap().unwrap()
+        };
+        assert_eq!(req.headers()[0].value(), "a");
+    }
+}
@@ -0,0 +1,663 @@
+//! HTTP caching.
+//!
+//! Parses `Cache-Control`, `Expires`, `Age`, `Date` and `Vary` header fields
+//! and calculates storability, freshness and age of responses as specified
+//! in RFC 9111.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::cache;
+//! use thhp::date::HttpDate;
+//!
+//! let buf = b"HTTP/1.1 200 OK\r\n\
+//!             Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n\
+//!             Cache-Control: max-age=60, s-maxage=600\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (res, _) = thhp::Response::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! assert_eq!(cache::freshness_lifetime(&res, false), Some(60));
+//! assert_eq!(cache::freshness_lifetime(&res, true), Some(600));
+//!
+//! let received = HttpDate::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+//! let now = HttpDate::from_unix_secs(received.unix_secs() + 30);
+//! assert_eq!(cache::current_age(&res, received, received, now), 30);
+//! ```
+use std::cmp;
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::date::HttpDate;
+use crate::fields;
+use crate::{HeaderField, Request, Response};
+
+/// A directive of `Cache-Control` header field.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Directive<'buffer> {
+    /// The directive name.
+    pub name: &'buffer str,
+    /// The directive argument, quotes of a quoted-string are removed.
+    pub value: Option<&'buffer str>,
+}
+
+impl<'buffer> Directive<'buffer> {
+    /// Returns `true` if the directive name is `name`, ignoring ASCII case.
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// Parses the argument as delta-seconds.
+    ///
+    /// Too large values are saturated.
+    pub fn seconds(&self) -> Option<u64> {
+        let value = self.value?;
+        if value.is_empty() || !value.bytes().all(crate::is_digit) {
+            return None;
+        }
+        Some(fields::parse_u64(value).unwrap_or(u64::MAX))
+    }
+}
+
+/// An iterator over directives of `Cache-Control` header fields.
+///
+/// Malformed directives are skipped.
+pub struct Directives<'a, 'buffer: 'a> {
+    values: &'a [HeaderField<'buffer>],
+    list: fields::ListIter<'buffer>,
+}
+
+impl<'a, 'buffer: 'a> Directives<'a, 'buffer> {
+    /// Iterates directives of all `Cache-Control` header fields.
+    pub fn new(headers: &'a [HeaderField<'buffer>]) -> Directives<'a, 'buffer> {
+        Directives {
+            values: headers,
+            list: fields::ListIter::new(""),
+        }
+    }
+}
+
+impl<'a, 'buffer: 'a> Iterator for Directives<'a, 'buffer> {
+    type Item = Directive<'buffer>;
+
+    fn next(&mut self) -> Option<Directive<'buffer>> {
+        loop {
+            if let Some(elem) = self.list.next() {
+                if let Some(d) = parse_directive(elem) {
+                    return Some(d);
+                }
+                continue;
+            }
+
+            let (first, rest) = self.values.split_first()?;
+            self.values = rest;
+            if first.name.eq_ignore_ascii_case("cache-control") {
+                self.list = fields::ListIter::new(first.value);
+            }
+        }
+    }
+}
+
+fn parse_directive(s: &str) -> Option<Directive<'_>> {
+    let (name, value) = match s.find('=') {
+        Some(eq) => (&s[..eq], Some(&s[eq + 1..])),
+        None => (s, None),
+    };
+    if !fields::is_token(name) {
+        return None;
+    }
+
+    let value = match value {
+        Some(v) if v.len() >= 2 && v.starts_with('"') && v.ends_with('"') => {
+            Some(&v[1..v.len() - 1])
+        }
+        Some(v) if fields::is_token(v) => Some(v),
+        Some(_) => return None,
+        None => None,
+    };
+    Some(Directive { name, value })
+}
+
+// Duplicated or invalid arguments make the response stale.
+fn set_seconds(field: &mut Option<u64>, d: &Directive) {
+    *field = match (*field, d.seconds()) {
+        (None, Some(v)) => Some(v),
+        _ => Some(0),
+    };
+}
+
+/// Directives of `Cache-Control` in a request.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RequestCacheControl {
+    /// `max-age`.
+    pub max_age: Option<u64>,
+    /// `max-stale`, `u64::MAX` if no argument is given.
+    pub max_stale: Option<u64>,
+    /// `min-fresh`.
+    pub min_fresh: Option<u64>,
+    /// `no-cache`.
+    pub no_cache: bool,
+    /// `no-store`.
+    pub no_store: bool,
+    /// `no-transform`.
+    pub no_transform: bool,
+    /// `only-if-cached`.
+    pub only_if_cached: bool,
+}
+
+impl RequestCacheControl {
+    /// Collects directives from `Cache-Control` header fields.
+    pub fn from_headers(headers: &[HeaderField]) -> RequestCacheControl {
+        let mut cc = RequestCacheControl::default();
+        for d in Directives::new(headers) {
+            if d.is("max-age") {
+                set_seconds(&mut cc.max_age, &d);
+            } else if d.is("max-stale") {
+                cc.max_stale = Some(d.seconds().unwrap_or(u64::MAX));
+            } else if d.is("min-fresh") {
+                set_seconds(&mut cc.min_fresh, &d);
+            } else if d.is("no-cache") {
+                cc.no_cache = true;
+            } else if d.is("no-store") {
+                cc.no_store = true;
+            } else if d.is("no-transform") {
+                cc.no_transform = true;
+            } else if d.is("only-if-cached") {
+                cc.only_if_cached = true;
+            }
+        }
+        cc
+    }
+}
+
+/// Directives of `Cache-Control` in a response.
+///
+/// Duplicated or invalid `max-age` and `s-maxage` are treated as `0`.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ResponseCacheControl {
+    /// `max-age`.
+    pub max_age: Option<u64>,
+    /// `s-maxage`.
+    pub s_maxage: Option<u64>,
+    /// `no-cache`, with or without field names.
+    pub no_cache: bool,
+    /// `no-store`.
+    pub no_store: bool,
+    /// `no-transform`.
+    pub no_transform: bool,
+    /// `private`, with or without field names.
+    pub private: bool,
+    /// `public`.
+    pub public: bool,
+    /// `must-revalidate`.
+    pub must_revalidate: bool,
+    /// `proxy-revalidate`.
+    pub proxy_revalidate: bool,
+    /// `must-understand`.
+    pub must_understand: bool,
+    /// `immutable`.
+    pub immutable: bool,
+    /// `stale-while-revalidate`.
+    pub stale_while_revalidate: Option<u64>,
+    /// `stale-if-error`.
+    pub stale_if_error: Option<u64>,
+}
+
+impl ResponseCacheControl {
+    /// Collects directives from `Cache-Control` header fields.
+    pub fn from_headers(headers: &[HeaderField]) -> ResponseCacheControl {
+        let mut cc = ResponseCacheControl::default();
+        for d in Directives::new(headers) {
+            if d.is("max-age") {
+                set_seconds(&mut cc.max_age, &d);
+            } else if d.is("s-maxage") {
+                set_seconds(&mut cc.s_maxage, &d);
+            } else if d.is("no-cache") {
+                cc.no_cache = true;
+            } else if d.is("no-store") {
+                cc.no_store = true;
+            } else if d.is("no-transform") {
+                cc.no_transform = true;
+            } else if d.is("private") {
+                cc.private = true;
+            } else if d.is("public") {
+                cc.public = true;
+            } else if d.is("must-revalidate") {
+                cc.must_revalidate = true;
+            } else if d.is("proxy-revalidate") {
+                cc.proxy_revalidate = true;
+            } else if d.is("must-understand") {
+                cc.must_understand = true;
+            } else if d.is("immutable") {
+                cc.immutable = true;
+            } else if d.is("stale-while-revalidate") {
+                cc.stale_while_revalidate = d.seconds();
+            } else if d.is("stale-if-error") {
+                cc.stale_if_error = d.seconds();
+            }
+        }
+        cc
+    }
+}
+
+/// Returns the value of `Date` header field.
+pub fn date(headers: &[HeaderField]) -> Option<HttpDate> {
+    fields::find(headers, "date").and_then(|v| HttpDate::parse(fields::trim_ows(v)).ok())
+}
+
+/// Returns the value of `Expires` header field.
+///
+/// Invalid dates such as `0` are interpreted as the unix epoch, that is a
+/// time in the past.
+pub fn expires(headers: &[HeaderField]) -> Option<HttpDate> {
+    fields::find(headers, "expires").map(|v| {
+        HttpDate::parse(fields::trim_ows(v)).unwrap_or_else(|_| HttpDate::from_unix_secs(0))
+    })
+}
+
+/// Returns the value of `Age` header field.
+///
+/// Invalid values are ignored, and too large values are saturated.
+pub fn age(headers: &[HeaderField]) -> Option<u64> {
+    let value = fields::trim_ows(fields::find(headers, "age")?);
+    if value.is_empty() || !value.bytes().all(crate::is_digit) {
+        return None;
+    }
+    Some(fields::parse_u64(value).unwrap_or(u64::MAX))
+}
+
+/// Returns `true` if the status code is heuristically cacheable.
+pub fn is_heuristically_cacheable(status: u16) -> bool {
+    matches!(
+        status,
+        200 | 203 | 204 | 206 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// Returns `true` if a cache is allowed to store the response to the request.
+///
+/// `shared` is `true` for a shared cache such as a proxy. Responses to
+/// methods other than `GET` and `HEAD`, and `206 Partial Content` and
+/// `304 Not Modified` are not stored.
+pub fn is_storable(req: &Request, res: &Response, shared: bool) -> bool {
+    if req.method != "GET" && req.method != "HEAD" {
+        return false;
+    }
+
+    let understood = match res.status {
+        100..=199 | 206 | 304 => false,
+        _ => res.status < 600,
+    };
+    let cc = ResponseCacheControl::from_headers(res.headers);
+    if !understood || (cc.no_store && !cc.must_understand) {
+        return false;
+    }
+    if RequestCacheControl::from_headers(req.headers).no_store {
+        return false;
+    }
+
+    if shared {
+        if cc.private {
+            return false;
+        }
+        if fields::find(req.headers, "authorization").is_some()
+            && !(cc.must_revalidate || cc.public || cc.s_maxage.is_some())
+        {
+            return false;
+        }
+    }
+
+    cc.public
+        || (cc.private && !shared)
+        || cc.max_age.is_some()
+        || (cc.s_maxage.is_some() && shared)
+        || fields::find(res.headers, "expires").is_some()
+        || is_heuristically_cacheable(res.status)
+}
+
+/// Calculates the freshness lifetime of the response in seconds.
+///
+/// The explicit expiration time from `s-maxage` (only for shared caches),
+/// `max-age` or `Expires` is preferred. Otherwise a heuristic lifetime of 10%
+/// of the time since `Last-Modified` is used for heuristically cacheable
+/// responses. Returns `None` if neither is available.
+pub fn freshness_lifetime(res: &Response, shared: bool) -> Option<u64> {
+    let cc = ResponseCacheControl::from_headers(res.headers);
+    if shared {
+        if let Some(v) = cc.s_maxage {
+            return Some(v);
+        }
+    }
+    if let Some(v) = cc.max_age {
+        return Some(v);
+    }
+
+    let date = date(res.headers);
+    if let Some(expires) = expires(res.headers) {
+        return Some(match date {
+            Some(date) => expires.unix_secs().saturating_sub(date.unix_secs()),
+            None => 0,
+        });
+    }
+
+    if !is_heuristically_cacheable(res.status) && !cc.public {
+        return None;
+    }
+    let last_modified = fields::find(res.headers, "last-modified")
+        .and_then(|v| HttpDate::parse(fields::trim_ows(v)).ok())?;
+    let date = date?;
+    Some(date.unix_secs().saturating_sub(last_modified.unix_secs()) / 10)
+}
+
+/// Calculates the current age of the response in seconds.
+///
+/// `request_time` and `response_time` are the local times when the request
+/// was sent and the response was received.
+pub fn current_age(
+    res: &Response,
+    request_time: HttpDate,
+    response_time: HttpDate,
+    now: HttpDate,
+) -> u64 {
+    let response_time = response_time.unix_secs();
+    let apparent_age = date(res.headers).map_or(0, |d| response_time.saturating_sub(d.unix_secs()));
+    let response_delay = response_time.saturating_sub(request_time.unix_secs());
+    let corrected_age_value = age(res.headers).unwrap_or(0).saturating_add(response_delay);
+    let corrected_initial_age = cmp::max(apparent_age, corrected_age_value);
+    let resident_time = now.unix_secs().saturating_sub(response_time);
+    corrected_initial_age.saturating_add(resident_time)
+}
+
+/// Calculates the secondary cache key of the request from `Vary` header
+/// fields of the response.
+///
+/// The key consists of the listed field names in lower case and the
+/// corresponding request field values combined with commas, each in a line.
+/// Requests that select the same stored response have the same key. Returns
+/// `None` if `Vary` contains `*`, which never matches.
+pub fn secondary_key(req: &Request, res: &Response) -> Option<String> {
+    let mut names = Vec::new();
+    for value in fields::find_all(res.headers, "vary") {
+        for name in fields::ListIter::new(value) {
+            if name == "*" {
+                return None;
+            }
+            names.push(name);
+        }
+    }
+    names.sort_by(|a, b| cmp_ignore_ascii_case(a, b));
+    names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+    let mut key = String::new();
+    for name in names {
+        key.extend(name.chars().map(|c| c.to_ascii_lowercase()));
+        key.push(':');
+        for (i, value) in fields::find_all(req.headers, name).enumerate() {
+            if i > 0 {
+                key.push(',');
+            }
+            key.push_str(fields::trim_ows(value));
+        }
+        key.push('\n');
+    }
+    Some(key)
+}
+
+fn cmp_ignore_ascii_case(a: &str, b: &str) -> cmp::Ordering {
+    a.bytes()
+        .map(|c| c.to_ascii_lowercase())
+        .cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::cache::*;
+    use crate::*;
+
+    macro_rules! response {
+        ($buf:expr, | $res:ident | $body:expr) => {{
+            let mut headers = Vec::<HeaderField>::with_capacity(16);
+            let ($res, _) = Response::parse($buf, &mut headers).unwrap().unwrap();
+            $body
+        }};
+    }
+
+    macro_rules! exchange {
+        ($req:expr, $res:expr, | $rq:ident, $rs:ident | $body:expr) => {{
+            let mut req_headers = Vec::<HeaderField>::with_capacity(16);
+            let ($rq, _) = Request::parse($req, &mut req_headers).unwrap().unwrap();
+            response!($res, |$rs| $body)
+        }};
+    }
+
+    #[test]
+    fn test_directives() {
+        let headers = [
+            HeaderField {
+                name: "Cache-Control",
+                value: "max-age=60, no-cache=\"Set-Cookie, Foo\"",
+            },
+            HeaderField {
+                name: "Host",
+                value: "example.com",
+            },
+            HeaderField {
+                name: "cache-control",
+                value: "PUBLIC,,bad=a b",
+            },
+        ];
+        let ds: Vec<_> = Directives::new(&headers).collect();
+        assert_eq!(
+            ds,
+            [
+                Directive {
+                    name: "max-age",
+                    value: Some("60")
+                },
+                Directive {
+                    name: "no-cache",
+                    value: Some("Set-Cookie, Foo")
+                },
+                Directive {
+                    name: "PUBLIC",
+                    value: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_response_cache_control() {
+        response!(
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=\"10\", private, stale-if-error=5\r\nCache-Control: must-revalidate\r\n\r\n",
+            |res| {
+                let cc = ResponseCacheControl::from_headers(res.headers);
+                assert_eq!(cc.max_age, Some(10));
+                assert!(cc.private);
+                assert!(cc.must_revalidate);
+                assert_eq!(cc.stale_if_error, Some(5));
+                assert!(!cc.no_store);
+            }
+        );
+        response!(
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=10, max-age=20, s-maxage=x\r\n\r\n",
+            |res| {
+                let cc = ResponseCacheControl::from_headers(res.headers);
+                assert_eq!(cc.max_age, Some(0));
+                assert_eq!(cc.s_maxage, Some(0));
+            }
+        );
+    }
+
+    #[test]
+    fn test_request_cache_control() {
+        let headers = [HeaderField {
+            name: "Cache-Control",
+            value: "max-stale, min-fresh=5, only-if-cached",
+        }];
+        let cc = RequestCacheControl::from_headers(&headers);
+        assert_eq!(cc.max_stale, Some(u64::MAX));
+        assert_eq!(cc.min_fresh, Some(5));
+        assert!(cc.only_if_cached);
+        assert_eq!(cc.max_age, None);
+    }
+
+    #[test]
+    fn test_header_values() {
+        response!(
+            b"HTTP/1.1 200 OK\r\nAge: 99999999999999999999999\r\nExpires: 0\r\n\r\n",
+            |res| {
+                assert_eq!(age(res.headers), Some(u64::MAX));
+                assert_eq!(expires(res.headers), Some(HttpDate::from_unix_secs(0)));
+                assert_eq!(date(res.headers), None);
+            }
+        );
+        response!(b"HTTP/1.1 200 OK\r\nAge: -1\r\n\r\n", |res| {
+            assert_eq!(age(res.headers), None);
+        });
+    }
+
+    #[test]
+    fn test_is_storable() {
+        let get = b"GET / HTTP/1.1\r\n\r\n";
+        let auth = b"GET / HTTP/1.1\r\nAuthorization: Basic eDp5\r\n\r\n";
+        exchange!(get, b"HTTP/1.1 200 OK\r\n\r\n", |req, res| {
+            assert!(is_storable(&req, &res, true));
+        });
+        exchange!(get, b"HTTP/1.1 201 Created\r\n\r\n", |req, res| {
+            assert!(!is_storable(&req, &res, true));
+        });
+        exchange!(
+            get,
+            b"HTTP/1.1 201 Created\r\nExpires: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            |req, res| {
+                assert!(is_storable(&req, &res, true));
+            }
+        );
+        exchange!(
+            b"POST / HTTP/1.1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nCache-Control: max-age=5\r\n\r\n",
+            |req, res| {
+                assert!(!is_storable(&req, &res, false));
+            }
+        );
+        exchange!(
+            get,
+            b"HTTP/1.1 200 OK\r\nCache-Control: no-store\r\n\r\n",
+            |req, res| {
+                assert!(!is_storable(&req, &res, false));
+            }
+        );
+        exchange!(
+            get,
+            b"HTTP/1.1 200 OK\r\nCache-Control: no-store, must-understand\r\n\r\n",
+            |req, res| {
+                assert!(is_storable(&req, &res, false));
+            }
+        );
+        exchange!(
+            get,
+            b"HTTP/1.1 200 OK\r\nCache-Control: private\r\n\r\n",
+            |req, res| {
+                assert!(is_storable(&req, &res, false));
+                assert!(!is_storable(&req, &res, true));
+            }
+        );
+        exchange!(auth, b"HTTP/1.1 200 OK\r\n\r\n", |req, res| {
+            assert!(is_storable(&req, &res, false));
+            assert!(!is_storable(&req, &res, true));
+        });
+        exchange!(
+            auth,
+            b"HTTP/1.1 200 OK\r\nCache-Control: s-maxage=5\r\n\r\n",
+            |req, res| {
+                assert!(is_storable(&req, &res, true));
+            }
+        );
+        exchange!(get, b"HTTP/1.1 206 Partial Content\r\n\r\n", |req, res| {
+            assert!(!is_storable(&req, &res, false));
+        });
+    }
+
+    #[test]
+    fn test_freshness_lifetime() {
+        response!(b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60, s-maxage=600\r\nExpires: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n", |res| {
+            assert_eq!(freshness_lifetime(&res, false), Some(60));
+            assert_eq!(freshness_lifetime(&res, true), Some(600));
+        });
+        response!(b"HTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nExpires: Sun, 06 Nov 1994 09:49:37 GMT\r\n\r\n", |res| {
+            assert_eq!(freshness_lifetime(&res, false), Some(3600));
+        });
+        response!(
+            b"HTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nExpires: 0\r\n\r\n",
+            |res| {
+                assert_eq!(freshness_lifetime(&res, false), Some(0));
+            }
+        );
+        response!(b"HTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nLast-Modified: Sun, 06 Nov 1994 06:09:37 GMT\r\n\r\n", |res| {
+            assert_eq!(freshness_lifetime(&res, false), Some(960));
+        });
+        response!(b"HTTP/1.1 201 Created\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nLast-Modified: Sun, 06 Nov 1994 06:09:37 GMT\r\n\r\n", |res| {
+            assert_eq!(freshness_lifetime(&res, false), None);
+        });
+        response!(b"HTTP/1.1 200 OK\r\n\r\n", |res| {
+            assert_eq!(freshness_lifetime(&res, false), None);
+        });
+    }
+
+    #[test]
+    fn test_current_age() {
+        response!(
+            b"HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:01:40 GMT\r\nAge: 10\r\n\r\n",
+            |res| {
+                let t = HttpDate::from_unix_secs;
+                // corrected_age_value (10 + 5) is greater than apparent_age (5).
+                assert_eq!(current_age(&res, t(100), t(105), t(105)), 15);
+                assert_eq!(current_age(&res, t(100), t(105), t(120)), 30);
+                // apparent_age (50) is greater than corrected_age_value (10 + 1).
+                assert_eq!(current_age(&res, t(149), t(150), t(150)), 50);
+            }
+        );
+    }
+
+    #[test]
+    fn test_secondary_key() {
+        let res =
+            b"HTTP/1.1 200 OK\r\nVary: Accept-Encoding, accept\r\nVary: ACCEPT-ENCODING\r\n\r\n";
+        exchange!(
+            b"GET / HTTP/1.1\r\nAccept: text/html\r\nAccept-Encoding: gzip\r\naccept-encoding:  br \r\n\r\n",
+            res,
+            |req, res| {
+                assert_eq!(
+                    secondary_key(&req, &res).unwrap(),
+                    "accept:text/html\naccept-encoding:gzip,br\n"
+                );
+            }
+        );
+        exchange!(b"GET / HTTP/1.1\r\n\r\n", res, |req, res| {
+            assert_eq!(
+                secondary_key(&req, &res).unwrap(),
+                "accept:\naccept-encoding:\n"
+            );
+        });
+        exchange!(
+            b"GET / HTTP/1.1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\n\r\n",
+            |req, res| {
+                assert_eq!(secondary_key(&req, &res).unwrap(), "");
+            }
+        );
+        exchange!(
+            b"GET / HTTP/1.1\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nVary: *\r\n\r\n",
+            |req, res| {
+                assert_eq!(secondary_key(&req, &res), None);
+            }
+        );
+    }
+}
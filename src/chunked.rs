@@ -0,0 +1,440 @@
+//! Chunked transfer coding encoder.
+//!
+//! Wraps body data with chunk size lines and writes the last chunk with
+//! trailer fields, either into a buffer or, with `std`, into an `io::Write`
+//! through `ChunkedWriter`.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::chunked;
+//!
+//! let mut buf = [0u8; 64];
+//! let mut len = chunked::encode_chunk(b"hello", &[], &mut buf).unwrap();
+//! let trailers = [thhp::HeaderField { name: "Expires", value: "0" }];
+//! len += chunked::encode_last_chunk(&[], &trailers, &mut buf[len..]).unwrap();
+//! assert_eq!(&buf[..len], &b"5\r\nhello\r\n0\r\nExpires: 0\r\n\r\n"[..]);
+//! ```
+#[cfg(feature = "std")]
+use std::io;
+use std::result;
+
+use crate::errors::*;
+use crate::fields::is_token;
+use crate::serialize;
+use crate::HeaderField;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// A chunk extension.
+///
+/// A value which is not a token is written as a quoted-string.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Extension<'a> {
+    /// The extension name.
+    pub name: &'a str,
+    /// The extension value.
+    pub value: Option<&'a str>,
+}
+
+#[inline]
+fn is_qdtext(c: u8) -> bool {
+    c == b'\t' || (c >= 0x20 && c != 0x7F)
+}
+
+fn validate_extensions(extensions: &[Extension]) -> Result<()> {
+    let valid = extensions
+        .iter()
+        .all(|ext| is_token(ext.name) && ext.value.map_or(true, |v| v.bytes().all(is_qdtext)));
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidChunkExtension)
+    }
+}
+
+fn write_size<E, F>(size: usize, put: &mut F) -> result::Result<(), E>
+where
+    F: ?Sized + FnMut(&[u8]) -> result::Result<(), E>,
+{
+    let mut digits = [0u8; 16];
+    let mut pos = digits.len();
+    let mut n = size;
+    loop {
+        pos -= 1;
+        digits[pos] = HEX_DIGITS[n & 0xF];
+        n >>= 4;
+        if n == 0 {
+            break;
+        }
+    }
+    put(&digits[pos..])
+}
+
+fn write_extensions<E, F>(extensions: &[Extension], put: &mut F) -> result::Result<(), E>
+where
+    F: ?Sized + FnMut(&[u8]) -> result::Result<(), E>,
+{
+    for ext in extensions {
+        put(b";")?;
+        put(ext.name.as_bytes())?;
+        match ext.value {
+            Some(v) if is_token(v) => {
+                put(b"=")?;
+                put(v.as_bytes())?;
+            }
+            Some(v) => {
+                put(b"=\"")?;
+                for part in v.as_bytes().split_inclusive(|c| *c == b'"' || *c == b'\\') {
+                    match part.split_last() {
+                        Some((&c, rest)) if c == b'"' || c == b'\\' => {
+                            put(rest)?;
+                            put(b"\\")?;
+                            put(&[c])?;
+                        }
+                        _ => put(part)?,
+                    }
+                }
+                put(b"\"")?;
+            }
+            None => (),
+        }
+    }
+    Ok(())
+}
+
+fn write_chunk_header<E, F>(
+    size: usize,
+    extensions: &[Extension],
+    put: &mut F,
+) -> result::Result<(), E>
+where
+    F: ?Sized + FnMut(&[u8]) -> result::Result<(), E>,
+{
+    write_size(size, put)?;
+    write_extensions(extensions, put)?;
+    put(b"\r\n")
+}
+
+fn write_last_chunk<E, F>(
+    extensions: &[Extension],
+    trailers: &[HeaderField],
+    put: &mut F,
+) -> result::Result<(), E>
+where
+    F: ?Sized + FnMut(&[u8]) -> result::Result<(), E>,
+{
+    write_chunk_header(0, extensions, put)?;
+    for trailer in trailers {
+        put(trailer.name.as_bytes())?;
+        put(b": ")?;
+        put(trailer.value.as_bytes())?;
+        put(b"\r\n")?;
+    }
+    put(b"\r\n")
+}
+
+fn encoded_len<F>(f: F) -> usize
+where
+    F: FnOnce(&mut dyn FnMut(&[u8]) -> result::Result<(), ()>) -> result::Result<(), ()>,
+{
+    let mut len = 0;
+    let _ = f(&mut |s| {
+        len += s.len();
+        Ok(())
+    });
+    len
+}
+
+fn encode_into<F>(buf: &mut [u8], f: F) -> Result<usize>
+where
+    F: FnOnce(&mut dyn FnMut(&[u8]) -> Result<()>) -> Result<()>,
+{
+    let mut pos = 0;
+    f(&mut |s| {
+        let dst = buf.get_mut(pos..pos + s.len()).ok_or(BufferTooSmall)?;
+        dst.copy_from_slice(s);
+        pos += s.len();
+        Ok(())
+    })?;
+    Ok(pos)
+}
+
+/// Returns the number of bytes of the chunk size line for a chunk of `size`
+/// bytes.
+pub fn chunk_header_len(size: usize, extensions: &[Extension]) -> usize {
+    encoded_len(|put| write_chunk_header(size, extensions, put))
+}
+
+/// Writes the chunk size line for a chunk of `size` bytes, returns the
+/// number of written bytes.
+///
+/// The chunk data and the following CRLF are to be written by the caller.
+/// Returns `InvalidChunkSize` if `size` is zero, which is the last chunk.
+pub fn encode_chunk_header(size: usize, extensions: &[Extension], buf: &mut [u8]) -> Result<usize> {
+    if size == 0 {
+        return Err(InvalidChunkSize);
+    }
+    validate_extensions(extensions)?;
+    encode_into(buf, |put| write_chunk_header(size, extensions, put))
+}
+
+/// Writes a chunk containing `data`, returns the number of written bytes.
+///
+/// Nothing is written if `data` is empty, since an empty chunk terminates
+/// the body.
+pub fn encode_chunk(data: &[u8], extensions: &[Extension], buf: &mut [u8]) -> Result<usize> {
+    if data.is_empty() {
+        return Ok(0);
+    }
+    validate_extensions(extensions)?;
+    encode_into(buf, |put| {
+        write_chunk_header(data.len(), extensions, put)?;
+        put(data)?;
+        put(b"\r\n")
+    })
+}
+
+/// Returns the number of bytes of the last chunk with trailer fields.
+pub fn last_chunk_len(extensions: &[Extension], trailers: &[HeaderField]) -> usize {
+    encoded_len(|put| write_last_chunk(extensions, trailers, put))
+}
+
+/// Writes the last chunk, trailer fields and the final CRLF, returns the
+/// number of written bytes.
+pub fn encode_last_chunk(
+    extensions: &[Extension],
+    trailers: &[HeaderField],
+    buf: &mut [u8],
+) -> Result<usize> {
+    validate_extensions(extensions)?;
+    serialize::validate_headers(trailers)?;
+    encode_into(buf, |put| write_last_chunk(extensions, trailers, put))
+}
+
+/// An `io::Write` adapter encoding written data in the chunked transfer
+/// coding.
+///
+/// Each `write` emits one chunk. Wrap the inner writer with a `BufWriter`
+/// to avoid small writes.
+///
+/// ## Example
+///
+/// ```
+/// use std::io::Write;
+///
+/// let mut writer = thhp::chunked::ChunkedWriter::new(Vec::new());
+/// writer.write_all(b"hello").unwrap();
+/// let out = writer.finish(&[]).unwrap();
+/// assert_eq!(out, b"5\r\nhello\r\n0\r\n\r\n");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ChunkedWriter<W: io::Write> {
+    inner: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> ChunkedWriter<W> {
+    /// Makes a writer writing chunks into `inner`.
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter { inner }
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes a chunk containing `data` with chunk extensions.
+    ///
+    /// Nothing is written if `data` is empty.
+    pub fn write_chunk(&mut self, data: &[u8], extensions: &[Extension]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        validate_extensions(extensions)?;
+        let inner = &mut self.inner;
+        let mut put = |s: &[u8]| inner.write_all(s);
+        write_chunk_header(data.len(), extensions, &mut put)?;
+        put(data)?;
+        put(b"\r\n")
+    }
+
+    /// Writes the last chunk with trailer fields and returns the inner
+    /// writer.
+    pub fn finish(self, trailers: &[HeaderField]) -> io::Result<W> {
+        self.finish_with_extensions(&[], trailers)
+    }
+
+    /// Writes the last chunk with chunk extensions and trailer fields and
+    /// returns the inner writer.
+    pub fn finish_with_extensions(
+        mut self,
+        extensions: &[Extension],
+        trailers: &[HeaderField],
+    ) -> io::Result<W> {
+        validate_extensions(extensions)?;
+        serialize::validate_headers(trailers)?;
+        {
+            let inner = &mut self.inner;
+            write_last_chunk(extensions, trailers, &mut |s| inner.write_all(s))?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> io::Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_chunk(buf, &[])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunked::*;
+
+    macro_rules! ext {
+        ($name:expr) => {
+            Extension {
+                name: $name,
+                value: None,
+            }
+        };
+        ($name:expr, $value:expr) => {
+            Extension {
+                name: $name,
+                value: Some($value),
+            }
+        };
+    }
+
+    #[test]
+    fn test_encode_chunk() {
+        let mut buf = [0u8; 64];
+        let len = encode_chunk(b"hello world", &[], &mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"b\r\nhello world\r\n"[..]);
+
+        let data = [b'x'; 300];
+        let mut buf = [0u8; 512];
+        let len = encode_chunk(&data, &[], &mut buf).unwrap();
+        assert_eq!(&buf[..5], &b"12c\r\n"[..]);
+        assert_eq!(len, 5 + 300 + 2);
+        assert_eq!(&buf[len - 2..len], &b"\r\n"[..]);
+
+        assert_eq!(encode_chunk(b"", &[], &mut buf), Ok(0));
+        assert_eq!(
+            encode_chunk(b"hello", &[], &mut buf[..9]),
+            Err(BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_encode_chunk_extensions() {
+        let mut buf = [0u8; 64];
+        let exts = [ext!("a"), ext!("b", "token"), ext!("c", "x \"y\" \\z")];
+        let len = encode_chunk(b"ab", &exts, &mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &b"2;a;b=token;c=\"x \\\"y\\\" \\\\z\"\r\nab\r\n"[..]
+        );
+        assert_eq!(chunk_header_len(2, &exts), len - 4);
+
+        assert_eq!(
+            encode_chunk(b"ab", &[ext!("a b")], &mut buf),
+            Err(InvalidChunkExtension)
+        );
+        assert_eq!(
+            encode_chunk(b"ab", &[ext!("a", "\r\n")], &mut buf),
+            Err(InvalidChunkExtension)
+        );
+    }
+
+    #[test]
+    fn test_encode_chunk_header() {
+        let mut buf = [0u8; 32];
+        let len = encode_chunk_header(0xabc_def0, &[], &mut buf).unwrap();
+        assert_eq!(len, chunk_header_len(0xabc_def0, &[]));
+        assert_eq!(&buf[..len - 2], &b"abcdef0"[..]);
+        assert_eq!(
+            encode_chunk_header(0x10, &[], &mut buf[..3]),
+            Err(BufferTooSmall)
+        );
+        assert_eq!(encode_chunk_header(0, &[], &mut buf), Err(InvalidChunkSize));
+    }
+
+    #[test]
+    fn test_encode_last_chunk() {
+        let mut buf = [0u8; 64];
+        let len = encode_last_chunk(&[], &[], &mut buf).unwrap();
+        assert_eq!(&buf[..len], &b"0\r\n\r\n"[..]);
+
+        let trailers = [
+            HeaderField {
+                name: "Digest",
+                value: "sha-256=abc",
+            },
+            HeaderField {
+                name: "Server-Timing",
+                value: "total;dur=12",
+            },
+        ];
+        let len = encode_last_chunk(&[ext!("end")], &trailers, &mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &b"0;end\r\nDigest: sha-256=abc\r\nServer-Timing: total;dur=12\r\n\r\n"[..]
+        );
+        assert_eq!(last_chunk_len(&[ext!("end")], &trailers), len);
+
+        let trailers = [HeaderField {
+            name: "X",
+            value: "a\r\n\r\nb",
+        }];
+        assert_eq!(
+            encode_last_chunk(&[], &trailers, &mut buf),
+            Err(InvalidFieldValue)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_chunked_writer() {
+        use std::io::Write;
+
+        let mut writer = ChunkedWriter::new(Vec::new());
+        writer.write_all(b"Wiki").unwrap();
+        writer.write_all(b"").unwrap();
+        writer
+            .write_chunk(b"pedia in\r\n\r\nchunks.", &[ext!("x", "1")])
+            .unwrap();
+        let trailers = [HeaderField {
+            name: "Expires",
+            value: "0",
+        }];
+        let out = writer.finish(&trailers).unwrap();
+        assert_eq!(
+            out,
+            &b"4\r\nWiki\r\n13;x=1\r\npedia in\r\n\r\nchunks.\r\n0\r\nExpires: 0\r\n\r\n"[..]
+        );
+
+        let writer = ChunkedWriter::new(Vec::new());
+        let trailers = [HeaderField {
+            name: "Bad Name",
+            value: "0",
+        }];
+        let err = writer.finish(&trailers).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,805 @@
+//! Tokio codecs for HTTP/1 messages.
+//!
+//! `RequestCodec` decodes requests and encodes responses for servers, and
+//! `ResponseCodec` decodes responses and encodes requests for clients. A
+//! message is a stream of frames: the head, body data and the end. Bodies
+//! are delimited by `Content-Length` or the chunked transfer coding, as
+//! given by the header fields of the head.
+//!
+//! A head is parsed only once an empty line is received, so a partially
+//! received head is not parsed again for every read.
+//!
+//! ## Example
+//!
+//! ```
+//! extern crate bytes;
+//! extern crate thhp;
+//! extern crate tokio_util;
+//!
+//! use bytes::BytesMut;
+//! use thhp::codec::{Frame, RequestCodec};
+//! use tokio_util::codec::Decoder;
+//!
+//! let mut codec = RequestCodec::new();
+//! let mut buf = BytesMut::from(&b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello"[..]);
+//! match codec.decode(&mut buf).unwrap() {
+//!     Some(Frame::Head(req)) => assert_eq!(req.method(), "POST"),
+//!     _ => unreachable!(),
+//! }
+//! match codec.decode(&mut buf).unwrap() {
+//!     Some(Frame::Data(data)) => assert_eq!(data, "hello"),
+//!     _ => unreachable!(),
+//! }
+//! match codec.decode(&mut buf).unwrap() {
+//!     Some(Frame::End) => (),
+//!     _ => unreachable!(),
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::result;
+use std::str;
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::body::{BodyDecoder, BodyEncoder, BodyLength, Chunk, Limits};
+use crate::bytes_compat::{BytesHeaderField, BytesRequest, BytesResponse};
+use crate::errors::*;
+use crate::{Complete, HeaderField, Incomplete, Request, Response};
+
+/// A frame of a message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Frame<H> {
+    /// The message head.
+    Head(H),
+    /// Body data.
+    Data(Bytes),
+    /// The end of the message.
+    End,
+}
+
+fn fields(headers: &[BytesHeaderField]) -> Vec<HeaderField<'_>> {
+    headers
+        .iter()
+        .map(BytesHeaderField::as_header_field)
+        .collect()
+}
+
+// Finds the end of a head, skipping `scanned` bytes which are known not to
+// contain it.
+fn has_head_end(buf: &[u8], scanned: usize) -> bool {
+    let start = scanned.saturating_sub(2);
+    buf[start..].windows(2).enumerate().any(|(i, w)| {
+        w == b"\n\n" || (w == b"\r\n" && start + i > 0 && buf[start + i - 1] == b'\n')
+    })
+}
+
+// Decoding state shared by both codecs.
+#[derive(Debug)]
+struct Decoding {
+    limits: Limits,
+    scanned: usize,
+    body: Option<(BodyDecoder, u64)>,
+}
+
+impl Decoding {
+    fn new(limits: Limits) -> Decoding {
+        Decoding {
+            limits,
+            scanned: 0,
+            body: None,
+        }
+    }
+
+    fn decode<H, F>(
+        &mut self,
+        buf: &mut BytesMut,
+        parse: F,
+    ) -> result::Result<Option<Frame<H>>, StreamError>
+    where
+        F: FnOnce(&mut BytesMut, usize) -> Result<crate::Status<(H, BodyLength)>>,
+    {
+        if self.body.is_some() {
+            return self.decode_body(buf);
+        }
+        if buf.is_empty() || !has_head_end(buf, self.scanned) {
+            return self.incomplete_head(buf);
+        }
+        let len = buf.len();
+        let (head, length) = match parse(buf, self.limits.max_headers).map_err(head_error)? {
+            Complete(v) => v,
+            Incomplete => return self.incomplete_head(buf),
+        };
+        self.scanned = 0;
+        if len - buf.len() > self.limits.max_head_len {
+            return Err(StreamError::HeadTooLarge);
+        }
+        if let BodyLength::Length(len) = length {
+            if len > self.limits.max_body_len {
+                return Err(StreamError::BodyTooLarge);
+            }
+        }
+        self.body = Some((BodyDecoder::new(length), 0));
+        Ok(Some(Frame::Head(head)))
+    }
+
+    fn incomplete_head<H>(
+        &mut self,
+        buf: &BytesMut,
+    ) -> result::Result<Option<Frame<H>>, StreamError> {
+        if buf.len() > self.limits.max_head_len {
+            return Err(StreamError::HeadTooLarge);
+        }
+        self.scanned = buf.len();
+        Ok(None)
+    }
+
+    fn decode_body<H>(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> result::Result<Option<Frame<H>>, StreamError> {
+        let (decoder, received) = self.body.as_mut().expect("decoding a body");
+        match decoder.decode(buf)? {
+            Complete((len, Chunk::Data(range))) => {
+                *received += range.len() as u64;
+                if *received > self.limits.max_body_len {
+                    return Err(StreamError::BodyTooLarge);
+                }
+                buf.advance(range.start);
+                let data = buf.split_to(range.len()).freeze();
+                buf.advance(len - range.end);
+                Ok(Some(Frame::Data(data)))
+            }
+            Complete((len, Chunk::End(_))) => {
+                buf.advance(len);
+                self.body = None;
+                Ok(Some(Frame::End))
+            }
+            Incomplete => {
+                if buf.len() > self.limits.max_head_len {
+                    return Err(StreamError::FramingTooLarge);
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof<H>(
+        &mut self,
+        frame: Option<Frame<H>>,
+        buf: &BytesMut,
+    ) -> result::Result<Option<Frame<H>>, StreamError> {
+        if frame.is_some() {
+            return Ok(frame);
+        }
+        match self.body.take() {
+            Some((mut decoder, _)) => {
+                if decoder.eof() {
+                    Ok(Some(Frame::End))
+                } else {
+                    Err(unexpected_eof())
+                }
+            }
+            None if buf.is_empty() => Ok(None),
+            None => Err(unexpected_eof()),
+        }
+    }
+}
+
+fn encode_head<F>(len: usize, buf: &mut BytesMut, encode: F) -> Result<()>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize>,
+{
+    let start = buf.len();
+    buf.resize(start + len, 0);
+    if let Err(err) = encode(&mut buf[start..]) {
+        buf.truncate(start);
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn encode_body(
+    encoding: &mut Option<BodyEncoder>,
+    frame_data: Option<Bytes>,
+    buf: &mut BytesMut,
+) -> Result<()> {
+    match frame_data {
+        Some(data) => encoding
+            .as_mut()
+            .ok_or(InvalidState)?
+            .encode_data(&data, |b| buf.extend_from_slice(b)),
+        None => encoding
+            .take()
+            .ok_or(InvalidState)?
+            .encode_end(|b| buf.extend_from_slice(b)),
+    }
+}
+
+// Frames a response by the method of the oldest pending request. `f` parses
+// or encodes the response with the method. Interim responses precede the
+// final response, so the request is completed only by a final response, and
+// only if `f` succeeds. A response without a pending request is
+// `InvalidState`.
+fn respond<T, F>(methods: &mut VecDeque<Bytes>, status: u16, f: F) -> Result<T>
+where
+    F: FnOnce(&str) -> Result<T>,
+{
+    let v = {
+        let method = methods.front().ok_or(InvalidState)?;
+        f(str::from_utf8(method).or(Err(InvalidState))?)?
+    };
+    if status / 100 != 1 || status == 101 {
+        methods.pop_front();
+    }
+    Ok(v)
+}
+
+/// A codec decoding requests and encoding responses.
+///
+/// Responses are framed by the method of the corresponding request, so
+/// they must be encoded in the order the requests are decoded.
+#[derive(Debug)]
+pub struct RequestCodec {
+    decoding: Decoding,
+    methods: VecDeque<Bytes>,
+    encoding: Option<BodyEncoder>,
+}
+
+impl Default for RequestCodec {
+    fn default() -> RequestCodec {
+        RequestCodec::new()
+    }
+}
+
+impl RequestCodec {
+    /// Creates a codec with the default limits.
+    pub fn new() -> RequestCodec {
+        RequestCodec::with_limits(Limits::default())
+    }
+
+    /// Creates a codec with `limits`.
+    pub fn with_limits(limits: Limits) -> RequestCodec {
+        RequestCodec {
+            decoding: Decoding::new(limits),
+            methods: VecDeque::new(),
+            encoding: None,
+        }
+    }
+}
+
+impl Decoder for RequestCodec {
+    type Item = Frame<BytesRequest>;
+    type Error = StreamError;
+
+    fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> result::Result<Option<Frame<BytesRequest>>, StreamError> {
+        let methods = &mut self.methods;
+        self.decoding.decode(buf, |buf, max_headers| {
+            let req = match BytesRequest::parse(buf, max_headers)? {
+                Complete(req) => req,
+                Incomplete => return Ok(Incomplete),
+            };
+            let length = BodyLength::of_request(&fields(req.headers()))?;
+            methods.push_back(req.method_bytes().clone());
+            Ok(Complete((req, length)))
+        })
+    }
+
+    fn decode_eof(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> result::Result<Option<Frame<BytesRequest>>, StreamError> {
+        let frame = self.decode(buf)?;
+        self.decoding.decode_eof(frame, buf)
+    }
+}
+
+impl<'headers, 'buffer> Encoder<Frame<Response<'headers, 'buffer>>> for RequestCodec {
+    type Error = StreamError;
+
+    fn encode(
+        &mut self,
+        frame: Frame<Response<'headers, 'buffer>>,
+        buf: &mut BytesMut,
+    ) -> result::Result<(), StreamError> {
+        match frame {
+            Frame::Head(res) => {
+                if self.encoding.is_some() {
+                    return Err(InvalidState.into());
+                }
+                let length = respond(&mut self.methods, res.status, |method| {
+                    let length = BodyLength::of_response(method, res.status, res.headers)?;
+                    encode_head(res.encoded_len(), buf, |buf| res.encode(buf))?;
+                    Ok(length)
+                })?;
+                self.encoding = Some(BodyEncoder::new(length));
+            }
+            Frame::Data(data) => encode_body(&mut self.encoding, Some(data), buf)?,
+            Frame::End => encode_body(&mut self.encoding, None, buf)?,
+        }
+        Ok(())
+    }
+}
+
+/// A codec decoding responses and encoding requests.
+///
+/// Responses are framed by the method of the corresponding request, so
+/// requests must be encoded before their responses are decoded.
+#[derive(Debug)]
+pub struct ResponseCodec {
+    decoding: Decoding,
+    methods: VecDeque<Bytes>,
+    encoding: Option<BodyEncoder>,
+}
+
+impl Default for ResponseCodec {
+    fn default() -> ResponseCodec {
+        ResponseCodec::new()
+    }
+}
+
+impl ResponseCodec {
+    /// Creates a codec with the default limits.
+    pub fn new() -> ResponseCodec {
+        ResponseCodec::with_limits(Limits::default())
+    }
+
+    /// Creates a codec with `limits`.
+    pub fn with_limits(limits: Limits) -> ResponseCodec {
+        ResponseCodec {
+            decoding: Decoding::new(limits),
+            methods: VecDeque::new(),
+            encoding: None,
+        }
+    }
+}
+
+impl Decoder for ResponseCodec {
+    type Item = Frame<BytesResponse>;
+    type Error = StreamError;
+
+    fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> result::Result<Option<Frame<BytesResponse>>, StreamError> {
+        let methods = &mut self.methods;
+        self.decoding.decode(buf, |buf, max_headers| {
+            let res = match BytesResponse::parse(buf, max_headers)? {
+                Complete(res) => res,
+                Incomplete => return Ok(Incomplete),
+            };
+            let length = respond(methods, res.status(), |method| {
+                BodyLength::of_response(method, res.status(), &fields(res.headers()))
+            })?;
+            Ok(Complete((res, length)))
+        })
+    }
+
+    fn decode_eof(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> result::Result<Option<Frame<BytesResponse>>, StreamError> {
+        let frame = self.decode(buf)?;
+        self.decoding.decode_eof(frame, buf)
+    }
+}
+
+impl<'headers, 'buffer> Encoder<Frame<Request<'headers, 'buffer>>> for ResponseCodec {
+    type Error = StreamError;
+
+    fn encode(
+        &mut self,
+        frame: Frame<Request<'headers, 'buffer>>,
+        buf: &mut BytesMut,
+    ) -> result::Result<(), StreamError> {
+        match frame {
+            Frame::Head(req) => {
+                if self.encoding.is_some() {
+                    return Err(InvalidState.into());
+                }
+                let length = BodyLength::of_request(req.headers)?;
+                encode_head(req.encoded_len(), buf, |buf| req.encode(buf))?;
+                self.methods
+                    .push_back(Bytes::copy_from_slice(req.method.as_bytes()));
+                self.encoding = Some(BodyEncoder::new(length));
+            }
+            Frame::Data(data) => encode_body(&mut self.encoding, Some(data), buf)?,
+            Frame::End => encode_body(&mut self.encoding, None, buf)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use bytes::{Bytes, BytesMut};
+    use futures::executor::block_on;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::duplex;
+    use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+    use crate::codec::*;
+
+    fn decode_all<D: Decoder>(codec: &mut D, buf: &mut BytesMut) -> Vec<D::Item>
+    where
+        D::Error: ::std::fmt::Debug,
+    {
+        let mut frames = Vec::new();
+        while let Some(frame) = codec.decode(buf).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn data(frames: &[Frame<BytesRequest>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for frame in frames {
+            if let Frame::Data(ref d) = *frame {
+                data.extend_from_slice(d);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_pipelined_requests() {
+        let input: &[u8] = b"POST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc\
+            POST /b HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nde\r\n1;x\r\nf\r\n0\r\nA: b\r\n\r\n\
+            GET /c HTTP/1.1\r\n\r\n";
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(input);
+        let frames = decode_all(&mut codec, &mut buf);
+        assert!(buf.is_empty());
+        let heads: Vec<_> = frames
+            .iter()
+            .filter_map(|f| match *f {
+                Frame::Head(ref req) => Some(req.target()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(heads, ["/a", "/b", "/c"]);
+        assert_eq!(frames.iter().filter(|f| **f == Frame::End).count(), 3);
+        assert_eq!(data(&frames), b"abcdef");
+
+        // The same frames are decoded when bytes arrive one by one.
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::new();
+        let mut split = Vec::new();
+        for &b in input {
+            buf.extend_from_slice(&[b]);
+            split.extend(decode_all(&mut codec, &mut buf));
+        }
+        assert_eq!(split.iter().filter(|f| **f == Frame::End).count(), 3);
+        assert_eq!(data(&split), b"abcdef");
+    }
+
+    #[test]
+    fn test_decode_eof() {
+        let mut codec = ResponseCodec::new();
+        let mut buf = BytesMut::new();
+        let req = Request {
+            method: "GET",
+            target: "/",
+            minor_version: 0,
+            headers: &[],
+        };
+        codec.encode(Frame::Head(req), &mut buf).unwrap();
+        codec.encode(Frame::End, &mut buf).unwrap();
+        let mut buf = BytesMut::from(&b"HTTP/1.0 200 OK\r\n\r\nhello"[..]);
+        let mut frames = decode_all(&mut codec, &mut buf);
+        while let Some(frame) = codec.decode_eof(&mut buf).unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1], Frame::Data(Bytes::from_static(b"hello")));
+        assert_eq!(frames[2], Frame::End);
+
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(&b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello"[..]);
+        decode_all(&mut codec, &mut buf);
+        match codec.decode_eof(&mut buf) {
+            Err(StreamError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\n"[..]);
+        assert!(codec.decode_eof(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let limits = Limits {
+            max_head_len: 48,
+            max_headers: 2,
+            max_body_len: 4,
+        };
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf =
+            BytesMut::from(&b"GET / HTTP/1.1\r\nHost: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"[..]);
+        match codec.decode(&mut buf) {
+            Err(StreamError::HeadTooLarge) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nA: b\r\nC: d\r\nE: f\r\n\r\n"[..]);
+        match codec.decode(&mut buf) {
+            Err(StreamError::TooManyHeaders) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf = BytesMut::from(&b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n"[..]);
+        match codec.decode(&mut buf) {
+            Err(StreamError::BodyTooLarge) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf = BytesMut::from(
+            &b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n"[..],
+        );
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        match codec.decode(&mut buf) {
+            Err(StreamError::BodyTooLarge) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf = BytesMut::from(
+            &b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+               1;aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"[..],
+        );
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+        match codec.decode(&mut buf) {
+            Err(StreamError::FramingTooLarge) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::with_limits(limits);
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nX: \x01\r\n\r\n"[..]);
+        match codec.decode(&mut buf) {
+            Err(StreamError::Parse(InvalidFieldValue)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_encode_responses() {
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(&b"HEAD / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n"[..]);
+        decode_all(&mut codec, &mut buf);
+
+        let headers = [HeaderField {
+            name: "Content-Length",
+            value: "5",
+        }];
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        // The response to HEAD has no body.
+        codec.encode(Frame::Head(res), &mut buf).unwrap();
+        codec.encode(Frame::End, &mut buf).unwrap();
+
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        codec.encode(Frame::Head(res), &mut buf).unwrap();
+        codec
+            .encode(Frame::Data(Bytes::from_static(b"hello")), &mut buf)
+            .unwrap();
+        codec.encode(Frame::Data(Bytes::new()), &mut buf).unwrap();
+        codec.encode(Frame::End, &mut buf).unwrap();
+        assert_eq!(
+            &buf[..],
+            &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+               HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_response_without_request() {
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &[],
+        };
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::new();
+        match codec.encode(Frame::Head(res), &mut buf) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+        assert!(buf.is_empty());
+
+        let mut codec = ResponseCodec::new();
+        let mut buf = BytesMut::from(&b"HTTP/1.1 200 OK\r\n\r\n"[..]);
+        match codec.decode(&mut buf) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_encode_invalid_head() {
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(&b"HEAD / HTTP/1.1\r\n\r\n"[..]);
+        decode_all(&mut codec, &mut buf);
+
+        match codec.encode(Frame::Data(Bytes::from_static(b"abc")), &mut buf) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+
+        let headers = [HeaderField {
+            name: "Bad Name",
+            value: "x",
+        }];
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        assert!(codec.encode(Frame::Head(res), &mut buf).is_err());
+        assert!(buf.is_empty());
+
+        // The rejected response does not complete the HEAD request.
+        let headers = [HeaderField {
+            name: "Content-Length",
+            value: "5",
+        }];
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        codec.encode(Frame::Head(res), &mut buf).unwrap();
+        codec.encode(Frame::End, &mut buf).unwrap();
+        assert_eq!(
+            &buf[..],
+            &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_head_before_end() {
+        let mut codec = ResponseCodec::new();
+        let mut buf = BytesMut::new();
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        let req = Request {
+            method: "POST",
+            target: "/",
+            minor_version: 1,
+            headers: &headers,
+        };
+        codec.encode(Frame::Head(req), &mut buf).unwrap();
+        let req = Request {
+            method: "GET",
+            target: "/",
+            minor_version: 1,
+            headers: &[],
+        };
+        match codec.encode(Frame::Head(req), &mut buf) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+
+        let mut codec = RequestCodec::new();
+        let mut buf = BytesMut::from(&b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n"[..]);
+        decode_all(&mut codec, &mut buf);
+        let headers = [HeaderField {
+            name: "Content-Length",
+            value: "5",
+        }];
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        codec.encode(Frame::Head(res), &mut buf).unwrap();
+        let res = Response {
+            minor_version: 1,
+            status: 200,
+            reason: "OK",
+            headers: &headers,
+        };
+        match codec.encode(Frame::Head(res), &mut buf) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+        assert_eq!(
+            &buf[..],
+            &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_content_length_mismatch() {
+        let mut codec = ResponseCodec::new();
+        let mut buf = BytesMut::new();
+        let headers = [HeaderField {
+            name: "Content-Length",
+            value: "2",
+        }];
+        let req = Request {
+            method: "POST",
+            target: "/",
+            minor_version: 1,
+            headers: &headers,
+        };
+        codec.encode(Frame::Head(req), &mut buf).unwrap();
+        match codec.encode(Frame::Data(Bytes::from_static(b"abc")), &mut buf) {
+            Err(StreamError::Parse(InvalidContentLength)) => (),
+            _ => panic!(),
+        }
+        codec
+            .encode(Frame::Data(Bytes::from_static(b"a")), &mut buf)
+            .unwrap();
+        match codec.encode(Frame::End, &mut buf) {
+            Err(StreamError::Parse(InvalidContentLength)) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_duplex() {
+        let (client, server) = duplex(4096);
+        let mut client = FramedWrite::new(client, ResponseCodec::new());
+        let mut server = FramedRead::new(server, RequestCodec::new());
+
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        let req = Request {
+            method: "POST",
+            target: "/upload",
+            minor_version: 1,
+            headers: &headers,
+        };
+        block_on(client.send(Frame::Head(req))).unwrap();
+        block_on(client.send(Frame::Data(Bytes::from_static(b"hello, ")))).unwrap();
+        block_on(client.send(Frame::Data(Bytes::from_static(b"world")))).unwrap();
+        block_on(client.send(Frame::End)).unwrap();
+        drop(client);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = block_on(server.next()) {
+            frames.push(frame.unwrap());
+        }
+        match frames[0] {
+            Frame::Head(ref req) => {
+                assert_eq!(req.method(), "POST");
+                assert_eq!(req.target(), "/upload");
+            }
+            _ => panic!(),
+        }
+        assert_eq!(data(&frames), b"hello, world");
+        assert_eq!(frames.last(), Some(&Frame::End));
+    }
+}
@@ -0,0 +1,507 @@
+//! Conditional requests.
+//!
+//! Provides entity-tags and evaluation of the preconditions `If-Match`,
+//! `If-None-Match`, `If-Modified-Since`, `If-Unmodified-Since` and `If-Range`.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::conditional::{evaluate, EntityTag, Outcome, Validators};
+//!
+//! let buf = b"GET / HTTP/1.1\r\nIf-None-Match: W/\"1\", \"2\"\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! let validators = Validators {
+//!     exists: true,
+//!     etag: Some(EntityTag::strong("1").unwrap()),
+//!     last_modified: None,
+//! };
+//! assert_eq!(evaluate(&req, &validators), Ok(Outcome::NotModified));
+//! ```
+use std::fmt;
+
+use crate::date::HttpDate;
+use crate::errors::*;
+use crate::fields;
+use crate::Request;
+
+/// An entity-tag.
+///
+/// It is formatted by `Display` as a header field value.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EntityTag<'buffer> {
+    weak: bool,
+    tag: &'buffer str,
+}
+
+#[inline]
+fn is_etagc(c: u8) -> bool {
+    c == 0x21 || (0x23 <= c && c != 0x7F)
+}
+
+impl<'buffer> EntityTag<'buffer> {
+    /// Makes a strong entity-tag from an opaque tag without quotes.
+    pub fn strong(tag: &'buffer str) -> Result<EntityTag<'buffer>> {
+        EntityTag::new(false, tag)
+    }
+
+    /// Makes a weak entity-tag from an opaque tag without quotes.
+    pub fn weak(tag: &'buffer str) -> Result<EntityTag<'buffer>> {
+        EntityTag::new(true, tag)
+    }
+
+    fn new(weak: bool, tag: &'buffer str) -> Result<EntityTag<'buffer>> {
+        if tag.bytes().all(is_etagc) {
+            Ok(EntityTag { weak, tag })
+        } else {
+            Err(InvalidEntityTag)
+        }
+    }
+
+    /// Parse an entity-tag such as `"xyzzy"` or `W/"xyzzy"`.
+    pub fn parse(value: &'buffer str) -> Result<EntityTag<'buffer>> {
+        match EntityTag::parse_prefix(fields::trim_ows(value))? {
+            (etag, "") => Ok(etag),
+            _ => Err(InvalidEntityTag),
+        }
+    }
+
+    fn parse_prefix(s: &'buffer str) -> Result<(EntityTag<'buffer>, &'buffer str)> {
+        let (weak, s) = match s.strip_prefix("W/") {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        if !s.starts_with('"') {
+            return Err(InvalidEntityTag);
+        }
+        let len = s[1..].find('"').ok_or(InvalidEntityTag)?;
+        let etag = EntityTag::new(weak, &s[1..len + 1])?;
+        Ok((etag, &s[len + 2..]))
+    }
+
+    /// Returns `true` if the entity-tag is weak.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// The opaque tag without quotes.
+    pub fn tag(&self) -> &'buffer str {
+        self.tag
+    }
+
+    /// Strong comparison: both are strong and the tags are identical.
+    pub fn strong_eq(&self, other: &EntityTag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison: the tags are identical regardless of weakness.
+    pub fn weak_eq(&self, other: &EntityTag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl<'buffer> fmt::Display for EntityTag<'buffer> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/\"{}\"", self.tag)
+        } else {
+            write!(f, "\"{}\"", self.tag)
+        }
+    }
+}
+
+/// A value of `If-Match` or `If-None-Match`: `*` or a list of entity-tags.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EntityTagList<'buffer> {
+    /// `*`, matches any current representation.
+    Any,
+    /// A comma separated list of entity-tags.
+    Tags(&'buffer str),
+}
+
+impl<'buffer> EntityTagList<'buffer> {
+    /// Parse an entity-tag list, validating every entity-tag.
+    pub fn parse(value: &'buffer str) -> Result<EntityTagList<'buffer>> {
+        let value = fields::trim_ows(value);
+        if value == "*" {
+            return Ok(EntityTagList::Any);
+        }
+
+        let list = EntityTagList::Tags(value);
+        for etag in list.iter() {
+            etag?;
+        }
+        Ok(list)
+    }
+
+    /// Iterates the entity-tags.
+    pub fn iter(&self) -> EntityTags<'buffer> {
+        EntityTags {
+            rest: match *self {
+                EntityTagList::Any => "",
+                EntityTagList::Tags(s) => s,
+            },
+        }
+    }
+
+    /// Returns `true` if any entity-tag in the list matches `etag`.
+    ///
+    /// `*` matches anything.
+    pub fn matches<F>(&self, etag: &EntityTag, mut eq: F) -> bool
+    where
+        F: FnMut(&EntityTag, &EntityTag) -> bool,
+    {
+        match *self {
+            EntityTagList::Any => true,
+            EntityTagList::Tags(_) => self.iter().any(|t| t.is_ok_and(|t| eq(&t, etag))),
+        }
+    }
+}
+
+/// An iterator over entity-tags in an entity-tag list.
+pub struct EntityTags<'buffer> {
+    rest: &'buffer str,
+}
+
+impl<'buffer> Iterator for EntityTags<'buffer> {
+    type Item = Result<EntityTag<'buffer>>;
+
+    fn next(&mut self) -> Option<Result<EntityTag<'buffer>>> {
+        self.rest = self.rest.trim_start_matches([' ', '\t', ',']);
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match EntityTag::parse_prefix(self.rest) {
+            Ok((etag, rest)) => {
+                let rest = fields::trim_ows(rest);
+                if rest.is_empty() || rest.starts_with(',') {
+                    self.rest = rest;
+                    Some(Ok(etag))
+                } else {
+                    self.rest = "";
+                    Some(Err(InvalidEntityTag))
+                }
+            }
+            Err(e) => {
+                self.rest = "";
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// The validators of the selected representation of the target resource.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Validators<'a> {
+    /// `true` if the target resource has a current representation.
+    pub exists: bool,
+    /// The current entity-tag.
+    pub etag: Option<EntityTag<'a>>,
+    /// The last modification date.
+    pub last_modified: Option<HttpDate>,
+}
+
+/// A result of evaluating preconditions.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Outcome {
+    /// Perform the request method.
+    Proceed,
+    /// Respond `304 Not Modified`.
+    NotModified,
+    /// Respond `412 Precondition Failed`.
+    PreconditionFailed,
+}
+
+/// Evaluates preconditions of the request in the order of RFC 9110, Section
+/// 13.2.2.
+///
+/// Preconditions with invalid dates are ignored. Invalid entity-tag lists
+/// result in `InvalidEntityTag`.
+pub fn evaluate(req: &Request, validators: &Validators) -> Result<Outcome> {
+    let headers = req.headers;
+    let is_get_or_head = req.method == "GET" || req.method == "HEAD";
+
+    let mut if_match = fields::find_all(headers, "if-match").peekable();
+    if if_match.peek().is_some() {
+        if !any_matches(if_match, validators, |a, b| a.strong_eq(b))? {
+            return Ok(Outcome::PreconditionFailed);
+        }
+    } else if let Some(date) = find_date(req, "if-unmodified-since") {
+        if validators.last_modified.is_some_and(|lm| lm > date) {
+            return Ok(Outcome::PreconditionFailed);
+        }
+    }
+
+    let mut if_none_match = fields::find_all(headers, "if-none-match").peekable();
+    if if_none_match.peek().is_some() {
+        if any_matches(if_none_match, validators, |a, b| a.weak_eq(b))? {
+            return Ok(if is_get_or_head {
+                Outcome::NotModified
+            } else {
+                Outcome::PreconditionFailed
+            });
+        }
+    } else if is_get_or_head {
+        if let Some(date) = find_date(req, "if-modified-since") {
+            if validators.last_modified.is_some_and(|lm| lm <= date) {
+                return Ok(Outcome::NotModified);
+            }
+        }
+    }
+
+    Ok(Outcome::Proceed)
+}
+
+/// Evaluates `If-Range` of the request.
+///
+/// Returns `true` if the `Range` header field should be honored, that is
+/// `If-Range` is absent, or it strongly matches the current entity-tag or
+/// exactly matches the last modification date.
+pub fn evaluate_if_range(req: &Request, validators: &Validators) -> bool {
+    let value = match fields::find(req.headers, "if-range") {
+        Some(value) => fields::trim_ows(value),
+        None => return true,
+    };
+
+    if let Ok(etag) = EntityTag::parse(value) {
+        validators.etag.is_some_and(|cur| cur.strong_eq(&etag))
+    } else if let Ok(date) = HttpDate::parse(value) {
+        validators.last_modified == Some(date)
+    } else {
+        false
+    }
+}
+
+fn any_matches<'a, I, F>(values: I, validators: &Validators, mut eq: F) -> Result<bool>
+where
+    I: Iterator<Item = &'a str>,
+    F: FnMut(&EntityTag, &EntityTag) -> bool,
+{
+    let mut matched = false;
+    for value in values {
+        let list = EntityTagList::parse(value)?;
+        matched |= match (list, validators.etag) {
+            (EntityTagList::Any, _) => validators.exists,
+            (_, Some(ref etag)) => list.matches(etag, &mut eq),
+            (_, None) => false,
+        };
+    }
+    Ok(matched)
+}
+
+fn find_date(req: &Request, name: &str) -> Option<HttpDate> {
+    fields::find(req.headers, name).and_then(|v| HttpDate::parse(fields::trim_ows(v)).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::conditional::*;
+    use crate::*;
+
+    const LAST_MODIFIED: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+
+    fn validators() -> Validators<'static> {
+        Validators {
+            exists: true,
+            etag: Some(EntityTag::strong("abc").unwrap()),
+            last_modified: Some(HttpDate::parse(LAST_MODIFIED).unwrap()),
+        }
+    }
+
+    macro_rules! check {
+        ($buf:expr, $expect:expr) => {
+            check!($buf, validators(), $expect)
+        };
+        ($buf:expr, $validators:expr, $expect:expr) => {{
+            let mut headers = Vec::<HeaderField>::with_capacity(10);
+            let (req, _) = Request::parse($buf, &mut headers).unwrap().unwrap();
+            assert_eq!(evaluate(&req, &$validators), $expect);
+        }};
+    }
+
+    #[test]
+    fn test_parse_entity_tag() {
+        let etag = EntityTag::parse("\"xyzzy\"").unwrap();
+        assert!(!etag.is_weak());
+        assert_eq!(etag.tag(), "xyzzy");
+
+        let etag = EntityTag::parse("W/\"xyzzy\"").unwrap();
+        assert!(etag.is_weak());
+        assert_eq!(etag.tag(), "xyzzy");
+        assert_eq!(etag.to_string(), "W/\"xyzzy\"");
+
+        assert_eq!(EntityTag::parse("\"\"").unwrap().tag(), "");
+        assert_eq!(EntityTag::parse("xyzzy"), Err(InvalidEntityTag));
+        assert_eq!(EntityTag::parse("w/\"xyzzy\""), Err(InvalidEntityTag));
+        assert_eq!(EntityTag::parse("\"xyzzy"), Err(InvalidEntityTag));
+        assert_eq!(EntityTag::parse("\"xy\"zy\""), Err(InvalidEntityTag));
+        assert_eq!(EntityTag::parse("\"xy zy\""), Err(InvalidEntityTag));
+    }
+
+    #[test]
+    fn test_compare_entity_tags() {
+        let s1 = EntityTag::strong("1").unwrap();
+        let s2 = EntityTag::strong("2").unwrap();
+        let w1 = EntityTag::weak("1").unwrap();
+        let w2 = EntityTag::weak("2").unwrap();
+
+        assert!(!w1.strong_eq(&w1) && w1.weak_eq(&w1));
+        assert!(!w1.strong_eq(&w2) && !w1.weak_eq(&w2));
+        assert!(!w1.strong_eq(&s1) && w1.weak_eq(&s1));
+        assert!(s1.strong_eq(&s1) && s1.weak_eq(&s1));
+        assert!(!s1.strong_eq(&s2) && !s1.weak_eq(&s2));
+    }
+
+    #[test]
+    fn test_parse_entity_tag_list() {
+        assert_eq!(EntityTagList::parse(" * "), Ok(EntityTagList::Any));
+
+        let list = EntityTagList::parse("\"a,b\", W/\"c\" ,,\"d\"").unwrap();
+        let tags: Vec<_> = list.iter().map(|t| t.unwrap().to_string()).collect();
+        assert_eq!(tags, ["\"a,b\"", "W/\"c\"", "\"d\""]);
+
+        assert_eq!(EntityTagList::parse("\"a\" \"b\""), Err(InvalidEntityTag));
+        assert_eq!(EntityTagList::parse("\"a\", *"), Err(InvalidEntityTag));
+        assert_eq!(EntityTagList::parse("\"a\", b"), Err(InvalidEntityTag));
+    }
+
+    #[test]
+    fn test_if_match() {
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: \"abc\"\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: \"x\", \"abc\"\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: \"x\"\r\nIf-Match: \"abc\"\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: W/\"abc\"\r\n\r\n",
+            Ok(Outcome::PreconditionFailed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: *\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+
+        let missing = Validators {
+            exists: false,
+            etag: None,
+            last_modified: None,
+        };
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: *\r\n\r\n",
+            missing,
+            Ok(Outcome::PreconditionFailed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: abc\r\n\r\n",
+            Err(InvalidEntityTag)
+        );
+    }
+
+    #[test]
+    fn test_if_unmodified_since() {
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT\r\n\r\n",
+            Ok(Outcome::PreconditionFailed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Unmodified-Since: yesterday\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        // If-Match takes precedence.
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-Match: \"abc\"\r\nIf-Unmodified-Since: Sun, 06 Nov 1994 08:49:36 GMT\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        check!(
+            b"GET / HTTP/1.1\r\nIf-None-Match: W/\"abc\"\r\n\r\n",
+            Ok(Outcome::NotModified)
+        );
+        check!(
+            b"HEAD / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n",
+            Ok(Outcome::NotModified)
+        );
+        check!(
+            b"GET / HTTP/1.1\r\nIf-None-Match: \"x\"\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n",
+            Ok(Outcome::PreconditionFailed)
+        );
+
+        let missing = Validators {
+            exists: false,
+            etag: None,
+            last_modified: None,
+        };
+        check!(
+            b"PUT / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n",
+            missing,
+            Ok(Outcome::Proceed)
+        );
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        check!(
+            b"GET / HTTP/1.1\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            Ok(Outcome::NotModified)
+        );
+        check!(
+            b"GET / HTTP/1.1\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:36 GMT\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        check!(
+            b"POST / HTTP/1.1\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+        // If-None-Match takes precedence.
+        check!(
+            b"GET / HTTP/1.1\r\nIf-None-Match: \"x\"\r\nIf-Modified-Since: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            Ok(Outcome::Proceed)
+        );
+    }
+
+    #[test]
+    fn test_if_range() {
+        macro_rules! if_range {
+            ($buf:expr) => {{
+                let mut headers = Vec::<HeaderField>::with_capacity(10);
+                let (req, _) = Request::parse($buf, &mut headers).unwrap().unwrap();
+                evaluate_if_range(&req, &validators())
+            }};
+        }
+
+        assert!(if_range!(b"GET / HTTP/1.1\r\nRange: bytes=0-1\r\n\r\n"));
+        assert!(if_range!(b"GET / HTTP/1.1\r\nIf-Range: \"abc\"\r\n\r\n"));
+        assert!(!if_range!(b"GET / HTTP/1.1\r\nIf-Range: W/\"abc\"\r\n\r\n"));
+        assert!(if_range!(
+            b"GET / HTTP/1.1\r\nIf-Range: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n"
+        ));
+        assert!(!if_range!(
+            b"GET / HTTP/1.1\r\nIf-Range: Sun, 06 Nov 1994 08:49:38 GMT\r\n\r\n"
+        ));
+    }
+}
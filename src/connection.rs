@@ -0,0 +1,1428 @@
+//! Sans-I/O HTTP/1 connections.
+//!
+//! `ServerConnection` and `ClientConnection` keep track of the state of a
+//! connection without doing any I/O, in the manner of Python's h11. Received
+//! bytes are given to `receive_data`, and `next_event` returns the messages
+//! and their bodies as events. Outgoing messages are written into a
+//! `Vec<u8>` by `send_response` or `send_request`, `send_data` and
+//! `send_end`, and the caller sends them.
+//!
+//! A server handles requests one at a time. A pipelined request is not
+//! parsed until the response to the previous request is sent, so responses
+//! are always in the order of requests.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::connection::{Event, ServerConnection};
+//!
+//! let mut conn = ServerConnection::new();
+//! conn.receive_data(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+//! match conn.next_event().unwrap() {
+//!     Event::Request(req) => assert_eq!(req.method(), "POST"),
+//!     _ => unreachable!(),
+//! }
+//! assert_eq!(conn.next_event().unwrap(), Event::Data(b"hello"));
+//! assert_eq!(conn.next_event().unwrap(), Event::End);
+//! assert_eq!(conn.next_event().unwrap(), Event::Paused);
+//!
+//! let headers = [thhp::HeaderField { name: "Content-Length", value: "2" }];
+//! let res = thhp::Response { minor_version: 1, status: 200, reason: "OK", headers: &headers };
+//! let mut out = Vec::new();
+//! conn.send_response(&res, &mut out).unwrap();
+//! conn.send_data(b"ok", &mut out).unwrap();
+//! conn.send_end(&mut out).unwrap();
+//! assert_eq!(out, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+//! assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+//! ```
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::Range;
+use std::result;
+
+use crate::body::{BodyDecoder, BodyEncoder, BodyLength, Chunk, Limits};
+use crate::errors::*;
+use crate::fields::has_token;
+use crate::io::{expects_continue, HeadScanner};
+use crate::{
+    Complete, HeaderField, Incomplete, OwnedRequest, OwnedResponse, Request, Response, Status,
+};
+
+/// An event on a connection.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// A request head.
+    Request(OwnedRequest),
+    /// An informational (1xx) response head preceding the final response,
+    /// other than `101 Switching Protocols`.
+    Informational(OwnedResponse),
+    /// A final response head.
+    Response(OwnedResponse),
+    /// Body data.
+    Data(&'a [u8]),
+    /// The end of a message.
+    End,
+    /// More data must be received.
+    NeedData,
+    /// No more events are returned until a message is sent: the response
+    /// to a received request, or a request on a client connection.
+    Paused,
+    /// The connection switched to another protocol. The bytes received after
+    /// the message belong to the new protocol.
+    Upgrade(&'a [u8]),
+    /// The connection is closed, or must be closed once the output is sent.
+    Closed,
+}
+
+// An event with body data as a range of the received bytes.
+enum Step {
+    Event(Event<'static>),
+    Data(Range<usize>),
+}
+
+// Returns `true` if a message keeps the connection open. HTTP/1.0
+// connections are always closed.
+fn keep_alive(minor_version: u8, headers: &[HeaderField]) -> bool {
+    minor_version >= 1 && !has_token(headers, "Connection", "close")
+}
+
+// Received bytes not processed yet.
+#[derive(Debug)]
+struct Inbound {
+    limits: Limits,
+    buf: Vec<u8>,
+    pos: usize,
+    // Scans the head at `pos` as bytes are received.
+    scanner: HeadScanner,
+    eof: bool,
+}
+
+impl Inbound {
+    fn new(limits: Limits) -> Inbound {
+        Inbound {
+            limits,
+            buf: Vec::new(),
+            pos: 0,
+            scanner: HeadScanner::default(),
+            eof: false,
+        }
+    }
+
+    fn receive_data(&mut self, data: &[u8]) {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(data);
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    // Parses a head with `parse` once it is received entirely, consuming
+    // it. Only bytes received since the last call are scanned for its end.
+    fn parse_head<T, F>(&mut self, parse: F) -> result::Result<Option<T>, StreamError>
+    where
+        F: for<'b> FnOnce(&'b [u8], &mut Vec<HeaderField<'b>>) -> Result<Status<(T, usize)>>,
+    {
+        let head_len = match self.scanner.scan(&self.buf[self.pos..]) {
+            Some(len) if len > self.limits.max_head_len => return Err(StreamError::HeadTooLarge),
+            Some(len) => len,
+            None if self.rest().len() > self.limits.max_head_len => {
+                return Err(StreamError::HeadTooLarge);
+            }
+            None if self.eof => return Err(unexpected_eof()),
+            None => return Ok(None),
+        };
+        let mut headers = Vec::with_capacity(self.limits.max_headers);
+        match parse(&self.buf[self.pos..self.pos + head_len], &mut headers).map_err(head_error)? {
+            Complete((head, len)) => {
+                self.pos += len;
+                self.scanner = HeadScanner::default();
+                Ok(Some(head))
+            }
+            // The head ends with an empty line, so a line break is malformed.
+            Incomplete => Err(InvalidNewLine.into()),
+        }
+    }
+
+    // Decodes the next body data, returning its range in `buf`, or `None` at
+    // the end of the body.
+    fn decode_body(
+        &mut self,
+        decoder: &mut BodyDecoder,
+        received: &mut u64,
+    ) -> result::Result<Status<Option<Range<usize>>>, StreamError> {
+        loop {
+            let start = self.pos;
+            match decoder.decode(&self.buf[start..])? {
+                Complete((len, Chunk::Data(range))) => {
+                    self.pos += len;
+                    if range.is_empty() {
+                        // Only framing is consumed.
+                        continue;
+                    }
+                    *received += range.len() as u64;
+                    if *received > self.limits.max_body_len {
+                        return Err(StreamError::BodyTooLarge);
+                    }
+                    return Ok(Complete(Some(start + range.start..start + range.end)));
+                }
+                Complete((len, Chunk::End(_))) => {
+                    self.pos += len;
+                    return Ok(Complete(None));
+                }
+                Incomplete if self.eof => {
+                    return if decoder.eof() {
+                        Ok(Complete(None))
+                    } else {
+                        Err(unexpected_eof())
+                    };
+                }
+                Incomplete if self.rest().len() > self.limits.max_head_len => {
+                    return Err(StreamError::FramingTooLarge);
+                }
+                Incomplete => return Ok(Incomplete),
+            }
+        }
+    }
+}
+
+// Writes a head of `len` bytes into `out` with `encode`, adding
+// `Connection: close` if `close` and `headers` don't have it.
+fn encode_head<F>(
+    len: usize,
+    headers: &[HeaderField],
+    close: bool,
+    out: &mut Vec<u8>,
+    encode: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut [u8]) -> Result<usize>,
+{
+    let start = out.len();
+    out.resize(start + len, 0);
+    if let Err(err) = encode(&mut out[start..]) {
+        out.truncate(start);
+        return Err(err);
+    }
+    if close && !has_token(headers, "Connection", "close") {
+        // Insert before the empty line.
+        out.truncate(out.len() - 2);
+        out.extend_from_slice(b"Connection: close\r\n\r\n");
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Reading {
+    Head,
+    Body(BodyDecoder, u64),
+    Done,
+}
+
+#[derive(Debug)]
+enum Writing {
+    Idle,
+    Head,
+    Body(BodyEncoder),
+    Done,
+}
+
+/// The server side of a connection.
+///
+/// Each request is followed by its response: `next_event` returns
+/// `Event::Paused` once a request is received until its response is sent.
+/// The connection is kept alive unless either message has
+/// `Connection: close`, the response is delimited by closing the
+/// connection, or the request is HTTP/1.0. `Connection: close` is added to
+/// the response head when the connection is to be closed.
+///
+/// Once `Event::Closed` is returned, or `is_closed` returns `true`, the
+/// output should be sent and the connection closed.
+#[derive(Debug)]
+pub struct ServerConnection {
+    inbound: Inbound,
+    reading: Reading,
+    writing: Writing,
+    // The method of the request being responded to.
+    method: String,
+    minor_version: u8,
+    keep_alive: bool,
+    expects_continue: bool,
+    upgrade: bool,
+    switched: bool,
+    closed: bool,
+}
+
+impl Default for ServerConnection {
+    fn default() -> ServerConnection {
+        ServerConnection::new()
+    }
+}
+
+impl ServerConnection {
+    /// Creates a connection with the default limits.
+    pub fn new() -> ServerConnection {
+        ServerConnection::with_limits(Limits::default())
+    }
+
+    /// Creates a connection with `limits` on received requests.
+    pub fn with_limits(limits: Limits) -> ServerConnection {
+        ServerConnection {
+            inbound: Inbound::new(limits),
+            reading: Reading::Head,
+            writing: Writing::Idle,
+            method: String::new(),
+            minor_version: 1,
+            keep_alive: true,
+            expects_continue: false,
+            upgrade: false,
+            switched: false,
+            closed: false,
+        }
+    }
+
+    /// Adds bytes received from the client.
+    pub fn receive_data(&mut self, data: &[u8]) {
+        self.inbound.receive_data(data);
+    }
+
+    /// Notifies that the client closed its side of the connection.
+    pub fn receive_eof(&mut self) {
+        self.inbound.eof = true;
+    }
+
+    /// Returns the next event from the received bytes.
+    ///
+    /// After an error, the connection must be closed and only
+    /// `Event::Closed` is returned. A response such as `400 Bad Request` can
+    /// still be sent if no response is started.
+    pub fn next_event(&mut self) -> result::Result<Event<'_>, StreamError> {
+        if self.closed {
+            return Ok(Event::Closed);
+        }
+        // The rest of the request body precedes the new protocol.
+        if let (true, &Reading::Done) = (self.switched, &self.reading) {
+            return Ok(Event::Upgrade(self.inbound.rest()));
+        }
+        match self.read() {
+            Ok(Step::Event(event)) => Ok(event),
+            Ok(Step::Data(range)) => Ok(Event::Data(&self.inbound.buf[range])),
+            Err(err) => {
+                self.fail();
+                Err(err)
+            }
+        }
+    }
+
+    fn read(&mut self) -> result::Result<Step, StreamError> {
+        match self.reading {
+            Reading::Head => {
+                if self.inbound.rest().is_empty() && self.inbound.eof {
+                    self.closed = true;
+                    return Ok(Step::Event(Event::Closed));
+                }
+                let (req, length, keep_alive, expects_continue, upgrade) =
+                    match self.inbound.parse_head(|buf, headers| {
+                        let (req, len) = match Request::parse(buf, headers)? {
+                            Complete(v) => v,
+                            Incomplete => return Ok(Incomplete),
+                        };
+                        let length = BodyLength::of_request(req.headers)?;
+                        let upgrade = req.is_upgrade();
+                        Ok(Complete((
+                            (
+                                req.to_owned(),
+                                length,
+                                keep_alive(req.minor_version, req.headers),
+                                expects_continue(&req),
+                                upgrade,
+                            ),
+                            len,
+                        )))
+                    })? {
+                        Some(v) => v,
+                        None => return Ok(Step::Event(Event::NeedData)),
+                    };
+                if let BodyLength::Length(len) = length {
+                    if len > self.inbound.limits.max_body_len {
+                        return Err(StreamError::BodyTooLarge);
+                    }
+                }
+
+                self.method.clear();
+                self.method.push_str(req.method());
+                self.minor_version = req.minor_version();
+                self.keep_alive = keep_alive;
+                self.upgrade = upgrade;
+                self.expects_continue = expects_continue && length != BodyLength::Length(0);
+                self.reading = Reading::Body(BodyDecoder::new(length), 0);
+                self.writing = Writing::Head;
+                Ok(Step::Event(Event::Request(req)))
+            }
+            Reading::Body(ref mut decoder, ref mut received) => {
+                match self.inbound.decode_body(decoder, received)? {
+                    Complete(Some(range)) => Ok(Step::Data(range)),
+                    Complete(None) => {
+                        self.reading = Reading::Done;
+                        self.finish();
+                        Ok(Step::Event(Event::End))
+                    }
+                    Incomplete => Ok(Step::Event(Event::NeedData)),
+                }
+            }
+            Reading::Done => Ok(Step::Event(Event::Paused)),
+        }
+    }
+
+    // Closes the connection after an error, still allowing a response.
+    fn fail(&mut self) {
+        self.closed = true;
+        self.keep_alive = false;
+        self.upgrade = false;
+        if let Writing::Idle = self.writing {
+            self.method.clear();
+            self.minor_version = 1;
+            self.writing = Writing::Head;
+        }
+    }
+
+    // Starts the next exchange once both the request and the response are
+    // complete.
+    fn finish(&mut self) {
+        if self.switched {
+            return;
+        }
+        if let (&Reading::Done, &Writing::Done) = (&self.reading, &self.writing) {
+            if self.keep_alive {
+                self.reading = Reading::Head;
+                self.writing = Writing::Idle;
+            } else {
+                self.closed = true;
+            }
+        }
+    }
+
+    /// Returns `true` if the client waits for a `100 Continue` interim
+    /// response before sending the request body.
+    ///
+    /// Send it to receive the body, or send a final response instead, in
+    /// which case the connection is closed after the response.
+    pub fn is_waiting_for_continue(&self) -> bool {
+        match (&self.reading, &self.writing) {
+            (&Reading::Body(..), &Writing::Head) => self.expects_continue,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the connection must be closed once the output is
+    /// sent.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Writes a response head into `out`.
+    ///
+    /// Any number of informational (1xx) responses may precede the final
+    /// response, unless the request is HTTP/1.0. A `101 Switching Protocols`
+    /// response to a request with `Upgrade`, or a 2xx response to `CONNECT`,
+    /// switches the connection to another protocol and `next_event` returns
+    /// `Event::Upgrade` once the rest of the request body is returned.
+    pub fn send_response(
+        &mut self,
+        res: &Response,
+        out: &mut Vec<u8>,
+    ) -> result::Result<(), StreamError> {
+        match self.writing {
+            Writing::Head => (),
+            _ => return Err(InvalidState.into()),
+        }
+        // HTTP/1.0 clients don't understand informational responses.
+        if (100..200).contains(&res.status) && self.minor_version == 0 {
+            return Err(InvalidState.into());
+        }
+
+        if res.status == 101 || (self.method == "CONNECT" && res.status / 100 == 2) {
+            if !self.upgrade {
+                return Err(InvalidState.into());
+            }
+            encode_head(res.encoded_len(), res.headers, false, out, |buf| {
+                res.encode(buf)
+            })?;
+            self.writing = Writing::Done;
+            self.switched = true;
+            return Ok(());
+        }
+        if (100..200).contains(&res.status) {
+            encode_head(res.encoded_len(), res.headers, false, out, |buf| {
+                res.encode(buf)
+            })?;
+            if res.status == 100 {
+                self.expects_continue = false;
+            }
+            return Ok(());
+        }
+
+        let length = BodyLength::of_response(&self.method, res.status, res.headers)?;
+        if length == BodyLength::Chunked && self.minor_version == 0 {
+            return Err(InvalidTransferEncoding.into());
+        }
+        // The client may or may not send the body it waits to send.
+        let keep_alive = self.keep_alive
+            && keep_alive(res.minor_version, res.headers)
+            && length != BodyLength::CloseDelimited
+            && !self.is_waiting_for_continue();
+        encode_head(res.encoded_len(), res.headers, !keep_alive, out, |buf| {
+            res.encode(buf)
+        })?;
+        self.keep_alive = keep_alive;
+        self.expects_continue = false;
+        self.writing = Writing::Body(BodyEncoder::new(length));
+        Ok(())
+    }
+
+    /// Writes response body data into `out`, framed as given by the response
+    /// head.
+    pub fn send_data(&mut self, data: &[u8], out: &mut Vec<u8>) -> result::Result<(), StreamError> {
+        match self.writing {
+            Writing::Body(ref mut encoding) => {
+                Ok(encoding.encode_data(data, |b| out.extend_from_slice(b))?)
+            }
+            _ => Err(InvalidState.into()),
+        }
+    }
+
+    /// Writes the end of the response body into `out`.
+    ///
+    /// The next request is parsed once the request body is also received
+    /// entirely, unless the connection is to be closed.
+    pub fn send_end(&mut self, out: &mut Vec<u8>) -> result::Result<(), StreamError> {
+        let encoding = match mem::replace(&mut self.writing, Writing::Done) {
+            Writing::Body(encoding) => encoding,
+            writing => {
+                self.writing = writing;
+                return Err(InvalidState.into());
+            }
+        };
+        if let Err(err) = encoding.encode_end(|b| out.extend_from_slice(b)) {
+            self.closed = true;
+            return Err(err.into());
+        }
+        if self.keep_alive {
+            self.finish();
+        } else {
+            self.closed = true;
+        }
+        Ok(())
+    }
+}
+
+/// The client side of a connection.
+///
+/// Responses are framed by the methods of the requests sent, so `HEAD` and
+/// `CONNECT` responses are handled correctly. Up to `set_pipeline_depth`
+/// requests, 1 by default, may be sent before their responses are received.
+///
+/// A `101 Switching Protocols` response to a request with `Upgrade`, or a
+/// 2xx response to `CONNECT`, switches the connection to another protocol,
+/// and no more requests can be sent after such a request until its
+/// response is received.
+///
+/// ## Example
+///
+/// ```
+/// use thhp::connection::{ClientConnection, Event};
+///
+/// let mut conn = ClientConnection::new();
+/// let req = thhp::Request { method: "HEAD", target: "/", minor_version: 1, headers: &[] };
+/// let mut out = Vec::new();
+/// conn.send_request(&req, &mut out).unwrap();
+/// conn.send_end(&mut out).unwrap();
+/// assert_eq!(out, b"HEAD / HTTP/1.1\r\n\r\n");
+///
+/// conn.receive_data(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+/// match conn.next_event().unwrap() {
+///     Event::Response(res) => assert_eq!(res.status(), 200),
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(conn.next_event().unwrap(), Event::End);
+/// assert!(conn.is_reusable());
+/// ```
+#[derive(Debug)]
+pub struct ClientConnection {
+    inbound: Inbound,
+    reading: Reading,
+    writing: Writing,
+    // Requests whose responses are not received yet, with whether they may
+    // switch protocols.
+    in_flight: VecDeque<(String, bool)>,
+    pipeline_depth: usize,
+    keep_alive: bool,
+    switched: bool,
+    closed: bool,
+}
+
+impl Default for ClientConnection {
+    fn default() -> ClientConnection {
+        ClientConnection::new()
+    }
+}
+
+impl ClientConnection {
+    /// Creates a connection with the default limits.
+    pub fn new() -> ClientConnection {
+        ClientConnection::with_limits(Limits::default())
+    }
+
+    /// Creates a connection with `limits` on received responses.
+    pub fn with_limits(limits: Limits) -> ClientConnection {
+        ClientConnection {
+            inbound: Inbound::new(limits),
+            reading: Reading::Head,
+            writing: Writing::Idle,
+            in_flight: VecDeque::new(),
+            pipeline_depth: 1,
+            keep_alive: true,
+            switched: false,
+            closed: false,
+        }
+    }
+
+    /// Sets the maximum number of requests sent before their responses are
+    /// received.
+    pub fn set_pipeline_depth(&mut self, depth: usize) {
+        self.pipeline_depth = depth.max(1);
+    }
+
+    /// Adds bytes received from the server.
+    pub fn receive_data(&mut self, data: &[u8]) {
+        self.inbound.receive_data(data);
+    }
+
+    /// Notifies that the server closed its side of the connection.
+    pub fn receive_eof(&mut self) {
+        self.inbound.eof = true;
+    }
+
+    /// Returns the next event from the received bytes.
+    ///
+    /// After an error, the connection must be closed and only
+    /// `Event::Closed` is returned.
+    pub fn next_event(&mut self) -> result::Result<Event<'_>, StreamError> {
+        if self.switched {
+            return Ok(Event::Upgrade(self.inbound.rest()));
+        }
+        if self.closed {
+            return Ok(Event::Closed);
+        }
+        match self.read() {
+            Ok(Step::Event(event)) => Ok(event),
+            Ok(Step::Data(range)) => Ok(Event::Data(&self.inbound.buf[range])),
+            Err(err) => {
+                self.closed = true;
+                Err(err)
+            }
+        }
+    }
+
+    fn read(&mut self) -> result::Result<Step, StreamError> {
+        match self.reading {
+            Reading::Head => {
+                if self.in_flight.is_empty() {
+                    return if self.inbound.rest().is_empty() && !self.inbound.eof {
+                        Ok(Step::Event(Event::Paused))
+                    } else if self.inbound.rest().is_empty() {
+                        self.closed = true;
+                        Ok(Step::Event(Event::Closed))
+                    } else {
+                        // A response without a request.
+                        Err(InvalidState.into())
+                    };
+                }
+                if self.inbound.rest().is_empty() && self.inbound.eof {
+                    return Err(unexpected_eof());
+                }
+                let (method, upgrade) = self.in_flight[0].clone();
+                let (res, length, keep_alive) = match self.inbound.parse_head(|buf, headers| {
+                    let (res, len) = match Response::parse(buf, headers)? {
+                        Complete(v) => v,
+                        Incomplete => return Ok(Incomplete),
+                    };
+                    let length = BodyLength::of_response(&method, res.status, res.headers)?;
+                    let keep_alive = keep_alive(res.minor_version, res.headers);
+                    Ok(Complete(((res.to_owned(), length, keep_alive), len)))
+                })? {
+                    Some(v) => v,
+                    None => return Ok(Step::Event(Event::NeedData)),
+                };
+
+                let status = res.status();
+                if status == 101 || (method == "CONNECT" && status / 100 == 2) {
+                    if !upgrade {
+                        return Err(InvalidState.into());
+                    }
+                    self.in_flight.clear();
+                    self.switched = true;
+                    return Ok(Step::Event(Event::Response(res)));
+                }
+                if (100..200).contains(&status) {
+                    return Ok(Step::Event(Event::Informational(res)));
+                }
+                if let BodyLength::Length(len) = length {
+                    if len > self.inbound.limits.max_body_len {
+                        return Err(StreamError::BodyTooLarge);
+                    }
+                }
+                self.keep_alive =
+                    self.keep_alive && keep_alive && length != BodyLength::CloseDelimited;
+                self.reading = Reading::Body(BodyDecoder::new(length), 0);
+                Ok(Step::Event(Event::Response(res)))
+            }
+            Reading::Body(ref mut decoder, ref mut received) => {
+                match self.inbound.decode_body(decoder, received)? {
+                    Complete(Some(range)) => Ok(Step::Data(range)),
+                    Complete(None) => {
+                        self.reading = Reading::Head;
+                        self.in_flight.pop_front();
+                        if !self.keep_alive {
+                            self.closed = true;
+                        }
+                        Ok(Step::Event(Event::End))
+                    }
+                    Incomplete => Ok(Step::Event(Event::NeedData)),
+                }
+            }
+            Reading::Done => Err(InvalidState.into()),
+        }
+    }
+
+    /// Returns `true` if the connection must be closed once the output is
+    /// sent.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns `true` if the connection is idle and can be reused for
+    /// another request, for example by returning it to a pool.
+    pub fn is_reusable(&self) -> bool {
+        self.in_flight.is_empty() && self.can_send_request()
+    }
+
+    /// Returns `true` if a request can be sent now.
+    pub fn can_send_request(&self) -> bool {
+        matches!(self.writing, Writing::Idle)
+            && !self.closed
+            && !self.switched
+            && self.keep_alive
+            && self.in_flight.len() < self.pipeline_depth
+            && !self.in_flight.iter().any(|&(_, upgrade)| upgrade)
+    }
+
+    /// Writes a request head into `out`.
+    ///
+    /// It is an error unless `can_send_request` returns `true`.
+    pub fn send_request(
+        &mut self,
+        req: &Request,
+        out: &mut Vec<u8>,
+    ) -> result::Result<(), StreamError> {
+        if !self.can_send_request() {
+            return Err(InvalidState.into());
+        }
+        let length = BodyLength::of_request(req.headers)?;
+        if length == BodyLength::Chunked && req.minor_version == 0 {
+            return Err(InvalidTransferEncoding.into());
+        }
+        encode_head(req.encoded_len(), req.headers, false, out, |buf| {
+            req.encode(buf)
+        })?;
+        self.keep_alive = keep_alive(req.minor_version, req.headers);
+        self.in_flight
+            .push_back((req.method.to_string(), req.is_upgrade()));
+        self.writing = Writing::Body(BodyEncoder::new(length));
+        Ok(())
+    }
+
+    /// Writes request body data into `out`, framed as given by the request
+    /// head.
+    pub fn send_data(&mut self, data: &[u8], out: &mut Vec<u8>) -> result::Result<(), StreamError> {
+        match self.writing {
+            Writing::Body(ref mut encoding) => {
+                Ok(encoding.encode_data(data, |b| out.extend_from_slice(b))?)
+            }
+            _ => Err(InvalidState.into()),
+        }
+    }
+
+    /// Writes the end of the request body into `out`.
+    pub fn send_end(&mut self, out: &mut Vec<u8>) -> result::Result<(), StreamError> {
+        let encoding = match mem::replace(&mut self.writing, Writing::Idle) {
+            Writing::Body(encoding) => encoding,
+            writing => {
+                self.writing = writing;
+                return Err(InvalidState.into());
+            }
+        };
+        if let Err(err) = encoding.encode_end(|b| out.extend_from_slice(b)) {
+            self.closed = true;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::connection::*;
+
+    fn response<'a>(status: u16, headers: &'a [HeaderField<'a>]) -> Response<'a, 'a> {
+        Response {
+            minor_version: 1,
+            status,
+            reason: "",
+            headers,
+        }
+    }
+
+    fn request(conn: &mut ServerConnection) -> OwnedRequest {
+        match conn.next_event().unwrap() {
+            Event::Request(req) => req,
+            event => panic!("{:?}", event),
+        }
+    }
+
+    // Collects body data until the end of the message.
+    fn body(conn: &mut ServerConnection) -> Vec<u8> {
+        let mut body = Vec::new();
+        loop {
+            match conn.next_event().unwrap() {
+                Event::Data(data) => body.extend_from_slice(data),
+                Event::End => return body,
+                event => panic!("{:?}", event),
+            }
+        }
+    }
+
+    const LENGTH_0: [HeaderField; 1] = [HeaderField {
+        name: "Content-Length",
+        value: "0",
+    }];
+
+    #[test]
+    fn test_pipelined_requests() {
+        let mut conn = ServerConnection::new();
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.receive_data(
+            b"GET /a HTTP/1.1\r\n\r\nPOST /b HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi",
+        );
+
+        assert_eq!(request(&mut conn).target(), "/a");
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        // The next request waits for the response.
+        assert_eq!(conn.next_event().unwrap(), Event::Paused);
+        let mut out = Vec::new();
+        assert!(conn.send_data(b"x", &mut out).is_err());
+        conn.send_response(&response(200, &LENGTH_0), &mut out)
+            .unwrap();
+        assert_eq!(conn.next_event().unwrap(), Event::Paused);
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(out, b"HTTP/1.1 200 \r\nContent-Length: 0\r\n\r\n");
+
+        assert_eq!(request(&mut conn).target(), "/b");
+        assert_eq!(body(&mut conn), b"hi");
+        out.clear();
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        conn.send_response(&response(200, &headers), &mut out)
+            .unwrap();
+        conn.send_data(b"hello", &mut out).unwrap();
+        conn.send_data(b"", &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"HTTP/1.1 200 \r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"[..]
+        );
+        assert!(!conn.is_closed());
+
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.receive_eof();
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+    }
+
+    #[test]
+    fn test_receive_byte_by_byte() {
+        let input = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3;a=b\r\nhel\r\n2\r\nlo\r\n0\r\nX: y\r\n\r\n";
+        let mut conn = ServerConnection::new();
+        let mut events = Vec::new();
+        let mut body = Vec::new();
+        for c in input.iter() {
+            conn.receive_data(&[*c]);
+            loop {
+                match conn.next_event().unwrap() {
+                    Event::NeedData | Event::Paused => break,
+                    Event::Request(req) => events.push(req.method().to_string()),
+                    Event::Data(data) => body.extend_from_slice(data),
+                    Event::End => events.push("end".to_string()),
+                    event => panic!("{:?}", event),
+                }
+            }
+        }
+        assert_eq!(events, ["POST", "end"]);
+        assert_eq!(body, b"hello");
+    }
+
+    #[test]
+    fn test_close() {
+        // HTTP/1.0 connections are closed.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        );
+        request(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        let mut out = Vec::new();
+        conn.send_response(&response(200, &LENGTH_0), &mut out)
+            .unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"HTTP/1.1 200 \r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..]
+        );
+        assert!(conn.is_closed());
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+
+        // The response closes the connection.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"GET / HTTP/1.1\r\n\r\n");
+        request(&mut conn);
+        out.clear();
+        let headers = [HeaderField {
+            name: "Connection",
+            value: "Close",
+        }];
+        conn.send_response(&response(200, &headers), &mut out)
+            .unwrap();
+        conn.send_data(b"until close", &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"HTTP/1.1 200 \r\nConnection: Close\r\n\r\nuntil close"[..]
+        );
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+
+        // A chunked response to HTTP/1.0 is an error.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"GET / HTTP/1.0\r\n\r\n");
+        request(&mut conn);
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        match conn.send_response(&response(200, &headers), &mut out) {
+            Err(StreamError::Parse(InvalidTransferEncoding)) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_head_response() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"HEAD / HTTP/1.1\r\n\r\n");
+        request(&mut conn);
+        let headers = [HeaderField {
+            name: "Content-Length",
+            value: "5",
+        }];
+        let mut out = Vec::new();
+        conn.send_response(&response(200, &headers), &mut out)
+            .unwrap();
+        match conn.send_data(b"hello", &mut out) {
+            Err(StreamError::Parse(InvalidContentLength)) => (),
+            r => panic!("{:?}", r),
+        }
+        conn.send_end(&mut out).unwrap();
+        assert!(!conn.is_closed());
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        let input = b"PUT / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\n";
+        let mut conn = ServerConnection::new();
+        conn.receive_data(input);
+        request(&mut conn);
+        assert!(conn.is_waiting_for_continue());
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+
+        let mut out = Vec::new();
+        conn.send_response(&response(100, &[]), &mut out).unwrap();
+        assert_eq!(out, b"HTTP/1.1 100 \r\n\r\n");
+        assert!(!conn.is_waiting_for_continue());
+        conn.receive_data(b"ok");
+        assert_eq!(body(&mut conn), b"ok");
+        out.clear();
+        conn.send_response(&response(204, &[]), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(out, b"HTTP/1.1 204 \r\n\r\n");
+        assert!(!conn.is_closed());
+
+        // Rejecting the body closes the connection.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(input);
+        request(&mut conn);
+        out.clear();
+        conn.send_response(&response(417, &LENGTH_0), &mut out)
+            .unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"HTTP/1.1 417 \r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..]
+        );
+        assert!(conn.is_closed());
+    }
+
+    #[test]
+    fn test_informational_to_http10() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"POST / HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\n");
+        request(&mut conn);
+        let mut out = Vec::new();
+        match conn.send_response(&response(100, &[]), &mut out) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            _ => panic!(),
+        }
+        assert!(out.is_empty());
+        conn.send_response(&response(200, &LENGTH_0), &mut out)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_response_before_request_body() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nab");
+        request(&mut conn);
+        let mut out = Vec::new();
+        conn.send_response(&response(200, &LENGTH_0), &mut out)
+            .unwrap();
+        conn.send_end(&mut out).unwrap();
+
+        // The rest of the body precedes the next request.
+        conn.receive_data(b"cdGET /next HTTP/1.1\r\n\r\n");
+        assert_eq!(body(&mut conn), b"abcd");
+        assert_eq!(request(&mut conn).target(), "/next");
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(
+            b"GET /chat HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n\x81\x00",
+        );
+        request(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert_eq!(conn.next_event().unwrap(), Event::Paused);
+        let headers = [
+            HeaderField {
+                name: "Connection",
+                value: "Upgrade",
+            },
+            HeaderField {
+                name: "Upgrade",
+                value: "websocket",
+            },
+        ];
+        let mut out = Vec::new();
+        conn.send_response(&response(101, &headers), &mut out)
+            .unwrap();
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b"\x81\x00"));
+        conn.receive_data(b"\x82");
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b"\x81\x00\x82"));
+        assert!(conn.send_end(&mut out).is_err());
+
+        // Declining the upgrade.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"GET / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n");
+        request(&mut conn);
+        conn.send_response(&response(200, &LENGTH_0), &mut out)
+            .unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+
+        // 101 without `Upgrade` in the request.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"GET / HTTP/1.1\r\n\r\n");
+        request(&mut conn);
+        match conn.send_response(&response(101, &[]), &mut out) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_before_request_body() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(
+            b"POST / HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: h2c\r\nContent-Length: 4\r\n\r\nab",
+        );
+        request(&mut conn);
+        let headers = [
+            HeaderField {
+                name: "Connection",
+                value: "Upgrade",
+            },
+            HeaderField {
+                name: "Upgrade",
+                value: "h2c",
+            },
+        ];
+        let mut out = Vec::new();
+        conn.send_response(&response(101, &headers), &mut out)
+            .unwrap();
+        assert_eq!(conn.next_event().unwrap(), Event::Data(b"ab"));
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.receive_data(b"cdPRI");
+        assert_eq!(body(&mut conn), b"cd");
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b"PRI"));
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n\x16\x03");
+        assert_eq!(request(&mut conn).target(), "example.com:443");
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        let mut out = Vec::new();
+        conn.send_response(&response(200, &[]), &mut out).unwrap();
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b"\x16\x03"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut conn = ServerConnection::new();
+        let mut out = Vec::new();
+        match conn.send_response(&response(200, &[]), &mut out) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            r => panic!("{:?}", r),
+        }
+
+        // An error response is sent after an invalid request.
+        conn.receive_data(b"GET / HTTP/1.1\r\nHo st: a\r\n\r\n");
+        match conn.next_event() {
+            Err(StreamError::Parse(InvalidFieldName)) => (),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+        conn.send_response(&response(400, &LENGTH_0), &mut out)
+            .unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"HTTP/1.1 400 \r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..]
+        );
+
+        let limits = Limits {
+            max_head_len: 48,
+            max_headers: 4,
+            max_body_len: 4,
+        };
+        let mut conn = ServerConnection::with_limits(limits);
+        conn.receive_data(b"GET / HTTP/1.1\r\nHost: www.example.com\r\nAccept: */*\r\n");
+        match conn.next_event() {
+            Err(StreamError::HeadTooLarge) => (),
+            r => panic!("{:?}", r),
+        }
+
+        let mut conn = ServerConnection::with_limits(limits);
+        conn.receive_data(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\nE: 5\r\n\r\n");
+        match conn.next_event() {
+            Err(StreamError::TooManyHeaders) => (),
+            r => panic!("{:?}", r),
+        }
+
+        let mut conn = ServerConnection::with_limits(limits);
+        conn.receive_data(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n");
+        match conn.next_event() {
+            Err(StreamError::BodyTooLarge) => (),
+            r => panic!("{:?}", r),
+        }
+
+        let mut conn = ServerConnection::with_limits(limits);
+        conn.receive_data(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1;");
+        request(&mut conn);
+        conn.receive_data(&[b'a'; 48]);
+        match conn.next_event() {
+            Err(StreamError::FramingTooLarge) => (),
+            r => panic!("{:?}", r),
+        }
+
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nab");
+        request(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::Data(b"ab"));
+        conn.receive_eof();
+        match conn.next_event() {
+            Err(StreamError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            r => panic!("{:?}", r),
+        }
+
+        let mut conn = ServerConnection::new();
+        conn.receive_data(b"GET / HTTP/1.1\r\n");
+        conn.receive_eof();
+        assert!(conn.next_event().is_err());
+
+        // A request framed by both headers may hide another request, so
+        // nothing after it is parsed.
+        let mut conn = ServerConnection::new();
+        conn.receive_data(
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n\
+              0\r\n\r\nGET /smuggled HTTP/1.1\r\n\r\n",
+        );
+        match conn.next_event() {
+            Err(StreamError::Parse(InvalidContentLength)) => (),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+        assert!(conn.is_closed());
+    }
+
+    fn get<'a>(target: &'a str, headers: &'a [HeaderField<'a>]) -> Request<'a, 'a> {
+        Request {
+            method: "GET",
+            target,
+            minor_version: 1,
+            headers,
+        }
+    }
+
+    fn client_response(conn: &mut ClientConnection) -> OwnedResponse {
+        match conn.next_event().unwrap() {
+            Event::Response(res) => res,
+            event => panic!("{:?}", event),
+        }
+    }
+
+    fn client_body(conn: &mut ClientConnection) -> Vec<u8> {
+        let mut body = Vec::new();
+        loop {
+            match conn.next_event().unwrap() {
+                Event::Data(data) => body.extend_from_slice(data),
+                Event::End => return body,
+                event => panic!("{:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn test_client_request_response() {
+        let mut conn = ClientConnection::new();
+        assert!(conn.is_reusable());
+        assert_eq!(conn.next_event().unwrap(), Event::Paused);
+
+        let headers = [HeaderField {
+            name: "Host",
+            value: "example.com",
+        }];
+        let mut out = Vec::new();
+        conn.send_request(&get("/", &headers), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert_eq!(out, b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert!(!conn.is_reusable());
+        assert!(!conn.can_send_request());
+
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.receive_data(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc");
+        assert_eq!(client_response(&mut conn).status(), 200);
+        assert_eq!(conn.next_event().unwrap(), Event::Data(b"abc"));
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.receive_data(b"\r\n0\r\n\r\n");
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert!(conn.is_reusable());
+
+        // A request body.
+        out.clear();
+        let headers = [HeaderField {
+            name: "Transfer-Encoding",
+            value: "chunked",
+        }];
+        let req = Request {
+            method: "POST",
+            target: "/",
+            minor_version: 1,
+            headers: &headers,
+        };
+        conn.send_request(&req, &mut out).unwrap();
+        assert!(conn.send_request(&req, &mut out).is_err());
+        conn.send_data(b"hello", &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert!(out.ends_with(b"\r\n\r\n5\r\nhello\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_client_pipelining() {
+        let mut conn = ClientConnection::new();
+        conn.set_pipeline_depth(2);
+        let head = Request {
+            method: "HEAD",
+            target: "/a",
+            minor_version: 1,
+            headers: &[],
+        };
+        let mut out = Vec::new();
+        conn.send_request(&head, &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        conn.send_request(&get("/b", &[]), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        assert!(!conn.can_send_request());
+        match conn.send_request(&get("/c", &[]), &mut out) {
+            Err(StreamError::Parse(InvalidState)) => (),
+            r => panic!("{:?}", r),
+        }
+
+        // The response to HEAD has no body.
+        conn.receive_data(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi");
+        client_response(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert!(conn.can_send_request());
+        client_response(&mut conn);
+        assert_eq!(client_body(&mut conn), b"hi");
+        assert!(conn.is_reusable());
+    }
+
+    #[test]
+    fn test_client_informational() {
+        let headers = [HeaderField {
+            name: "Expect",
+            value: "100-continue",
+        }];
+        let mut conn = ClientConnection::new();
+        let mut out = Vec::new();
+        conn.send_request(&get("/", &headers), &mut out).unwrap();
+        conn.receive_data(
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\n",
+        );
+        match conn.next_event().unwrap() {
+            Event::Informational(res) => assert_eq!(res.status(), 100),
+            event => panic!("{:?}", event),
+        }
+        match conn.next_event().unwrap() {
+            Event::Informational(res) => {
+                assert_eq!(res.status(), 103);
+                assert_eq!(res.headers().next().unwrap().value, "</a.css>");
+            }
+            event => panic!("{:?}", event),
+        }
+        assert_eq!(conn.next_event().unwrap(), Event::NeedData);
+        conn.send_end(&mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 204 No Content\r\n\r\n");
+        assert_eq!(client_response(&mut conn).status(), 204);
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+    }
+
+    #[test]
+    fn test_client_upgrade() {
+        let headers = [
+            HeaderField {
+                name: "Connection",
+                value: "upgrade",
+            },
+            HeaderField {
+                name: "Upgrade",
+                value: "websocket",
+            },
+        ];
+        let mut conn = ClientConnection::new();
+        conn.set_pipeline_depth(4);
+        let mut out = Vec::new();
+        conn.send_request(&get("/", &headers), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        // No requests are pipelined after an upgrade.
+        assert!(!conn.can_send_request());
+        conn.receive_data(
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n\x81\x00",
+        );
+        assert_eq!(client_response(&mut conn).status(), 101);
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b"\x81\x00"));
+        assert!(!conn.is_reusable());
+
+        let connect = Request {
+            method: "CONNECT",
+            target: "example.com:443",
+            minor_version: 1,
+            headers: &[],
+        };
+        let mut conn = ClientConnection::new();
+        conn.send_request(&connect, &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 200 Connection Established\r\n\r\n");
+        client_response(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::Upgrade(b""));
+
+        // A failed CONNECT has a body.
+        let mut conn = ClientConnection::new();
+        conn.send_request(&connect, &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 1\r\n\r\nx");
+        client_response(&mut conn);
+        assert_eq!(client_body(&mut conn), b"x");
+        assert!(conn.is_reusable());
+
+        // 101 without `Upgrade` in the request.
+        let mut conn = ClientConnection::new();
+        conn.send_request(&get("/", &[]), &mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 101 Switching Protocols\r\n\r\n");
+        match conn.next_event() {
+            Err(StreamError::Parse(InvalidState)) => (),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+    }
+
+    #[test]
+    fn test_client_close() {
+        // A body until the end of the connection.
+        let mut conn = ClientConnection::new();
+        let mut out = Vec::new();
+        conn.send_request(&get("/", &[]), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 200 OK\r\n\r\nabc");
+        client_response(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::Data(b"abc"));
+        conn.receive_eof();
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+        assert!(!conn.is_reusable());
+
+        let mut conn = ClientConnection::new();
+        conn.send_request(&get("/", &[]), &mut out).unwrap();
+        conn.send_end(&mut out).unwrap();
+        conn.receive_data(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+        client_response(&mut conn);
+        assert_eq!(conn.next_event().unwrap(), Event::End);
+        assert!(conn.is_closed());
+        assert!(conn.send_request(&get("/", &[]), &mut out).is_err());
+
+        // The server closes the connection before a response.
+        let mut conn = ClientConnection::new();
+        conn.send_request(&get("/", &[]), &mut out).unwrap();
+        conn.receive_eof();
+        match conn.next_event() {
+            Err(StreamError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            r => panic!("{:?}", r),
+        }
+
+        // A response without a request.
+        let mut conn = ClientConnection::new();
+        conn.receive_data(b"HTTP/1.1 408 Request Timeout\r\n\r\n");
+        assert!(conn.next_event().is_err());
+
+        let mut conn = ClientConnection::new();
+        conn.receive_eof();
+        assert_eq!(conn.next_event().unwrap(), Event::Closed);
+    }
+}
@@ -0,0 +1,333 @@
+//! HTTP-date.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::date::HttpDate;
+//!
+//! let date = HttpDate::parse("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+//! assert_eq!(date.unix_secs(), 784111777);
+//! assert_eq!(date, HttpDate::parse("Sunday, 06-Nov-94 08:49:37 GMT").unwrap());
+//! assert_eq!(date, HttpDate::parse("Sun Nov  6 08:49:37 1994").unwrap());
+//! ```
+use std::fmt;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::*;
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+
+const LONG_DAY_NAMES: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A timestamp in seconds precision as used in HTTP-date.
+///
+/// It is formatted by `Display` as IMF-fixdate.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HttpDate {
+    secs: u64,
+}
+
+impl HttpDate {
+    /// Makes a date from seconds since the unix epoch.
+    pub fn from_unix_secs(secs: u64) -> HttpDate {
+        HttpDate { secs }
+    }
+
+    /// Returns seconds since the unix epoch.
+    pub fn unix_secs(&self) -> u64 {
+        self.secs
+    }
+
+    /// Parse an HTTP-date.
+    ///
+    /// Accepts IMF-fixdate and the obsolete RFC 850 and asctime formats.
+    pub fn parse(s: &str) -> Result<HttpDate> {
+        let s = s.as_bytes();
+        let fields = if s.get(3) == Some(&b',') {
+            parse_imf_fixdate(s)
+        } else if s.get(3) == Some(&b' ') {
+            parse_asctime(s)
+        } else {
+            parse_rfc850(s)
+        };
+        let (year, month, day, hour, min, sec) = fields.ok_or(InvalidDate)?;
+
+        if year < 1970
+            || month == 0
+            || month > 12
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || min > 59
+            || sec > 60
+        {
+            return Err(InvalidDate);
+        }
+
+        let days = days_from_civil(year, month, day);
+        Ok(HttpDate {
+            secs: days * 86400 + hour * 3600 + min * 60 + sec,
+        })
+    }
+}
+
+impl fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = self.secs / 86400;
+        let secs = self.secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            DAY_NAMES[(days % 7) as usize],
+            day,
+            MONTH_NAMES[(month - 1) as usize],
+            year,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for HttpDate {
+    fn from(t: SystemTime) -> HttpDate {
+        HttpDate::from_unix_secs(t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<HttpDate> for SystemTime {
+    fn from(date: HttpDate) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(date.secs)
+    }
+}
+
+type DateFields = (u64, u64, u64, u64, u64, u64);
+
+// Sun, 06 Nov 1994 08:49:37 GMT
+fn parse_imf_fixdate(s: &[u8]) -> Option<DateFields> {
+    if s.len() != 29 || s[4] != b' ' || s[7] != b' ' || s[11] != b' ' || s[16] != b' ' {
+        return None;
+    }
+    if !DAY_NAMES.iter().any(|d| d.as_bytes() == &s[..3]) || &s[25..] != b" GMT" {
+        return None;
+    }
+    let (hour, min, sec) = parse_time(&s[17..25])?;
+    Some((
+        parse_number(&s[12..16])?,
+        parse_month(&s[8..11])?,
+        parse_number(&s[5..7])?,
+        hour,
+        min,
+        sec,
+    ))
+}
+
+// Sunday, 06-Nov-94 08:49:37 GMT
+fn parse_rfc850(s: &[u8]) -> Option<DateFields> {
+    let comma = s.iter().position(|c| *c == b',')?;
+    if !LONG_DAY_NAMES.iter().any(|d| d.as_bytes() == &s[..comma]) {
+        return None;
+    }
+    let s = &s[comma..];
+    if s.len() != 24 || s[1] != b' ' || s[4] != b'-' || s[8] != b'-' || s[11] != b' ' {
+        return None;
+    }
+    if &s[20..] != b" GMT" {
+        return None;
+    }
+    let (hour, min, sec) = parse_time(&s[12..20])?;
+    let year = parse_number(&s[9..11])?;
+    Some((
+        if year < 70 { 2000 + year } else { 1900 + year },
+        parse_month(&s[5..8])?,
+        parse_number(&s[2..4])?,
+        hour,
+        min,
+        sec,
+    ))
+}
+
+// Sun Nov  6 08:49:37 1994
+fn parse_asctime(s: &[u8]) -> Option<DateFields> {
+    if s.len() != 24 || s[7] != b' ' || s[10] != b' ' || s[19] != b' ' {
+        return None;
+    }
+    if !DAY_NAMES.iter().any(|d| d.as_bytes() == &s[..3]) {
+        return None;
+    }
+    let day = if s[8] == b' ' {
+        parse_number(&s[9..10])?
+    } else {
+        parse_number(&s[8..10])?
+    };
+    let (hour, min, sec) = parse_time(&s[11..19])?;
+    Some((
+        parse_number(&s[20..24])?,
+        parse_month(&s[4..7])?,
+        day,
+        hour,
+        min,
+        sec,
+    ))
+}
+
+fn parse_time(s: &[u8]) -> Option<(u64, u64, u64)> {
+    if s[2] != b':' || s[5] != b':' {
+        return None;
+    }
+    Some((
+        parse_number(&s[0..2])?,
+        parse_number(&s[3..5])?,
+        parse_number(&s[6..8])?,
+    ))
+}
+
+fn parse_number(s: &[u8]) -> Option<u64> {
+    s.iter().try_fold(0, |acc, c| {
+        crate::to_digit(*c).map(|d| acc * 10 + u64::from(d))
+    })
+}
+
+fn parse_month(s: &[u8]) -> Option<u64> {
+    MONTH_NAMES
+        .iter()
+        .position(|m| m.as_bytes() == s)
+        .map(|i| i as u64 + 1)
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a date after the epoch.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+
+    use crate::date::*;
+
+    #[test]
+    fn test_parse_formats() {
+        let expected = HttpDate::from_unix_secs(784_111_777);
+        assert_eq!(
+            HttpDate::parse("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Ok(expected)
+        );
+        assert_eq!(
+            HttpDate::parse("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Ok(expected)
+        );
+        assert_eq!(HttpDate::parse("Sun Nov  6 08:49:37 1994"), Ok(expected));
+        assert_eq!(
+            HttpDate::parse("Thursday, 01-Jan-70 00:00:00 GMT"),
+            Ok(HttpDate::from_unix_secs(0))
+        );
+        assert_eq!(
+            HttpDate::parse("Wed, 29 Feb 2040 23:59:59 GMT"),
+            Ok(HttpDate::from_unix_secs(2_214_172_799))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_dates() {
+        assert_eq!(HttpDate::parse(""), Err(InvalidDate));
+        assert_eq!(
+            HttpDate::parse("Sun, 06 Nov 1994 08:49:37 UTC"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Sun, 06 Foo 1994 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Xyz, 06 Nov 1994 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Sun, 31 Nov 1994 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Sun, 06 Nov 1994 24:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Sun, 06 Nov 1969 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(
+            HttpDate::parse("Sun, 6 Nov 1994 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+        assert_eq!(HttpDate::parse("Sun Nov 6 08:49:37 1994"), Err(InvalidDate));
+        assert_eq!(
+            HttpDate::parse("Sunday, 06-Nov-1994 08:49:37 GMT"),
+            Err(InvalidDate)
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            HttpDate::from_unix_secs(784_111_777).to_string(),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_unix_secs(0).to_string(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_unix_secs(2_214_172_799).to_string(),
+            "Wed, 29 Feb 2040 23:59:59 GMT"
+        );
+    }
+}
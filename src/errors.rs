@@ -1,3 +1,6 @@
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
+
 /// An error in parsing the http header.
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Error {
@@ -19,6 +22,48 @@ pub enum Error {
     InvalidReasonPhrase,
     /// Too many header fields.
     OutOfCapacity,
+    /// Invalid value of `Range` header field.
+    InvalidRange,
+    /// Invalid value of `Content-Range` header field.
+    InvalidContentRange,
+    /// Invalid entity-tag.
+    InvalidEntityTag,
+    /// Invalid HTTP-date.
+    InvalidDate,
+    /// Invalid credentials in `Authorization` header field.
+    InvalidCredentials,
+    /// Invalid challenge in `WWW-Authenticate` header field.
+    InvalidChallenge,
+    /// Invalid structured field value.
+    InvalidStructuredField,
+    /// Buffer is too small to write a message into.
+    BufferTooSmall,
+    /// Failed to write a message into a writer.
+    WriteFailed,
+    /// Invalid chunk extension.
+    InvalidChunkExtension,
+    /// Invalid chunk size.
+    InvalidChunkSize,
+    /// Invalid value of `Content-Length` header field.
+    InvalidContentLength,
+    /// Invalid value of `Transfer-Encoding` header field.
+    InvalidTransferEncoding,
+    /// Message sent or received in an invalid state of a connection.
+    InvalidState,
+    /// Invalid WebSocket opening handshake.
+    InvalidHandshake,
+    /// Invalid protocol upgrade.
+    InvalidUpgrade,
+    /// Invalid HTTP/2 settings.
+    InvalidSettings,
+    /// Invalid HPACK header block.
+    InvalidHeaderBlock,
+    /// Invalid PROXY protocol header.
+    InvalidProxyHeader,
+    /// Invalid value of `Forwarded` header field.
+    InvalidForwarded,
+    /// Invalid value of `Via` header field.
+    InvalidVia,
 }
 
 pub use crate::Error::*;
@@ -35,6 +80,27 @@ impl Error {
             InvalidStatusCode => "invalid status code",
             InvalidReasonPhrase => "invalid reason phrase",
             OutOfCapacity => "out of capacity",
+            InvalidRange => "invalid range",
+            InvalidContentRange => "invalid content range",
+            InvalidEntityTag => "invalid entity tag",
+            InvalidDate => "invalid date",
+            InvalidCredentials => "invalid credentials",
+            InvalidChallenge => "invalid challenge",
+            InvalidStructuredField => "invalid structured field",
+            BufferTooSmall => "buffer too small",
+            WriteFailed => "write failed",
+            InvalidChunkExtension => "invalid chunk extension",
+            InvalidChunkSize => "invalid chunk size",
+            InvalidContentLength => "invalid content length",
+            InvalidTransferEncoding => "invalid transfer encoding",
+            InvalidState => "invalid state",
+            InvalidHandshake => "invalid handshake",
+            InvalidUpgrade => "invalid upgrade",
+            InvalidSettings => "invalid settings",
+            InvalidHeaderBlock => "invalid header block",
+            InvalidProxyHeader => "invalid proxy header",
+            InvalidForwarded => "invalid forwarded",
+            InvalidVia => "invalid via",
         }
     }
 }
@@ -52,5 +118,100 @@ impl ::std::error::Error for Error {
     }
 }
 
+#[cfg(feature = "std")]
+impl From<Error> for ::std::io::Error {
+    fn from(err: Error) -> ::std::io::Error {
+        ::std::io::Error::new(::std::io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// An error in reading or writing messages on a stream.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum StreamError {
+    /// An I/O error.
+    Io(::std::io::Error),
+    /// An invalid message.
+    Parse(Error),
+    /// A message head exceeds the limit.
+    HeadTooLarge,
+    /// A message body exceeds the limit.
+    BodyTooLarge,
+    /// A chunk size line or trailer section of a body exceeds the limit of
+    /// the head length.
+    FramingTooLarge,
+    /// A message head has more header fields than the limit.
+    TooManyHeaders,
+}
+
+#[cfg(feature = "std")]
+impl ::std::fmt::Display for StreamError {
+    fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            StreamError::Io(ref err) => write!(fmt, "{}", err),
+            StreamError::Parse(ref err) => write!(fmt, "{}", err),
+            StreamError::HeadTooLarge => write!(fmt, "message head too large"),
+            StreamError::BodyTooLarge => write!(fmt, "message body too large"),
+            StreamError::FramingTooLarge => write!(fmt, "body framing too large"),
+            StreamError::TooManyHeaders => write!(fmt, "too many header fields"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            StreamError::Io(ref err) => Some(err),
+            StreamError::Parse(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Returns the error of a connection closed in a message.
+#[cfg(feature = "std")]
+pub(crate) fn unexpected_eof() -> StreamError {
+    StreamError::Io(::std::io::Error::new(
+        ::std::io::ErrorKind::UnexpectedEof,
+        "connection closed in a message",
+    ))
+}
+
+// Returns the error of parsing a head, where running out of capacity for
+// header fields is the limit on their number.
+#[cfg(feature = "std")]
+pub(crate) fn head_error(err: Error) -> StreamError {
+    match err {
+        OutOfCapacity => StreamError::TooManyHeaders,
+        err => StreamError::Parse(err),
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<::std::io::Error> for StreamError {
+    fn from(err: ::std::io::Error) -> StreamError {
+        StreamError::Io(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for StreamError {
+    fn from(err: Error) -> StreamError {
+        StreamError::Parse(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<StreamError> for ::std::io::Error {
+    fn from(err: StreamError) -> ::std::io::Error {
+        match err {
+            StreamError::Io(err) => err,
+            StreamError::Parse(err) => err.into(),
+            err => ::std::io::Error::new(::std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 /// A result type in parsing http header.
 pub type Result<T> = ::std::result::Result<T, Error>;
@@ -0,0 +1,165 @@
+use crate::HeaderField;
+
+/// Finds the first header field named `name`, ignoring ASCII case.
+#[inline]
+pub fn find<'buffer>(headers: &[HeaderField<'buffer>], name: &str) -> Option<&'buffer str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value)
+}
+
+/// Iterates values of all header fields named `name`, ignoring ASCII case.
+#[inline]
+pub fn find_all<'a, 'buffer>(
+    headers: &'a [HeaderField<'buffer>],
+    name: &'a str,
+) -> impl Iterator<Item = &'buffer str> + 'a {
+    headers
+        .iter()
+        .filter(move |h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value)
+}
+
+/// Returns `true` if any header field named `name` has `token` in its
+/// comma separated list, ignoring ASCII case.
+#[inline]
+pub fn has_token(headers: &[HeaderField], name: &str, token: &str) -> bool {
+    find_all(headers, name)
+        .flat_map(ListIter::new)
+        .any(|v| v.eq_ignore_ascii_case(token))
+}
+
+/// Removes leading and trailing optional whitespaces.
+#[inline]
+pub fn trim_ows(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+/// Returns `true` if `s` is a non empty token.
+#[inline]
+pub fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(crate::is_tchar)
+}
+
+/// Parses a non empty sequence of decimal digits without sign.
+#[inline]
+pub fn parse_u64(s: &str) -> Option<u64> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut v: u64 = 0;
+    for c in s.bytes() {
+        let d = crate::to_digit(c)?;
+        v = v.checked_mul(10)?.checked_add(u64::from(d))?;
+    }
+    Some(v)
+}
+
+/// Iterates elements of a comma separated list (`#rule`).
+///
+/// Commas inside quoted strings don't split elements, and empty elements are
+/// skipped.
+#[derive(Clone, Debug)]
+pub struct ListIter<'buffer> {
+    rest: &'buffer str,
+}
+
+impl<'buffer> ListIter<'buffer> {
+    #[inline]
+    pub fn new(value: &'buffer str) -> ListIter<'buffer> {
+        ListIter { rest: value }
+    }
+}
+
+impl<'buffer> Iterator for ListIter<'buffer> {
+    type Item = &'buffer str;
+
+    fn next(&mut self) -> Option<&'buffer str> {
+        while !self.rest.is_empty() {
+            let bytes = self.rest.as_bytes();
+            let mut quoted = false;
+            let mut i = 0;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'\\' if quoted => i += 1,
+                    b'"' => quoted = !quoted,
+                    b',' if !quoted => break,
+                    _ => (),
+                }
+                i += 1;
+            }
+
+            let end = i.min(bytes.len());
+            let elem = trim_ows(&self.rest[..end]);
+            self.rest = if end < bytes.len() {
+                &self.rest[end + 1..]
+            } else {
+                ""
+            };
+
+            if !elem.is_empty() {
+                return Some(elem);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::fields::*;
+
+    #[test]
+    fn test_find_ignores_case() {
+        let headers = [
+            HeaderField {
+                name: "Host",
+                value: "example.com",
+            },
+            HeaderField {
+                name: "range",
+                value: "bytes=0-1",
+            },
+        ];
+        assert_eq!(find(&headers, "RANGE"), Some("bytes=0-1"));
+        assert_eq!(find(&headers, "accept"), None);
+    }
+
+    #[test]
+    fn test_has_token() {
+        let headers = [
+            HeaderField {
+                name: "Connection",
+                value: "keep-alive",
+            },
+            HeaderField {
+                name: "connection",
+                value: " Upgrade , close",
+            },
+        ];
+        assert!(has_token(&headers, "Connection", "upgrade"));
+        assert!(has_token(&headers, "CONNECTION", "Keep-Alive"));
+        assert!(!has_token(&headers, "Connection", "te"));
+        assert!(!has_token(&headers, "Upgrade", "close"));
+    }
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64("0"), Some(0));
+        assert_eq!(parse_u64("1234"), Some(1234));
+        assert_eq!(parse_u64(""), None);
+        assert_eq!(parse_u64("+1"), None);
+        assert_eq!(parse_u64("18446744073709551616"), None);
+    }
+
+    #[test]
+    fn test_list_iter() {
+        let v: Vec<_> = ListIter::new(" a , ,b,\"c,d\" ,, \"e\\\",f\"").collect();
+        assert_eq!(v, ["a", "b", "\"c,d\"", "\"e\\\",f\""]);
+        assert_eq!(ListIter::new(" , ").next(), None);
+    }
+}
@@ -0,0 +1,972 @@
+//! Helpers for proxies forwarding messages.
+//!
+//! Hop-by-hop header fields, which are the fixed ones and those listed in
+//! `Connection`, are removed from a forwarded message, and the proxy is
+//! recorded in `Forwarded` (RFC 7239), `X-Forwarded-For`,
+//! `X-Forwarded-Proto` and `Via`. `HeaderList` holds the header fields to
+//! forward: unchanged fields borrow the parsed buffer, and the list is
+//! written out with `Request::encode` and friends.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::forwarding::{origin_form, ForwardedElement, HeaderList, Node, NodeName, Via};
+//!
+//! let buf = b"GET http://example.com/index.html HTTP/1.1\r\nHost: example.com\r\n\
+//!             Connection: close, X-Trace\r\nX-Trace: 1\r\nAccept: */*\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! let target = origin_form(req.target).unwrap();
+//! let client = "192.0.2.43".parse().unwrap();
+//! let mut list = HeaderList::end_to_end(req.headers);
+//! list.set("Host", target.authority);
+//! list.append_forwarded(&ForwardedElement {
+//!     for_: Some(Node { name: NodeName::Ip(client), port: None }),
+//!     proto: Some("http"),
+//!     ..ForwardedElement::default()
+//! })
+//! .unwrap();
+//! list.append_x_forwarded_for(client);
+//! list.append_via(&Via::new("1.1", "proxy.example")).unwrap();
+//!
+//! let headers = list.header_fields();
+//! let req = thhp::Request { target: &target.target, headers: &headers, ..req };
+//! let mut out = [0u8; 256];
+//! let len = req.encode(&mut out).unwrap();
+//! assert_eq!(
+//!     &out[..len],
+//!     &b"GET /index.html HTTP/1.1\r\nAccept: */*\r\nHost: example.com\r\n\
+//!        Forwarded: for=192.0.2.43;proto=http\r\nX-Forwarded-For: 192.0.2.43\r\n\
+//!        Via: 1.1 proxy.example\r\n\r\n"[..]
+//! );
+//! ```
+#[cfg(not(feature = "std"))]
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv6Addr};
+
+use crate::errors::*;
+use crate::fields::{find_all, has_token, is_token, trim_ows, ListIter};
+use crate::HeaderField;
+
+/// Header fields which are always hop-by-hop.
+pub const HOP_BY_HOP: [&str; 9] = [
+    "Connection",
+    "Keep-Alive",
+    "Proxy-Authenticate",
+    "Proxy-Authorization",
+    "Proxy-Connection",
+    "TE",
+    "Trailer",
+    "Transfer-Encoding",
+    "Upgrade",
+];
+
+/// The hop-by-hop header fields of a message.
+#[derive(Copy, Clone, Debug)]
+pub struct HopByHop<'a, 'buffer: 'a> {
+    headers: &'a [HeaderField<'buffer>],
+}
+
+impl<'a, 'buffer> HopByHop<'a, 'buffer> {
+    /// Creates the set of hop-by-hop header fields of a message with
+    /// `headers`.
+    pub fn new(headers: &'a [HeaderField<'buffer>]) -> HopByHop<'a, 'buffer> {
+        HopByHop { headers }
+    }
+
+    /// Returns `true` if a field named `name` is hop-by-hop, ignoring ASCII
+    /// case.
+    pub fn contains(&self, name: &str) -> bool {
+        HOP_BY_HOP.iter().any(|n| n.eq_ignore_ascii_case(name))
+            || has_token(self.headers, "Connection", name)
+    }
+
+    /// Returns an iterator over the names of hop-by-hop fields: the fixed
+    /// ones followed by the ones listed in `Connection`.
+    pub fn names(&self) -> impl Iterator<Item = &'buffer str> + 'a {
+        HOP_BY_HOP
+            .iter()
+            .cloned()
+            .chain(find_all(self.headers, "Connection").flat_map(ListIter::new))
+    }
+
+    /// Returns an iterator over the end-to-end header fields.
+    pub fn filter(self) -> impl Iterator<Item = &'a HeaderField<'buffer>> + 'a {
+        self.headers.iter().filter(move |h| !self.contains(h.name))
+    }
+}
+
+/// Returns an iterator over the end-to-end header fields of `headers`.
+pub fn end_to_end<'a, 'buffer>(
+    headers: &'a [HeaderField<'buffer>],
+) -> impl Iterator<Item = &'a HeaderField<'buffer>> + 'a {
+    HopByHop::new(headers).filter()
+}
+
+/// The name of a node in `Forwarded`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum NodeName<'buffer> {
+    /// An IP address.
+    Ip(IpAddr),
+    /// `unknown`.
+    Unknown,
+    /// An obfuscated identifier starting with `_`.
+    Obfuscated(&'buffer str),
+}
+
+/// The port of a node in `Forwarded`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum NodePort<'buffer> {
+    /// A port number.
+    Port(u16),
+    /// An obfuscated port starting with `_`.
+    Obfuscated(&'buffer str),
+}
+
+/// A node in `Forwarded`, such as `192.0.2.43` or `"[2001:db8::17]:4711"`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Node<'buffer> {
+    /// The name.
+    pub name: NodeName<'buffer>,
+    /// The port.
+    pub port: Option<NodePort<'buffer>>,
+}
+
+// obfnode = "_" 1*( ALPHA / DIGIT / "." / "_" / "-")
+fn is_obfuscated(s: &str) -> bool {
+    s.len() > 1
+        && s.starts_with('_')
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'-')
+}
+
+impl<'buffer> Node<'buffer> {
+    fn parse(s: &'buffer str) -> Option<Node<'buffer>> {
+        let (name, port) = if let Some(rest) = s.strip_prefix('[') {
+            let end = rest.find(']')?;
+            let ip: Ipv6Addr = rest[..end].parse().ok()?;
+            (NodeName::Ip(IpAddr::V6(ip)), &rest[end + 1..])
+        } else {
+            let (name, port) = s.split_at(s.find(':').unwrap_or(s.len()));
+            let name = if name.eq_ignore_ascii_case("unknown") {
+                NodeName::Unknown
+            } else if is_obfuscated(name) {
+                NodeName::Obfuscated(name)
+            } else {
+                NodeName::Ip(IpAddr::V4(name.parse().ok()?))
+            };
+            (name, port)
+        };
+        let port = match port.strip_prefix(':') {
+            None if port.is_empty() => None,
+            Some(port) if is_obfuscated(port) => Some(NodePort::Obfuscated(port)),
+            Some(port) if port.len() <= 5 && port.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(NodePort::Port(port.parse().ok()?))
+            }
+            _ => return None,
+        };
+        Some(Node { name, port })
+    }
+
+    fn write(&self, s: &mut String) {
+        // Writing into a `String` never fails.
+        let _ = match self.name {
+            NodeName::Ip(IpAddr::V4(ip)) => write!(s, "{}", ip),
+            NodeName::Ip(IpAddr::V6(ip)) => write!(s, "[{}]", ip),
+            NodeName::Unknown => write!(s, "unknown"),
+            NodeName::Obfuscated(name) => write!(s, "{}", name),
+        };
+        let _ = match self.port {
+            Some(NodePort::Port(port)) => write!(s, ":{}", port),
+            Some(NodePort::Obfuscated(port)) => write!(s, ":{}", port),
+            None => Ok(()),
+        };
+    }
+}
+
+/// An element of `Forwarded`, added by a proxy.
+///
+/// Quotes of a quoted-string are removed but escapes are left as is.
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ForwardedElement<'buffer> {
+    /// `by`, the interface where the request came in to the proxy.
+    pub by: Option<Node<'buffer>>,
+    /// `for`, the client which made the request to the proxy.
+    pub for_: Option<Node<'buffer>>,
+    /// `host`, the value of `Host` received by the proxy.
+    pub host: Option<&'buffer str>,
+    /// `proto`, the scheme of the request received by the proxy.
+    pub proto: Option<&'buffer str>,
+}
+
+// Reads a quoted-string, returns the content without quotes and the rest.
+fn read_quoted_string(s: &str) -> Option<(&str, &str)> {
+    let bytes = s.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return None;
+    }
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Some((&s[1..i], &s[i + 1..])),
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Reads a token or a quoted-string.
+fn read_value(s: &str) -> Option<(&str, &str)> {
+    if s.starts_with('"') {
+        return read_quoted_string(s);
+    }
+    let n = s.bytes().take_while(|c| crate::is_tchar(*c)).count();
+    if n == 0 {
+        return None;
+    }
+    Some(s.split_at(n))
+}
+
+// Appends `value` as a token, or as a quoted-string unless it is a token.
+fn push_value(s: &mut String, value: &str) -> Result<()> {
+    if is_token(value) {
+        s.push_str(value);
+        return Ok(());
+    }
+    s.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                s.push('\\');
+                s.push(c);
+            }
+            '\t' => s.push(c),
+            _ if c.is_ascii_control() => return Err(InvalidForwarded),
+            _ => s.push(c),
+        }
+    }
+    s.push('"');
+    Ok(())
+}
+
+// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+fn is_scheme(s: &str) -> bool {
+    s.bytes().next().is_some_and(|b| b.is_ascii_alphabetic())
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'-' || b == b'.')
+}
+
+impl<'buffer> ForwardedElement<'buffer> {
+    /// Parses an element of `Forwarded`.
+    ///
+    /// Parameters other than `by`, `for`, `host` and `proto` are ignored.
+    pub fn parse(s: &'buffer str) -> Result<ForwardedElement<'buffer>> {
+        let mut element = ForwardedElement::default();
+        let mut rest = trim_ows(s);
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix(';') {
+                rest = trim_ows(r);
+                continue;
+            }
+            let n = rest.bytes().take_while(|c| crate::is_tchar(*c)).count();
+            let (name, r) = rest.split_at(n);
+            let (value, r) = match r.strip_prefix('=').and_then(read_value) {
+                Some(v) if !name.is_empty() => v,
+                _ => return Err(InvalidForwarded),
+            };
+            rest = trim_ows(r);
+            if !rest.is_empty() && !rest.starts_with(';') {
+                return Err(InvalidForwarded);
+            }
+
+            // Each parameter occurs at most once.
+            let duplicate = if name.eq_ignore_ascii_case("by") {
+                element
+                    .by
+                    .replace(Node::parse(value).ok_or(InvalidForwarded)?)
+                    .is_some()
+            } else if name.eq_ignore_ascii_case("for") {
+                element
+                    .for_
+                    .replace(Node::parse(value).ok_or(InvalidForwarded)?)
+                    .is_some()
+            } else if name.eq_ignore_ascii_case("host") {
+                element.host.replace(value).is_some()
+            } else if name.eq_ignore_ascii_case("proto") {
+                if !is_scheme(value) {
+                    return Err(InvalidForwarded);
+                }
+                element.proto.replace(value).is_some()
+            } else {
+                false
+            };
+            if duplicate {
+                return Err(InvalidForwarded);
+            }
+        }
+        Ok(element)
+    }
+
+    fn write(&self, s: &mut String) -> Result<()> {
+        let mut sep = "";
+        let mut node = String::new();
+        for &(name, value) in &[("for", self.for_), ("by", self.by)] {
+            if let Some(value) = value {
+                node.clear();
+                value.write(&mut node);
+                s.push_str(sep);
+                s.push_str(name);
+                s.push('=');
+                push_value(s, &node)?;
+                sep = ";";
+            }
+        }
+        for &(name, value) in &[("host", self.host), ("proto", self.proto)] {
+            if let Some(value) = value {
+                s.push_str(sep);
+                s.push_str(name);
+                s.push('=');
+                push_value(s, value)?;
+                sep = ";";
+            }
+        }
+        if sep.is_empty() {
+            return Err(InvalidForwarded);
+        }
+        Ok(())
+    }
+}
+
+/// Returns an iterator over the elements of `Forwarded`, the first of which
+/// is added by the first proxy.
+pub fn forwarded<'a, 'buffer>(
+    headers: &'a [HeaderField<'buffer>],
+) -> impl Iterator<Item = Result<ForwardedElement<'buffer>>> + 'a {
+    find_all(headers, "Forwarded")
+        .flat_map(ListIter::new)
+        .map(ForwardedElement::parse)
+}
+
+/// Returns an iterator over the addresses in `X-Forwarded-For`, the first
+/// of which is the client.
+///
+/// The addresses are not validated.
+pub fn x_forwarded_for<'a, 'buffer>(
+    headers: &'a [HeaderField<'buffer>],
+) -> impl Iterator<Item = &'buffer str> + 'a {
+    find_all(headers, "X-Forwarded-For").flat_map(ListIter::new)
+}
+
+/// Returns an iterator over the schemes in `X-Forwarded-Proto`, the first of
+/// which is used by the client.
+pub fn x_forwarded_proto<'a, 'buffer>(
+    headers: &'a [HeaderField<'buffer>],
+) -> impl Iterator<Item = &'buffer str> + 'a {
+    find_all(headers, "X-Forwarded-Proto").flat_map(ListIter::new)
+}
+
+/// An element of `Via`, added by a recipient of the message.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Via<'buffer> {
+    /// The protocol name, which is omitted for HTTP.
+    pub protocol: Option<&'buffer str>,
+    /// The protocol version.
+    pub version: &'buffer str,
+    /// The host and port or the pseudonym of the recipient.
+    pub received_by: &'buffer str,
+    /// The comment without parentheses.
+    pub comment: Option<&'buffer str>,
+}
+
+// Splits at the first whitespace or comma.
+fn read_word(s: &str) -> (&str, &str) {
+    s.split_at(s.find([' ', '\t', ',']).unwrap_or(s.len()))
+}
+
+// Reads a comment, returns the content without parentheses and the rest.
+fn read_comment(s: &str) -> Option<(&str, &str)> {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&s[1..i], &s[i + 1..]));
+                }
+            }
+            b'\\' => i += 1,
+            _ => (),
+        }
+        i += 1;
+    }
+    None
+}
+
+impl<'buffer> Via<'buffer> {
+    /// Makes an element received in `protocol`, such as `1.1` for HTTP/1.1
+    /// or `HTTP/2`, by `received_by`.
+    pub fn new(protocol: &'buffer str, received_by: &'buffer str) -> Via<'buffer> {
+        let (protocol, version) = match protocol.find('/') {
+            Some(i) => (Some(&protocol[..i]), &protocol[i + 1..]),
+            None => (None, protocol),
+        };
+        Via {
+            protocol,
+            version,
+            received_by,
+            comment: None,
+        }
+    }
+
+    // Parses an element at the start of `s`, returns it and the rest.
+    fn parse(s: &'buffer str) -> Option<(Via<'buffer>, &'buffer str)> {
+        let (protocol, rest) = read_word(s);
+        let rest = rest.strip_prefix([' ', '\t'])?;
+        let (received_by, rest) = read_word(trim_ows(rest));
+        let mut via = Via::new(protocol, received_by);
+        if !via.is_valid() {
+            return None;
+        }
+        let mut rest = trim_ows(rest);
+        if rest.starts_with('(') {
+            let (comment, r) = read_comment(rest)?;
+            via.comment = Some(comment);
+            rest = trim_ows(r);
+        }
+        match rest.strip_prefix(',') {
+            Some(rest) => Some((via, rest)),
+            None if rest.is_empty() => Some((via, rest)),
+            None => None,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.protocol.map_or(true, is_token)
+            && is_token(self.version)
+            && !self.received_by.is_empty()
+            && !self.received_by.contains([' ', '\t', ',', '(', ')'])
+    }
+
+    fn write(&self, s: &mut String) -> Result<()> {
+        if !self.is_valid() {
+            return Err(InvalidVia);
+        }
+        if let Some(protocol) = self.protocol {
+            s.push_str(protocol);
+            s.push('/');
+        }
+        s.push_str(self.version);
+        s.push(' ');
+        s.push_str(self.received_by);
+        if let Some(comment) = self.comment {
+            s.push(' ');
+            let start = s.len();
+            s.push('(');
+            s.push_str(comment);
+            s.push(')');
+            // The comment must be balanced without control characters.
+            if read_comment(&s[start..]) != Some((comment, ""))
+                || comment.bytes().any(|b| b.is_ascii_control() && b != b'\t')
+            {
+                return Err(InvalidVia);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the elements of `Via`, the first of which is added by
+/// the first recipient.
+#[derive(Clone, Debug)]
+pub struct ViaElements<'a, 'buffer: 'a> {
+    headers: &'a [HeaderField<'buffer>],
+    rest: &'buffer str,
+}
+
+impl<'a, 'buffer> Iterator for ViaElements<'a, 'buffer> {
+    type Item = Result<Via<'buffer>>;
+
+    fn next(&mut self) -> Option<Result<Via<'buffer>>> {
+        loop {
+            let rest = self.rest.trim_start_matches([' ', '\t', ',']);
+            if !rest.is_empty() {
+                return Some(match Via::parse(rest) {
+                    Some((via, rest)) => {
+                        self.rest = rest;
+                        Ok(via)
+                    }
+                    None => {
+                        // Stop at an invalid element.
+                        self.rest = "";
+                        self.headers = &[];
+                        Err(InvalidVia)
+                    }
+                });
+            }
+            let (header, headers) = self.headers.split_first()?;
+            self.headers = headers;
+            self.rest = if header.name.eq_ignore_ascii_case("Via") {
+                header.value
+            } else {
+                ""
+            };
+        }
+    }
+}
+
+/// Returns an iterator over the elements of `Via`.
+pub fn via<'a, 'buffer>(headers: &'a [HeaderField<'buffer>]) -> ViaElements<'a, 'buffer> {
+    ViaElements { headers, rest: "" }
+}
+
+/// An absolute-form target rewritten to the origin-form.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OriginForm<'buffer> {
+    /// The scheme.
+    pub scheme: &'buffer str,
+    /// The authority without user information, which is the value of
+    /// `Host` to forward.
+    pub authority: &'buffer str,
+    /// The target in the origin-form.
+    pub target: Cow<'buffer, str>,
+}
+
+/// Rewrites an absolute-form target, such as `http://example.com/a?b`, to
+/// the origin-form, `/a?b`.
+///
+/// Returns `None` unless `target` is in the absolute-form.
+pub fn origin_form(target: &str) -> Option<OriginForm<'_>> {
+    let i = target.find("://")?;
+    let scheme = &target[..i];
+    if !is_scheme(scheme) {
+        return None;
+    }
+    let rest = &target[i + 3..];
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..end];
+    let authority = match authority.rfind('@') {
+        Some(j) => &authority[j + 1..],
+        None => authority,
+    };
+    if authority.is_empty() {
+        return None;
+    }
+    let path = &rest[end..];
+    let path = &path[..path.find('#').unwrap_or(path.len())];
+    let target = if path.starts_with('/') {
+        Cow::Borrowed(path)
+    } else {
+        let mut s = String::with_capacity(path.len() + 1);
+        s.push('/');
+        s.push_str(path);
+        Cow::Owned(s)
+    };
+    Some(OriginForm {
+        scheme,
+        authority,
+        target,
+    })
+}
+
+/// A list of header fields to forward.
+///
+/// Fields from a parsed message borrow the buffer, and only changed values
+/// are owned.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct HeaderList<'buffer> {
+    fields: Vec<(&'buffer str, Cow<'buffer, str>)>,
+}
+
+impl<'buffer> HeaderList<'buffer> {
+    /// Creates an empty list.
+    pub fn new() -> HeaderList<'buffer> {
+        HeaderList { fields: Vec::new() }
+    }
+
+    /// Creates a list of the end-to-end fields in `headers`.
+    pub fn end_to_end(headers: &[HeaderField<'buffer>]) -> HeaderList<'buffer> {
+        HeaderList {
+            fields: end_to_end(headers)
+                .map(|h| (h.name, Cow::Borrowed(h.value)))
+                .collect(),
+        }
+    }
+
+    /// Returns the value of the first field named `name`, ignoring ASCII
+    /// case.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| &v[..])
+    }
+
+    /// Appends a field.
+    pub fn push<V>(&mut self, name: &'buffer str, value: V)
+    where
+        V: Into<Cow<'buffer, str>>,
+    {
+        self.fields.push((name, value.into()));
+    }
+
+    /// Removes all fields named `name`, ignoring ASCII case.
+    pub fn remove(&mut self, name: &str) {
+        self.fields.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    /// Replaces all fields named `name` by a field.
+    pub fn set<V>(&mut self, name: &'buffer str, value: V)
+    where
+        V: Into<Cow<'buffer, str>>,
+    {
+        self.remove(name);
+        self.push(name, value);
+    }
+
+    // Appends an element to the last field named `name`, or appends a field.
+    fn append(&mut self, name: &'buffer str, element: String) {
+        match self
+            .fields
+            .iter_mut()
+            .rev()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        {
+            Some((_, value)) => {
+                let value = value.to_mut();
+                value.push_str(", ");
+                value.push_str(&element);
+            }
+            None => self.push(name, element),
+        }
+    }
+
+    /// Appends an element to `Forwarded`.
+    ///
+    /// Returns `InvalidForwarded` if the element is empty or a value has
+    /// control characters.
+    pub fn append_forwarded(&mut self, element: &ForwardedElement) -> Result<()> {
+        let mut s = String::new();
+        element.write(&mut s)?;
+        self.append("Forwarded", s);
+        Ok(())
+    }
+
+    /// Appends the address of the client to `X-Forwarded-For`.
+    pub fn append_x_forwarded_for(&mut self, ip: IpAddr) {
+        let mut s = String::new();
+        // Writing into a `String` never fails.
+        let _ = write!(s, "{}", ip);
+        self.append("X-Forwarded-For", s);
+    }
+
+    /// Appends the scheme used by the client to `X-Forwarded-Proto`.
+    ///
+    /// Returns `InvalidFieldValue` if `scheme` is not a scheme.
+    pub fn append_x_forwarded_proto(&mut self, scheme: &str) -> Result<()> {
+        if !is_scheme(scheme) {
+            return Err(InvalidFieldValue);
+        }
+        self.append("X-Forwarded-Proto", scheme.into());
+        Ok(())
+    }
+
+    /// Appends an element to `Via`.
+    ///
+    /// Returns `InvalidVia` if a part of the element is invalid.
+    pub fn append_via(&mut self, via: &Via) -> Result<()> {
+        let mut s = String::new();
+        via.write(&mut s)?;
+        self.append("Via", s);
+        Ok(())
+    }
+
+    /// Returns the fields, which are written out as a part of a `Request` or
+    /// `Response`.
+    pub fn header_fields(&self) -> Vec<HeaderField<'_>> {
+        self.fields
+            .iter()
+            .map(|(name, value)| HeaderField { name, value })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::field;
+    use crate::forwarding::*;
+
+    fn ip(s: &str) -> NodeName<'_> {
+        NodeName::Ip(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_hop_by_hop() {
+        let headers = [
+            field("Host", "example.com"),
+            field("connection", "Keep-Alive, X-A"),
+            field("Connection", "x-b"),
+            field("Keep-Alive", "timeout=5"),
+            field("x-a", "1"),
+            field("X-B", "2"),
+            field("TE", "trailers"),
+            field("Proxy-Authorization", "Basic Zm9vOmJhcg=="),
+            field("Accept", "*/*"),
+        ];
+        let hop_by_hop = HopByHop::new(&headers);
+        assert!(hop_by_hop.contains("transfer-encoding"));
+        assert!(hop_by_hop.contains("X-A"));
+        assert!(!hop_by_hop.contains("Accept"));
+        assert_eq!(
+            hop_by_hop
+                .names()
+                .skip(HOP_BY_HOP.len())
+                .collect::<Vec<_>>(),
+            ["Keep-Alive", "X-A", "x-b"]
+        );
+        let names: Vec<_> = end_to_end(&headers).map(|h| h.name).collect();
+        assert_eq!(names, ["Host", "Accept"]);
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let headers = [
+            field(
+                "Forwarded",
+                "for=192.0.2.43, for=\"[2001:db8:cafe::17]:4711\"",
+            ),
+            field(
+                "forwarded",
+                "for=unknown;By=\"_hidden:_port\"; proto=https;host=\"a.example\";ext=1",
+            ),
+        ];
+        let elements: Vec<_> = forwarded(&headers).map(|e| e.unwrap()).collect();
+        assert_eq!(
+            elements,
+            [
+                ForwardedElement {
+                    for_: Some(Node {
+                        name: ip("192.0.2.43"),
+                        port: None,
+                    }),
+                    ..ForwardedElement::default()
+                },
+                ForwardedElement {
+                    for_: Some(Node {
+                        name: ip("2001:db8:cafe::17"),
+                        port: Some(NodePort::Port(4711)),
+                    }),
+                    ..ForwardedElement::default()
+                },
+                ForwardedElement {
+                    by: Some(Node {
+                        name: NodeName::Obfuscated("_hidden"),
+                        port: Some(NodePort::Obfuscated("_port")),
+                    }),
+                    for_: Some(Node {
+                        name: NodeName::Unknown,
+                        port: None,
+                    }),
+                    host: Some("a.example"),
+                    proto: Some("https"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_forwarded() {
+        for value in &[
+            "for=2001:db8::17",
+            "for=[2001:db8::17]",
+            "for=\"[2001:db8::17\"",
+            "for=\"192.0.2.43:65536\"",
+            "for=\"192.0.2.43:\"",
+            "for=192.0.2.43:80",
+            "for=_",
+            "for=a.example",
+            "for=192.0.2.43;for=192.0.2.44",
+            "proto=1http",
+            "for",
+            "for=\"192.0.2.43",
+            "for=192.0.2.43 by=192.0.2.44",
+        ] {
+            assert_eq!(
+                ForwardedElement::parse(value),
+                Err(InvalidForwarded),
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            ForwardedElement::parse(";;"),
+            Ok(ForwardedElement::default())
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        let headers = [
+            field("X-Forwarded-For", "203.0.113.195, 2001:db8::1"),
+            field("x-forwarded-for", "198.51.100.178"),
+            field("X-Forwarded-Proto", "https"),
+        ];
+        let addresses: Vec<_> = x_forwarded_for(&headers).collect();
+        assert_eq!(
+            addresses,
+            ["203.0.113.195", "2001:db8::1", "198.51.100.178"]
+        );
+        assert_eq!(x_forwarded_proto(&headers).next(), Some("https"));
+    }
+
+    #[test]
+    fn test_via() {
+        let headers = [
+            field(
+                "Via",
+                "1.0 fred, 1.1 p.example.net:8080 (Apache/1.1, (nested))",
+            ),
+            field("Via", " , HTTP/2 _pseudonym"),
+        ];
+        let elements: Vec<_> = via(&headers).map(|v| v.unwrap()).collect();
+        assert_eq!(
+            elements,
+            [
+                Via::new("1.0", "fred"),
+                Via {
+                    comment: Some("Apache/1.1, (nested)"),
+                    ..Via::new("1.1", "p.example.net:8080")
+                },
+                Via {
+                    protocol: Some("HTTP"),
+                    ..Via::new("2", "_pseudonym")
+                },
+            ]
+        );
+
+        for value in &["1.1", "1.1 a (comment", "1.1 a b", "/1.1 a", "1.1 a (c) d"] {
+            let headers = [field("Via", value), field("Via", "1.1 b")];
+            let mut elements = via(&headers);
+            assert_eq!(elements.next(), Some(Err(InvalidVia)), "{}", value);
+            assert_eq!(elements.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_origin_form() {
+        let form = origin_form("http://user@example.com:8080/a/b?c#d").unwrap();
+        assert_eq!(form.scheme, "http");
+        assert_eq!(form.authority, "example.com:8080");
+        assert_eq!(form.target, "/a/b?c");
+        assert!(matches!(form.target, Cow::Borrowed(_)));
+
+        assert_eq!(origin_form("https://example.com").unwrap().target, "/");
+        assert_eq!(origin_form("https://example.com?q").unwrap().target, "/?q");
+
+        assert_eq!(origin_form("/index.html"), None);
+        assert_eq!(origin_form("example.com:443"), None);
+        assert_eq!(origin_form("http:///a"), None);
+        assert_eq!(origin_form("1http://example.com/"), None);
+    }
+
+    #[test]
+    fn test_header_list() {
+        let headers = [
+            field("Connection", "close"),
+            field("Forwarded", "for=192.0.2.1"),
+            field("X-Forwarded-For", "192.0.2.1"),
+            field("Via", "1.0 fred"),
+        ];
+        let mut list = HeaderList::end_to_end(&headers);
+        list.append_forwarded(&ForwardedElement {
+            for_: Some(Node {
+                name: ip("2001:db8::17"),
+                port: Some(NodePort::Port(4711)),
+            }),
+            by: Some(Node {
+                name: NodeName::Obfuscated("_proxy"),
+                port: None,
+            }),
+            host: Some("a b"),
+            proto: Some("https"),
+        })
+        .unwrap();
+        list.append_x_forwarded_for("2001:db8::17".parse().unwrap());
+        list.append_x_forwarded_proto("https").unwrap();
+        list.append_via(&Via {
+            comment: Some("thhp"),
+            ..Via::new("1.1", "proxy")
+        })
+        .unwrap();
+        list.push("X-A", "1");
+        list.set("x-a", "2");
+
+        let fields: Vec<_> = list
+            .header_fields()
+            .iter()
+            .map(|h| (h.name, h.value))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (
+                    "Forwarded",
+                    "for=192.0.2.1, for=\"[2001:db8::17]:4711\";by=_proxy;host=\"a b\";proto=https"
+                ),
+                ("X-Forwarded-For", "192.0.2.1, 2001:db8::17"),
+                ("Via", "1.0 fred, 1.1 proxy (thhp)"),
+                ("X-Forwarded-Proto", "https"),
+                ("x-a", "2"),
+            ]
+        );
+        assert_eq!(list.get("X-A"), Some("2"));
+
+        // Appended elements are parsed back.
+        let headers = list.header_fields();
+        assert_eq!(forwarded(&headers).filter(|e| e.is_ok()).count(), 2);
+        assert_eq!(via(&headers).filter(|v| v.is_ok()).count(), 2);
+    }
+
+    #[test]
+    fn test_append_invalid() {
+        let mut list = HeaderList::new();
+        assert_eq!(
+            list.append_forwarded(&ForwardedElement::default()),
+            Err(InvalidForwarded)
+        );
+        assert_eq!(
+            list.append_forwarded(&ForwardedElement {
+                host: Some("a\r\nb"),
+                ..ForwardedElement::default()
+            }),
+            Err(InvalidForwarded)
+        );
+        assert_eq!(
+            list.append_x_forwarded_proto("ht tp"),
+            Err(InvalidFieldValue)
+        );
+        assert_eq!(list.append_via(&Via::new("1.1", "a,b")), Err(InvalidVia));
+        assert_eq!(
+            list.append_via(&Via {
+                comment: Some("a) (b"),
+                ..Via::new("1.1", "a")
+            }),
+            Err(InvalidVia)
+        );
+        assert_eq!(list.header_fields().len(), 0);
+    }
+}
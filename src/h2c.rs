@@ -0,0 +1,365 @@
+//! Upgrading to HTTP/2 over cleartext TCP (RFC 7540 section 3.2).
+//!
+//! A client asks for `h2c` with `Upgrade: h2c`, `Connection: Upgrade,
+//! HTTP2-Settings` and a single `HTTP2-Settings` header field, which is the
+//! base64url encoded payload of a SETTINGS frame. `UpgradeRequest` validates
+//! the request and decodes the settings without allocation.
+//!
+//! After the `101 Switching Protocols` response, the server sends the HTTP/2
+//! connection preface and the response to the request on stream 1. A
+//! request body must be read in full before the response.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::h2c::{Setting, UpgradeRequest};
+//!
+//! let buf = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+//!             Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! let upgrade = UpgradeRequest::from_request(&req).unwrap().unwrap();
+//! let settings: Vec<_> = upgrade.settings().collect();
+//! assert_eq!(
+//!     settings,
+//!     [Setting::MaxConcurrentStreams(100), Setting::InitialWindowSize(65535)]
+//! );
+//!
+//! let mut out = [0u8; 128];
+//! let len = upgrade.encode_response(&mut out).unwrap();
+//! assert_eq!(
+//!     &out[..len],
+//!     &b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n"[..]
+//! );
+//! ```
+use crate::base64;
+use crate::errors::*;
+use crate::fields::{find_all, has_token, trim_ows};
+use crate::{HeaderField, Request, Response};
+
+// A setting is 6 bytes, which are 8 characters in base64url.
+const ENCODED_SETTING_LEN: usize = 8;
+
+/// A setting in a SETTINGS frame.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Setting {
+    /// `SETTINGS_HEADER_TABLE_SIZE` (0x1).
+    HeaderTableSize(u32),
+    /// `SETTINGS_ENABLE_PUSH` (0x2).
+    EnablePush(bool),
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS` (0x3).
+    MaxConcurrentStreams(u32),
+    /// `SETTINGS_INITIAL_WINDOW_SIZE` (0x4), at most 2^31-1.
+    InitialWindowSize(u32),
+    /// `SETTINGS_MAX_FRAME_SIZE` (0x5), between 2^14 and 2^24-1.
+    MaxFrameSize(u32),
+    /// `SETTINGS_MAX_HEADER_LIST_SIZE` (0x6).
+    MaxHeaderListSize(u32),
+    /// A setting with an unknown identifier, which must be ignored.
+    Unknown(u16, u32),
+}
+
+impl Setting {
+    /// Makes a setting from its identifier and value.
+    ///
+    /// Returns `InvalidSettings` if the value is out of range.
+    pub fn new(id: u16, value: u32) -> Result<Setting> {
+        Ok(match id {
+            0x1 => Setting::HeaderTableSize(value),
+            0x2 => match value {
+                0 => Setting::EnablePush(false),
+                1 => Setting::EnablePush(true),
+                _ => return Err(InvalidSettings),
+            },
+            0x3 => Setting::MaxConcurrentStreams(value),
+            0x4 if value <= 0x7FFF_FFFF => Setting::InitialWindowSize(value),
+            0x5 if (0x4000..=0xFF_FFFF).contains(&value) => Setting::MaxFrameSize(value),
+            0x4 | 0x5 => return Err(InvalidSettings),
+            0x6 => Setting::MaxHeaderListSize(value),
+            _ => Setting::Unknown(id, value),
+        })
+    }
+
+    /// Returns the identifier.
+    pub fn id(&self) -> u16 {
+        match *self {
+            Setting::HeaderTableSize(_) => 0x1,
+            Setting::EnablePush(_) => 0x2,
+            Setting::MaxConcurrentStreams(_) => 0x3,
+            Setting::InitialWindowSize(_) => 0x4,
+            Setting::MaxFrameSize(_) => 0x5,
+            Setting::MaxHeaderListSize(_) => 0x6,
+            Setting::Unknown(id, _) => id,
+        }
+    }
+
+    /// Returns the value.
+    pub fn value(&self) -> u32 {
+        match *self {
+            Setting::HeaderTableSize(v)
+            | Setting::MaxConcurrentStreams(v)
+            | Setting::InitialWindowSize(v)
+            | Setting::MaxFrameSize(v)
+            | Setting::MaxHeaderListSize(v)
+            | Setting::Unknown(_, v) => v,
+            Setting::EnablePush(v) => v as u32,
+        }
+    }
+
+    // Decodes a setting from 8 characters of base64url.
+    fn decode(encoded: &[u8]) -> Result<Setting> {
+        let mut buf = [0; 6];
+        match base64::decode_url(encoded, &mut buf) {
+            Some(6) => (),
+            _ => return Err(InvalidSettings),
+        }
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        let value = u32::from_be_bytes([buf[2], buf[3], buf[4], buf[5]]);
+        Setting::new(id, value)
+    }
+}
+
+/// An iterator over the settings in a value of `HTTP2-Settings`.
+#[derive(Clone, Debug)]
+pub struct Settings<'buffer> {
+    rest: &'buffer [u8],
+}
+
+impl<'buffer> Settings<'buffer> {
+    /// Validates a value of `HTTP2-Settings`.
+    ///
+    /// The value is the base64url encoded payload of a SETTINGS frame
+    /// without padding, and every setting must have a valid value.
+    pub fn decode(value: &'buffer str) -> Result<Settings<'buffer>> {
+        let value = trim_ows(value).as_bytes();
+        if value.len() % ENCODED_SETTING_LEN != 0 {
+            return Err(InvalidSettings);
+        }
+        for encoded in value.chunks(ENCODED_SETTING_LEN) {
+            Setting::decode(encoded)?;
+        }
+        Ok(Settings { rest: value })
+    }
+}
+
+impl<'buffer> Iterator for Settings<'buffer> {
+    type Item = Setting;
+
+    fn next(&mut self) -> Option<Setting> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (encoded, rest) = self.rest.split_at(ENCODED_SETTING_LEN);
+        self.rest = rest;
+        // The settings are already validated.
+        Setting::decode(encoded).ok()
+    }
+}
+
+/// A validated request to upgrade to `h2c`.
+#[derive(Clone, Debug)]
+pub struct UpgradeRequest<'buffer> {
+    settings: Settings<'buffer>,
+}
+
+impl<'buffer> UpgradeRequest<'buffer> {
+    /// Validates a request to upgrade to `h2c`.
+    ///
+    /// Returns `None` if the request doesn't offer `h2c` in `Upgrade`, and
+    /// `InvalidSettings` if it does without a valid `HTTP2-Settings`, which
+    /// must also be listed in `Connection`. A server may then ignore the
+    /// upgrade and respond in HTTP/1.1.
+    pub fn from_request(req: &Request<'_, 'buffer>) -> Result<Option<UpgradeRequest<'buffer>>> {
+        if !req
+            .upgrade_protocols()
+            .any(|p| p.name.eq_ignore_ascii_case("h2c") && p.version.is_none())
+        {
+            return Ok(None);
+        }
+        if !has_token(req.headers, "Connection", "HTTP2-Settings") {
+            return Err(InvalidSettings);
+        }
+        let mut values = find_all(req.headers, "HTTP2-Settings");
+        match (values.next(), values.next()) {
+            (Some(value), None) => Ok(Some(UpgradeRequest {
+                settings: Settings::decode(value)?,
+            })),
+            _ => Err(InvalidSettings),
+        }
+    }
+
+    /// Returns the settings of the client, which are applied as if sent in
+    /// the first SETTINGS frame of the HTTP/2 connection.
+    pub fn settings(&self) -> Settings<'buffer> {
+        self.settings.clone()
+    }
+
+    /// Calls `f` with the `101 Switching Protocols` response.
+    pub fn with_response<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&Response) -> T,
+    {
+        let headers = [
+            HeaderField {
+                name: "Connection",
+                value: "Upgrade",
+            },
+            HeaderField {
+                name: "Upgrade",
+                value: "h2c",
+            },
+        ];
+        f(&Response {
+            minor_version: 1,
+            status: 101,
+            reason: "Switching Protocols",
+            headers: &headers,
+        })
+    }
+
+    /// Writes the `101 Switching Protocols` response into `buf`, returns the
+    /// number of written bytes.
+    pub fn encode_response(&self, buf: &mut [u8]) -> Result<usize> {
+        self.with_response(|res| res.encode(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::field;
+    use crate::h2c::*;
+    use crate::upgrade::{Protocol, Upgrade};
+
+    fn request<'a>(headers: &'a [HeaderField<'a>]) -> Request<'a, 'a> {
+        Request {
+            method: "GET",
+            target: "/",
+            minor_version: 1,
+            headers,
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings: Vec<_> =
+            Settings::decode("AAEAABAAAAIAAAAAAAMAAABkAAR_____AAUAAEAAAAYAACAAAP8AAAAH")
+                .unwrap()
+                .collect();
+        assert_eq!(
+            settings,
+            [
+                Setting::HeaderTableSize(4096),
+                Setting::EnablePush(false),
+                Setting::MaxConcurrentStreams(100),
+                Setting::InitialWindowSize(0x7FFF_FFFF),
+                Setting::MaxFrameSize(16384),
+                Setting::MaxHeaderListSize(8192),
+                Setting::Unknown(0xFF, 7),
+            ]
+        );
+        for s in &settings {
+            assert_eq!(Setting::new(s.id(), s.value()), Ok(*s));
+        }
+        assert_eq!(Settings::decode("").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        for value in &[
+            "AAIAAAAC",           // ENABLE_PUSH of 2
+            "AASAAAAA",           // INITIAL_WINDOW_SIZE of 2^31
+            "AAUAAD__",           // MAX_FRAME_SIZE of 2^14-1
+            "AAUBAAAA",           // MAX_FRAME_SIZE of 2^24
+            "AAMAAABkAAQ",        // truncated
+            "AAMAAABk====",       // padded
+            "AAMAAAB+",           // base64, not base64url
+            "AAMAAABkAAQAAP__AA", // trailing bytes
+        ] {
+            assert_eq!(
+                Settings::decode(value).err(),
+                Some(InvalidSettings),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_upgrade_request() {
+        let headers = [
+            field("Host", "example.com"),
+            field("Connection", "Upgrade, HTTP2-Settings"),
+            field("Upgrade", "websocket, h2c"),
+            field("HTTP2-Settings", "AAMAAABk"),
+        ];
+        let upgrade = UpgradeRequest::from_request(&request(&headers))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            upgrade.settings().collect::<Vec<_>>(),
+            [Setting::MaxConcurrentStreams(100)]
+        );
+
+        // Not an upgrade to h2c.
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&headers[..1])).map(|u| u.is_some()),
+            Ok(false)
+        );
+        let other = [headers[1], field("Upgrade", "h2c/1"), headers[3]];
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&other)).map(|u| u.is_some()),
+            Ok(false)
+        );
+
+        // `HTTP2-Settings` must be in `Connection`.
+        let missing = [field("Connection", "Upgrade"), headers[2], headers[3]];
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&missing)).err(),
+            Some(InvalidSettings)
+        );
+        // Exactly one `HTTP2-Settings` is required.
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&headers[..3])).err(),
+            Some(InvalidSettings)
+        );
+        let twice = [headers[1], headers[2], headers[3], headers[3]];
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&twice)).err(),
+            Some(InvalidSettings)
+        );
+        let invalid = [headers[1], headers[2], field("HTTP2-Settings", "AAIAAAAC")];
+        assert_eq!(
+            UpgradeRequest::from_request(&request(&invalid)).err(),
+            Some(InvalidSettings)
+        );
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let headers = [
+            field("Connection", "Upgrade, HTTP2-Settings"),
+            field("Upgrade", "h2c"),
+            field("HTTP2-Settings", ""),
+        ];
+        let upgrade = UpgradeRequest::from_request(&request(&headers))
+            .unwrap()
+            .unwrap();
+        assert_eq!(upgrade.settings().count(), 0);
+        upgrade.with_response(|res| {
+            assert_eq!(res.status, 101);
+            assert_eq!(
+                res.upgrade("GET"),
+                Some(Upgrade::Protocol(Protocol {
+                    name: "h2c",
+                    version: None
+                }))
+            );
+        });
+        let mut buf = [0; 16];
+        assert_eq!(upgrade.encode_response(&mut buf), Err(BufferTooSmall));
+    }
+}
@@ -0,0 +1,280 @@
+//! Conversions from and into the `http` crate types.
+//!
+//! Parsed messages are converted into `http::Request<()>` and
+//! `http::Response<()>`. `http::request::Parts` and `http::response::Parts`
+//! are viewed as a `Request` and a `Response` to write them.
+//!
+//! ## Example
+//!
+//! ```
+//! let buf = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! let req = req.to_http().unwrap();
+//! assert_eq!(req.method(), http::Method::GET);
+//! assert_eq!(req.headers()["host"], "example.com");
+//!
+//! let (parts, _) = req.into_parts();
+//! let mut headers = Vec::with_capacity(parts.headers.len());
+//! let req = thhp::http_compat::request_from_parts(&parts, &mut headers).unwrap();
+//! let mut out = Vec::new();
+//! req.encode_io(&mut out).unwrap();
+//! assert_eq!(out, &b"GET /index.html HTTP/1.1\r\nhost: example.com\r\n\r\n"[..]);
+//! ```
+use http;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::errors::*;
+use crate::{HeaderField, HeaderFieldCollection, Request, Response};
+
+fn to_version(minor_version: u8) -> Result<http::Version> {
+    match minor_version {
+        0 => Ok(http::Version::HTTP_10),
+        1 => Ok(http::Version::HTTP_11),
+        _ => Err(InvalidVersion),
+    }
+}
+
+fn from_version(version: http::Version) -> Result<u8> {
+    match version {
+        http::Version::HTTP_10 => Ok(0),
+        http::Version::HTTP_11 => Ok(1),
+        _ => Err(InvalidVersion),
+    }
+}
+
+fn to_header_map(headers: &[HeaderField]) -> Result<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for header in headers {
+        let name = HeaderName::from_bytes(header.name.as_bytes()).or(Err(InvalidFieldName))?;
+        let value = HeaderValue::from_str(header.value).or(Err(InvalidFieldValue))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+fn push_header_map<'a, Headers>(map: &'a HeaderMap, headers: &mut Headers) -> Result<()>
+where
+    Headers: HeaderFieldCollection<'a>,
+{
+    for (name, value) in map {
+        headers.push(HeaderField {
+            name: name.as_str(),
+            value: value.to_str().or(Err(InvalidFieldValue))?,
+        })?;
+    }
+    Ok(())
+}
+
+impl<'headers, 'buffer: 'headers> Request<'headers, 'buffer> {
+    /// Converts the request into an `http::Request`.
+    ///
+    /// Only HTTP/1.0 and HTTP/1.1 are supported.
+    pub fn to_http(&self) -> Result<http::Request<()>> {
+        let mut req = http::Request::new(());
+        *req.method_mut() =
+            http::Method::from_bytes(self.method.as_bytes()).or(Err(InvalidMethod))?;
+        *req.uri_mut() = self.target.parse().or(Err(InvalidPath))?;
+        *req.version_mut() = to_version(self.minor_version)?;
+        *req.headers_mut() = to_header_map(self.headers)?;
+        Ok(req)
+    }
+}
+
+impl<'headers, 'buffer: 'headers> Response<'headers, 'buffer> {
+    /// Converts the response into an `http::Response`.
+    ///
+    /// The reason phrase is dropped. Only HTTP/1.0 and HTTP/1.1 are
+    /// supported.
+    pub fn to_http(&self) -> Result<http::Response<()>> {
+        let mut res = http::Response::new(());
+        *res.status_mut() = http::StatusCode::from_u16(self.status).or(Err(InvalidStatusCode))?;
+        *res.version_mut() = to_version(self.minor_version)?;
+        *res.headers_mut() = to_header_map(self.headers)?;
+        Ok(res)
+    }
+}
+
+/// Returns `http::request::Parts` as a `Request`, pushing header fields into
+/// `headers`.
+///
+/// The request target is the authority for `CONNECT` and the path and query
+/// otherwise, so the absolute-form is not produced. Header field names are
+/// lowercase as they are in `HeaderMap`.
+pub fn request_from_parts<'headers, 'buffer, Headers>(
+    parts: &'buffer http::request::Parts,
+    headers: &'headers mut Headers,
+) -> Result<Request<'headers, 'buffer>>
+where
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    let target = if parts.method == http::Method::CONNECT {
+        parts.uri.authority().map(|a| a.as_str())
+    } else {
+        parts.uri.path_and_query().map(|p| p.as_str())
+    };
+    let minor_version = from_version(parts.version)?;
+    push_header_map(&parts.headers, headers)?;
+    Ok(Request {
+        method: parts.method.as_str(),
+        target: target.unwrap_or("/"),
+        minor_version,
+        headers,
+    })
+}
+
+/// Returns `http::response::Parts` as a `Response`, pushing header fields
+/// into `headers`.
+///
+/// The reason phrase is the canonical one of the status code, or empty if
+/// there is none.
+pub fn response_from_parts<'headers, 'buffer, Headers>(
+    parts: &'buffer http::response::Parts,
+    headers: &'headers mut Headers,
+) -> Result<Response<'headers, 'buffer>>
+where
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    let minor_version = from_version(parts.version)?;
+    push_header_map(&parts.headers, headers)?;
+    Ok(Response {
+        minor_version,
+        status: parts.status.as_u16(),
+        reason: parts.status.canonical_reason().unwrap_or(""),
+        headers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use http;
+
+    use crate::http_compat::*;
+
+    #[test]
+    fn test_request_to_http() {
+        let buf = b"POST /a?b=c HTTP/1.0\r\nHost: example.com\r\nAccept: a\r\naccept: b\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(8);
+        let (req, _) = Request::parse(buf, &mut headers).unwrap().unwrap();
+
+        let req = req.to_http().unwrap();
+        assert_eq!(req.method(), http::Method::POST);
+        assert_eq!(req.uri().path(), "/a");
+        assert_eq!(req.uri().query(), Some("b=c"));
+        assert_eq!(req.version(), http::Version::HTTP_10);
+        assert_eq!(req.headers()["host"], "example.com");
+        let accept: Vec<_> = req.headers().get_all("accept").iter().collect();
+        assert_eq!(accept, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_request_to_http_errors() {
+        let req = Request {
+            method: "GET",
+            target: "/",
+            minor_version: 2,
+            headers: &[],
+        };
+        assert_eq!(req.to_http().err(), Some(InvalidVersion));
+
+        let req = Request {
+            target: "/a b",
+            minor_version: 1,
+            ..req
+        };
+        assert_eq!(req.to_http().err(), Some(InvalidPath));
+
+        let headers = [HeaderField {
+            name: "X",
+            value: "\u{7f}",
+        }];
+        let req = Request {
+            target: "/",
+            headers: &headers,
+            ..req
+        };
+        assert_eq!(req.to_http().err(), Some(InvalidFieldValue));
+    }
+
+    #[test]
+    fn test_response_to_http() {
+        let buf = b"HTTP/1.1 418 I'm a teapot\r\nContent-Length: 0\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(8);
+        let (res, _) = Response::parse(buf, &mut headers).unwrap().unwrap();
+
+        let res = res.to_http().unwrap();
+        assert_eq!(res.status(), http::StatusCode::IM_A_TEAPOT);
+        assert_eq!(res.version(), http::Version::HTTP_11);
+        assert_eq!(res.headers()["content-length"], "0");
+    }
+
+    #[test]
+    fn test_request_from_parts() {
+        let (parts, _) = http::Request::connect("example.com:443")
+            .header("Host", "example.com:443")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut headers = Vec::with_capacity(4);
+        let req = request_from_parts(&parts, &mut headers).unwrap();
+        assert_eq!(req.method, "CONNECT");
+        assert_eq!(req.target, "example.com:443");
+
+        let mut out = Vec::new();
+        req.encode_io(&mut out).unwrap();
+        assert_eq!(
+            out,
+            &b"CONNECT example.com:443 HTTP/1.1\r\nhost: example.com:443\r\n\r\n"[..]
+        );
+
+        let (parts, _) = http::Request::get("http://example.com/a?b")
+            .version(http::Version::HTTP_2)
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut headers = Vec::with_capacity(4);
+        assert_eq!(
+            request_from_parts(&parts, &mut headers).err(),
+            Some(InvalidVersion)
+        );
+    }
+
+    #[test]
+    fn test_response_from_parts() {
+        let (parts, _) = http::Response::builder()
+            .status(404)
+            .header("Content-Length", "0")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let mut headers = Vec::with_capacity(4);
+        let res = response_from_parts(&parts, &mut headers).unwrap();
+
+        let mut buf = [0u8; 64];
+        let len = res.encode(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n"[..]
+        );
+
+        let mut headers = Vec::with_capacity(0);
+        assert_eq!(
+            response_from_parts(&parts, &mut headers).err(),
+            Some(OutOfCapacity)
+        );
+    }
+
+    #[test]
+    fn test_non_utf8_header_value() {
+        let mut parts = http::Response::new(()).into_parts().0;
+        parts
+            .headers
+            .insert("x", http::HeaderValue::from_bytes(b"\xff").unwrap());
+        let mut headers = Vec::with_capacity(4);
+        assert_eq!(
+            response_from_parts(&parts, &mut headers).err(),
+            Some(InvalidFieldValue)
+        );
+    }
+}
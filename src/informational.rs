@@ -0,0 +1,232 @@
+//! Informational (1xx) responses.
+//!
+//! A server may send any number of informational responses, such as
+//! `100 Continue` or `103 Early Hints`, before the final response.
+//! `Response::parse_final` parses them all, passing each to a callback, and
+//! returns the final response. It records how far it got, so parsing a
+//! longer buffer resumes after the informational responses already seen.
+//!
+//! ## Example
+//!
+//! ```
+//! let buf = b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+//!             HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+//! let mut links = Vec::new();
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let mut pos = 0;
+//! let (res, len) = thhp::Response::parse_final(buf, &mut pos, &mut headers, |info| {
+//!     if info.status == 103 {
+//!         links.extend(info.headers().filter(|h| h.name == "Link").map(|h| h.value));
+//!     }
+//! })
+//! .unwrap()
+//! .unwrap();
+//! assert_eq!(res.status, 200);
+//! assert_eq!(len, buf.len());
+//! assert_eq!(links, ["</style.css>; rel=preload"]);
+//! ```
+use crate::errors::*;
+use crate::{
+    Complete, Discard, HeaderField, HeaderFieldCollection, HttpPartParser, Incomplete, Response,
+    Status,
+};
+
+/// An informational (1xx) response.
+///
+/// Header fields are parsed again from the buffer when iterated, so no
+/// storage is needed for them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InformationalResponse<'buffer> {
+    /// The http minor version.
+    pub minor_version: u8,
+    /// The status code.
+    pub status: u16,
+    /// The reason phrase.
+    pub reason: &'buffer str,
+    fields: &'buffer [u8],
+}
+
+impl<'buffer> InformationalResponse<'buffer> {
+    /// Returns an iterator over the header fields.
+    pub fn headers(&self) -> InformationalHeaders<'buffer> {
+        InformationalHeaders { rest: self.fields }
+    }
+}
+
+/// An iterator over header fields of an informational response.
+#[derive(Clone, Debug)]
+pub struct InformationalHeaders<'buffer> {
+    rest: &'buffer [u8],
+}
+
+impl<'buffer> Iterator for InformationalHeaders<'buffer> {
+    type Item = HeaderField<'buffer>;
+
+    fn next(&mut self) -> Option<HeaderField<'buffer>> {
+        let mut parser = HttpPartParser::new(self.rest);
+        // The header fields are already validated.
+        let field = match parser.skip_eol() {
+            Ok(Complete(false)) => match parser.parse_header_field() {
+                Ok(Complete(field)) => Some(field),
+                _ => None,
+            },
+            _ => None,
+        };
+        self.rest = match field {
+            Some(_) => &self.rest[self.rest.len() - parser.len()..],
+            None => &[],
+        };
+        field
+    }
+}
+
+// Returns the header section of a parsed response head.
+fn header_section(head: &[u8]) -> &[u8] {
+    let mut parser = HttpPartParser::new(head);
+    // The head is already validated, so each part is complete.
+    let _ = parser.skip_empty_lines();
+    let _ = parser.parse_response_http_version();
+    let _ = parser.parse_response_status_code();
+    let _ = parser.parse_response_reason_phrase();
+    &head[head.len() - parser.len()..]
+}
+
+impl<'headers, 'buffer: 'headers> Response<'headers, 'buffer> {
+    /// Parse the buffer as informational responses followed by a final
+    /// response.
+    ///
+    /// Parsing starts at `*pos`, which is advanced past each informational
+    /// response after `informational` is called for it. The final response
+    /// is returned with the length of all responses from the start of
+    /// `buf`. A `101 Switching Protocols` response is final. If `Incomplete`
+    /// is returned, parse the longer buffer again with the same `pos` to
+    /// resume without seeing the same responses twice.
+    ///
+    /// Returns `BufferTooSmall` if `*pos` exceeds the buffer.
+    pub fn parse_final<Headers, F>(
+        buf: &'buffer [u8],
+        pos: &mut usize,
+        headers: &'headers mut Headers,
+        mut informational: F,
+    ) -> Result<Status<(Self, usize)>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+        F: FnMut(&InformationalResponse<'buffer>),
+    {
+        loop {
+            let rest = buf.get(*pos..).ok_or(BufferTooSmall)?;
+            let mut discard = Discard::new();
+            let (res, len) = match Response::parse(rest, &mut discard)? {
+                Complete(v) => v,
+                Incomplete => return Ok(Incomplete),
+            };
+            if res.status / 100 != 1 || res.status == 101 {
+                break;
+            }
+            informational(&InformationalResponse {
+                minor_version: res.minor_version,
+                status: res.status,
+                reason: res.reason,
+                fields: header_section(&rest[..len]),
+            });
+            *pos += len;
+        }
+
+        match Response::parse(&buf[*pos..], headers)? {
+            Complete((res, len)) => Ok(Complete((res, *pos + len))),
+            Incomplete => unreachable!("a parsed head is incomplete"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::informational::*;
+
+    #[test]
+    fn test_parse_final() {
+        let buf = b"HTTP/1.1 100 Continue\r\n\r\n\
+                    HTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\nLink: </b.js>\r\n\r\n\
+                    HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody";
+        let mut statuses = Vec::new();
+        let mut links = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let mut pos = 0;
+        let (res, len) = Response::parse_final(buf, &mut pos, &mut headers, |info| {
+            statuses.push(info.status);
+            links.extend(info.headers().map(|h| h.value));
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(statuses, [100, 103]);
+        assert_eq!(links, ["</a.css>", "</b.js>"]);
+        assert_eq!(res.status, 200);
+        assert_eq!(res.headers.len(), 1);
+        assert_eq!(&buf[len..], b"body");
+        assert_eq!(&buf[pos..pos + 15], b"HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn test_parse_final_without_informational() {
+        let buf = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let (res, len) = Response::parse_final(buf, &mut 0, &mut headers, |_| panic!())
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.status, 101);
+        assert_eq!(len, buf.len());
+    }
+
+    #[test]
+    fn test_parse_final_incomplete() {
+        let buf = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n";
+        let mut count = 0;
+        let mut pos = 0;
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        assert_eq!(
+            Response::parse_final(buf, &mut pos, &mut headers, |_| count += 1),
+            Ok(Incomplete)
+        );
+        assert_eq!(count, 1);
+        assert_eq!(pos, 25);
+
+        // Resuming does not see the 100 response again.
+        let buf = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n\r\n";
+        let (res, len) = Response::parse_final(buf, &mut pos, &mut headers, |_| count += 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(res.status, 200);
+        assert_eq!(len, buf.len());
+
+        assert_eq!(
+            Response::parse_final(&buf[..10], &mut pos, &mut headers, |_| ()),
+            Err(BufferTooSmall)
+        );
+
+        let buf = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 O\x01\r\n\r\n";
+        assert_eq!(
+            Response::parse_final(buf, &mut 0, &mut headers, |_| ()),
+            Err(InvalidReasonPhrase)
+        );
+    }
+
+    #[test]
+    fn test_headers_of_informational() {
+        let buf = b"\r\nHTTP/1.1 103 Early Hints\r\nA: 1\r\nB:2\r\n\r\n";
+        let mut discard = Discard::new();
+        let (res, len) = Response::parse(buf, &mut discard).unwrap().unwrap();
+        let info = InformationalResponse {
+            minor_version: res.minor_version,
+            status: res.status,
+            reason: res.reason,
+            fields: header_section(&buf[..len]),
+        };
+        let fields: Vec<_> = info.headers().map(|h| (h.name, h.value)).collect();
+        assert_eq!(fields, [("A", "1"), ("B", "2")]);
+        assert_eq!(info.headers().count(), 2);
+    }
+}
@@ -0,0 +1,574 @@
+//! Reading messages from `std::io::BufRead`.
+//!
+//! `read_request` and `read_response` read a message head into a buffer and
+//! parse it, leaving the reader at the start of the body. `BodyReader` then
+//! reads the body framed by `Content-Length`, the chunked transfer coding or
+//! the end of the connection.
+//!
+//! ## Example
+//!
+//! ```
+//! use std::io::Read;
+//! use thhp::io::{read_request, BodyReader};
+//!
+//! let mut reader = &b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"[..];
+//! let mut buf = Vec::new();
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+//!
+//! let mut body = String::new();
+//! BodyReader::for_request(&mut reader, &req).unwrap().read_to_string(&mut body).unwrap();
+//! assert_eq!(body, "hello");
+//! ```
+use std::io::{self, BufRead, Read, Write};
+use std::result;
+
+use crate::body::{parse_trailers, BodyDecoder, BodyLength, Chunk, Limits};
+use crate::errors::*;
+use crate::fields::{find, trim_ows};
+use crate::{Complete, HeaderField, HeaderFieldCollection, Incomplete, Request, Response};
+
+// Finds the empty line ending a head, resuming after the bytes already
+// scanned. Empty lines before the start line are skipped as the parser does.
+#[derive(Debug, Default)]
+pub(crate) struct HeadScanner {
+    pos: usize,
+    line_start: usize,
+    started: bool,
+}
+
+impl HeadScanner {
+    // Returns the length of the head if `buf` contains its end. `buf` must
+    // start with the bytes given to the previous scans.
+    pub(crate) fn scan(&mut self, buf: &[u8]) -> Option<usize> {
+        while let Some(i) = buf[self.pos..].iter().position(|&c| c == b'\n') {
+            let line = &buf[self.line_start..self.pos + i];
+            self.pos += i + 1;
+            self.line_start = self.pos;
+            if line.is_empty() || line == b"\r" {
+                if self.started {
+                    return Some(self.pos);
+                }
+            } else {
+                self.started = true;
+            }
+        }
+        self.pos = buf.len();
+        None
+    }
+}
+
+// Reads a head into `buf`, consuming exactly the head from `reader`. Only the
+// bytes of each new fill are scanned, so the head is parsed once after it is
+// read.
+fn read_head<R: BufRead>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max_head_len: usize,
+) -> result::Result<(), StreamError> {
+    buf.clear();
+    let mut scanner = HeadScanner::default();
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Err(StreamError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before a message head",
+            )));
+        }
+
+        let buffered = buf.len();
+        let len = available.len().min(max_head_len - buffered);
+        buf.extend_from_slice(&available[..len]);
+        if let Some(head_len) = scanner.scan(buf) {
+            buf.truncate(head_len);
+            reader.consume(head_len - buffered);
+            return Ok(());
+        }
+        reader.consume(len);
+        if buf.len() >= max_head_len {
+            return Err(StreamError::HeadTooLarge);
+        }
+    }
+}
+
+/// Reads a request head from `reader` into `buf` and parses it.
+///
+/// The reader is left at the start of the body. The head may be as long as
+/// the default `Limits`.
+pub fn read_request<'headers, 'buffer, R, Headers>(
+    reader: &mut R,
+    buf: &'buffer mut Vec<u8>,
+    headers: &'headers mut Headers,
+) -> result::Result<Request<'headers, 'buffer>, StreamError>
+where
+    R: BufRead,
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    read_request_with_limit(reader, buf, headers, Limits::default().max_head_len)
+}
+
+/// Reads a request head of at most `max_head_len` bytes from `reader` into
+/// `buf` and parses it.
+pub fn read_request_with_limit<'headers, 'buffer, R, Headers>(
+    reader: &mut R,
+    buf: &'buffer mut Vec<u8>,
+    headers: &'headers mut Headers,
+    max_head_len: usize,
+) -> result::Result<Request<'headers, 'buffer>, StreamError>
+where
+    R: BufRead,
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    read_head(reader, buf, max_head_len)?;
+    match Request::parse(buf, headers).map_err(head_error)? {
+        Complete((req, _)) => Ok(req),
+        // The head ends with an empty line, so a line break is malformed.
+        Incomplete => Err(StreamError::Parse(InvalidNewLine)),
+    }
+}
+
+/// Reads a response head from `reader` into `buf` and parses it.
+///
+/// The reader is left at the start of the body. The head may be as long as
+/// the default `Limits`.
+pub fn read_response<'headers, 'buffer, R, Headers>(
+    reader: &mut R,
+    buf: &'buffer mut Vec<u8>,
+    headers: &'headers mut Headers,
+) -> result::Result<Response<'headers, 'buffer>, StreamError>
+where
+    R: BufRead,
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    read_response_with_limit(reader, buf, headers, Limits::default().max_head_len)
+}
+
+/// Reads a response head of at most `max_head_len` bytes from `reader` into
+/// `buf` and parses it.
+pub fn read_response_with_limit<'headers, 'buffer, R, Headers>(
+    reader: &mut R,
+    buf: &'buffer mut Vec<u8>,
+    headers: &'headers mut Headers,
+    max_head_len: usize,
+) -> result::Result<Response<'headers, 'buffer>, StreamError>
+where
+    R: BufRead,
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    read_head(reader, buf, max_head_len)?;
+    match Response::parse(buf, headers).map_err(head_error)? {
+        Complete((res, _)) => Ok(res),
+        // The head ends with an empty line, so a line break is malformed.
+        Incomplete => Err(StreamError::Parse(InvalidNewLine)),
+    }
+}
+
+/// Returns `true` if the request has `Expect: 100-continue`.
+///
+/// The expectation is ignored for HTTP/1.0 requests.
+pub fn expects_continue(req: &Request) -> bool {
+    req.minor_version >= 1
+        && find(req.headers, "Expect")
+            .is_some_and(|v| trim_ows(v).eq_ignore_ascii_case("100-continue"))
+}
+
+// Returns the length of the first line in `buf`, or the whole length.
+fn line_len(buf: &[u8]) -> usize {
+    buf.iter()
+        .position(|&c| c == b'\n')
+        .map_or(buf.len(), |i| i + 1)
+}
+
+/// A reader of a message body.
+///
+/// It reads the body from the underlying reader and leaves bytes after the
+/// body there, so the next message can be read from it.
+///
+/// For a request with `Expect: 100-continue`, the client waits for an
+/// interim response before sending the body. Call `send_continue` before
+/// reading the body, or respond with a final response without reading it.
+#[derive(Debug)]
+pub struct BodyReader<R> {
+    reader: R,
+    decoder: BodyDecoder,
+    // Length of body data at the start of the reader's buffer.
+    pending: usize,
+    // Framing split across the reader's buffers.
+    framing: Vec<u8>,
+    trailers: Vec<u8>,
+    max_head_len: usize,
+    expects_continue: bool,
+}
+
+impl<R: BufRead> BodyReader<R> {
+    /// Creates a reader of a body delimited by `length`.
+    pub fn new(reader: R, length: BodyLength) -> BodyReader<R> {
+        BodyReader {
+            reader,
+            decoder: BodyDecoder::new(length),
+            pending: 0,
+            framing: Vec::new(),
+            trailers: Vec::new(),
+            max_head_len: Limits::default().max_head_len,
+            expects_continue: false,
+        }
+    }
+
+    /// Creates a reader of the body of `req`.
+    pub fn for_request(reader: R, req: &Request) -> Result<BodyReader<R>> {
+        let length = BodyLength::of_request(req.headers)?;
+        let mut body = BodyReader::new(reader, length);
+        body.expects_continue = expects_continue(req) && length != BodyLength::Length(0);
+        Ok(body)
+    }
+
+    /// Creates a reader of the body of `res` to a request with `method`.
+    pub fn for_response(reader: R, method: &str, res: &Response) -> Result<BodyReader<R>> {
+        let length = BodyLength::of_response(method, res.status, res.headers)?;
+        Ok(BodyReader::new(reader, length))
+    }
+
+    /// Sets the maximum length of a chunk size line or a trailer section,
+    /// which is the default `Limits` unless set.
+    ///
+    /// Pass the limit given to `read_request_with_limit` or
+    /// `read_response_with_limit` to apply it to the body as well.
+    pub fn set_max_head_len(&mut self, max_head_len: usize) {
+        self.max_head_len = max_head_len;
+    }
+
+    /// Returns `true` if a `100 Continue` interim response is expected and
+    /// not sent yet.
+    pub fn expects_continue(&self) -> bool {
+        self.expects_continue
+    }
+
+    /// Writes a `100 Continue` interim response into `writer` if expected.
+    ///
+    /// It is written at most once.
+    pub fn send_continue<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.expects_continue {
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            writer.flush()?;
+            self.expects_continue = false;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the end of the body is read.
+    pub fn is_done(&self) -> bool {
+        self.pending == 0 && self.decoder.is_done()
+    }
+
+    /// Parses the trailer fields of a chunked body, pushing them into
+    /// `headers`.
+    ///
+    /// There are no trailer fields until the whole body is read.
+    pub fn trailers<'headers, 'buffer, Headers>(
+        &'buffer self,
+        headers: &'headers mut Headers,
+    ) -> Result<&'headers [HeaderField<'buffer>]>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        if self.trailers.is_empty() {
+            return Ok(&[]);
+        }
+        match parse_trailers(&self.trailers, headers)? {
+            Complete((trailers, _)) => Ok(trailers),
+            Incomplete => unreachable!("parsed trailers are incomplete"),
+        }
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Decodes the next part, returning `false` at the end of the body.
+    fn decode(&mut self) -> io::Result<bool> {
+        // The end may be decoded without reading, so reading does not block
+        // after the body.
+        if let Complete((_, Chunk::End(_))) = self.decoder.decode(&[])? {
+            return Ok(false);
+        }
+        if self.framing.is_empty() {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return self.eof();
+            }
+            match self.decoder.decode_partial(available)? {
+                Complete((len, Chunk::Data(range))) => {
+                    self.reader.consume(range.start);
+                    self.pending = range.len();
+                    debug_assert_eq!(len, range.end);
+                }
+                Complete((len, Chunk::End(range))) => {
+                    self.trailers.extend_from_slice(&available[range]);
+                    self.reader.consume(len);
+                    return Ok(false);
+                }
+                Incomplete => {
+                    let len = line_len(available);
+                    self.framing.extend_from_slice(&available[..len]);
+                    self.reader.consume(len);
+                }
+            }
+        } else {
+            match self.decoder.decode_partial(&self.framing)? {
+                Complete((len, Chunk::Data(ref range))) if range.is_empty() => {
+                    self.framing.drain(..len);
+                }
+                Complete((_, Chunk::Data(_))) => unreachable!("body data in framing"),
+                Complete((len, Chunk::End(range))) => {
+                    self.trailers.extend_from_slice(&self.framing[range]);
+                    self.framing.drain(..len);
+                    return Ok(false);
+                }
+                Incomplete => {
+                    let available = self.reader.fill_buf()?;
+                    if available.is_empty() {
+                        return self.eof();
+                    }
+                    let len = line_len(available);
+                    if self.framing.len() + len > self.max_head_len {
+                        return Err(StreamError::FramingTooLarge.into());
+                    }
+                    self.framing.extend_from_slice(&available[..len]);
+                    self.reader.consume(len);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn eof(&mut self) -> io::Result<bool> {
+        if self.decoder.eof() {
+            Ok(false)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed in a message body",
+            ))
+        }
+    }
+}
+
+impl<R: BufRead> Read for BodyReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        while self.pending == 0 {
+            if !self.decode()? {
+                return Ok(0);
+            }
+        }
+        let available = self.reader.fill_buf()?;
+        let len = available.len().min(self.pending).min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.reader.consume(len);
+        self.pending -= len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use crate::io::*;
+
+    fn read_body<R: BufRead>(body: &mut BodyReader<R>) -> Vec<u8> {
+        let mut data = Vec::new();
+        body.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_read_pipelined_requests() {
+        let input = &b"POST /a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+            POST /b HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+            3;x=y\r\nabc\r\n10\r\n0123456789abcdef\r\n0\r\nExpires: 0\r\n\r\n\
+            GET /c HTTP/1.1\r\n\r\n"[..];
+        // Small buffers split heads and chunk size lines.
+        for &capacity in [1, 2, 5, 16, 1024].iter() {
+            let mut reader = BufReader::with_capacity(capacity, input);
+            let mut buf = Vec::new();
+
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+            assert_eq!(req.target, "/a");
+            let mut body = BodyReader::for_request(&mut reader, &req).unwrap();
+            assert_eq!(read_body(&mut body), b"hello");
+            assert!(body.is_done());
+
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+            assert_eq!(req.target, "/b");
+            let mut body = BodyReader::for_request(&mut reader, &req).unwrap();
+            assert_eq!(read_body(&mut body), b"abc0123456789abcdef");
+            let mut trailers = Vec::<HeaderField>::with_capacity(4);
+            assert_eq!(
+                body.trailers(&mut trailers).unwrap(),
+                [HeaderField {
+                    name: "Expires",
+                    value: "0"
+                }]
+            );
+
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+            assert_eq!(req.target, "/c");
+            let mut body = BodyReader::for_request(&mut reader, &req).unwrap();
+            assert_eq!(read_body(&mut body), b"");
+            assert!(reader.fill_buf().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_response_until_eof() {
+        let mut reader = Cursor::new(&b"HTTP/1.0 200 OK\r\n\r\nhello"[..]);
+        let mut buf = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let res = read_response(&mut reader, &mut buf, &mut headers).unwrap();
+        assert_eq!(res.status, 200);
+        let mut body = BodyReader::for_response(&mut reader, "GET", &res).unwrap();
+        assert_eq!(read_body(&mut body), b"hello");
+
+        let mut reader = Cursor::new(&b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel"[..]);
+        let mut buf = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let res = read_response(&mut reader, &mut buf, &mut headers).unwrap();
+        let mut body = BodyReader::for_response(&mut reader, "GET", &res).unwrap();
+        let mut data = Vec::new();
+        let err = body.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(data, b"hel");
+    }
+
+    fn read_head_with_limit(input: &[u8], max_head_len: usize) -> result::Result<(), StreamError> {
+        let mut reader = input;
+        let mut buf = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(1);
+        read_request_with_limit(&mut reader, &mut buf, &mut headers, max_head_len).map(|_| ())
+    }
+
+    #[test]
+    fn test_read_head_errors() {
+        match read_head_with_limit(b"GET / HTTP/1.1\r\nHost: a\r\n", 64) {
+            Err(StreamError::Io(ref err)) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+            _ => panic!(),
+        }
+        match read_head_with_limit(b"GET / HTTP/1.1\r\nA: b\r\nC: d\r\n\r\n", 64) {
+            Err(StreamError::TooManyHeaders) => (),
+            _ => panic!(),
+        }
+        match read_head_with_limit(b"GET / HTTP/1.1\r\nA: b\r\n\r\n", 23) {
+            Err(StreamError::HeadTooLarge) => (),
+            _ => panic!(),
+        }
+        assert!(read_head_with_limit(b"GET / HTTP/1.1\r\nA: b\r\n\r\n", 24).is_ok());
+
+        let mut reader = &b"HTTP/1.1 200 OK\r\nA: b\r\n\r\n"[..];
+        let mut buf = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(1);
+        match read_response_with_limit(&mut reader, &mut buf, &mut headers, 24) {
+            Err(StreamError::HeadTooLarge) => (),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_read_head_after_empty_lines() {
+        let input = &b"\r\n\nGET / HTTP/1.1\r\nA: b\n\r\nrest"[..];
+        for &capacity in [1, 3, 1024].iter() {
+            let mut reader = BufReader::with_capacity(capacity, input);
+            let mut buf = Vec::new();
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+            assert_eq!(req.headers.len(), 1);
+            assert_eq!(reader.fill_buf().unwrap()[0], b'r');
+        }
+    }
+
+    #[test]
+    fn test_read_chunked_limit() {
+        let input = &b"3;ext=aaaaaaaaaaaaaaaa\r\nabc\r\n0\r\n\r\n"[..];
+        let mut body = BodyReader::new(BufReader::with_capacity(4, input), BodyLength::Chunked);
+        body.set_max_head_len(16);
+        let mut data = Vec::new();
+        match body.read_to_end(&mut data) {
+            Err(ref err) => {
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                match err.get_ref().and_then(|e| e.downcast_ref()) {
+                    Some(&StreamError::FramingTooLarge) => (),
+                    r => panic!("{:?}", r),
+                }
+            }
+            Ok(_) => panic!(),
+        }
+
+        let mut body = BodyReader::new(BufReader::with_capacity(4, input), BodyLength::Chunked);
+        assert_eq!(read_body(&mut body), b"abc");
+    }
+
+    #[test]
+    fn test_read_chunked_errors() {
+        let inputs: [&[u8]; 3] = [b"5\r\nhello", b"5\r\nhelloX", b"z\r\n"];
+        for input in inputs.iter() {
+            let mut body = BodyReader::new(*input, BodyLength::Chunked);
+            let mut data = Vec::new();
+            assert!(body.read_to_end(&mut data).is_err());
+        }
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        let input = &b"PUT / HTTP/1.1\r\nExpect: 100-Continue\r\nContent-Length: 2\r\n\r\nok"[..];
+        let mut reader = input;
+        let mut buf = Vec::new();
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let req = read_request(&mut reader, &mut buf, &mut headers).unwrap();
+        assert!(expects_continue(&req));
+
+        let mut body = BodyReader::for_request(&mut reader, &req).unwrap();
+        assert!(body.expects_continue());
+        let mut out = Vec::new();
+        body.send_continue(&mut out).unwrap();
+        body.send_continue(&mut out).unwrap();
+        assert_eq!(out, b"HTTP/1.1 100 Continue\r\n\r\n");
+        assert!(!body.expects_continue());
+        assert_eq!(read_body(&mut body), b"ok");
+
+        // No interim response is needed without a body.
+        let req = Request {
+            method: "GET",
+            target: "/",
+            minor_version: 1,
+            headers: &[HeaderField {
+                name: "Expect",
+                value: "100-continue",
+            }],
+        };
+        let body = BodyReader::for_request(&b""[..], &req).unwrap();
+        assert!(!body.expects_continue());
+
+        let req = Request {
+            minor_version: 0,
+            ..req
+        };
+        assert!(!expects_continue(&req));
+    }
+}
@@ -6,6 +6,7 @@
 #[cfg(not(feature = "std"))]
 extern crate core as std;
 
+use std::marker::PhantomData;
 use std::ops;
 use std::str;
 
@@ -14,15 +15,73 @@ extern crate alloc;
 
 #[cfg(feature = "arrayvec")]
 extern crate arrayvec;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "futures-io")]
+extern crate futures_io;
+#[cfg(feature = "http")]
+extern crate http;
+#[cfg(feature = "tokio")]
+extern crate tokio_util;
+
+#[cfg(test)]
+extern crate futures;
+#[cfg(test)]
+extern crate tokio;
 
 #[cfg(feature = "arrayvec")]
 mod arrayvec_header;
+#[cfg(feature = "futures-io")]
+pub mod async_io;
+pub mod auth;
+mod base64;
+pub mod body;
+#[cfg(feature = "bytes")]
+pub mod bytes_compat;
+#[cfg(feature = "alloc")]
+pub mod cache;
+pub mod chunked;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod conditional;
+#[cfg(feature = "std")]
+pub mod connection;
+pub mod date;
 mod errors;
+mod fields;
+#[cfg(feature = "alloc")]
+pub mod forwarding;
+pub mod h2c;
+#[cfg(feature = "alloc")]
+pub mod hpack;
+#[cfg(feature = "http")]
+pub mod http_compat;
+#[cfg(feature = "alloc")]
+mod huffman;
+pub mod informational;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "alloc")]
+mod owned;
+pub mod pipeline;
+pub mod proxy_protocol;
+#[cfg(feature = "alloc")]
+pub mod range;
 mod scanner;
+mod serialize;
+#[cfg(feature = "alloc")]
+pub mod sfv;
+mod sha1;
 mod simd;
+pub mod spans;
+pub mod upgrade;
 mod vec_header;
+pub mod websocket;
 
 pub use crate::errors::*;
+#[cfg(feature = "alloc")]
+pub use crate::owned::{OwnedHeaders, OwnedRequest, OwnedResponse};
+pub use crate::serialize::EncodedSlices;
 use crate::scanner::Scanner;
 
 /// A variants of parsing status.
@@ -179,9 +238,45 @@ pub struct HeaderField<'buffer> {
     pub value: &'buffer str,
 }
 
+// Makes a header field in tests.
+#[cfg(test)]
+pub(crate) fn field<'a>(name: &'a str, value: &'a str) -> HeaderField<'a> {
+    HeaderField { name, value }
+}
+
 /// Trait for a container of header fields.
-pub trait HeaderFieldCollection<'buffer>: ops::Deref<Target = [HeaderField<'buffer>]> {
-    fn push(&mut self, header_field: HeaderField<'buffer>) -> Result<()>;
+///
+/// `Field` is `HeaderFieldSpan` for containers used by span-based parsing.
+pub trait HeaderFieldCollection<'buffer, Field = HeaderField<'buffer>>:
+    ops::Deref<Target = [Field]>
+{
+    fn push(&mut self, header_field: Field) -> Result<()>;
+}
+
+// A collection validating header fields without storing them.
+struct Discard<'buffer>(PhantomData<HeaderField<'buffer>>);
+
+impl<'buffer> Discard<'buffer> {
+    #[inline]
+    fn new() -> Discard<'buffer> {
+        Discard(PhantomData)
+    }
+}
+
+impl<'buffer> ops::Deref for Discard<'buffer> {
+    type Target = [HeaderField<'buffer>];
+
+    #[inline]
+    fn deref(&self) -> &[HeaderField<'buffer>] {
+        &[]
+    }
+}
+
+impl<'buffer> HeaderFieldCollection<'buffer> for Discard<'buffer> {
+    #[inline]
+    fn push(&mut self, _: HeaderField<'buffer>) -> Result<()> {
+        Ok(())
+    }
 }
 
 macro_rules! make_bool_table {
@@ -332,21 +427,48 @@ const FIELD_VALUE_CHAR_RANGES: simd::CharRanges = simd::CharRanges {
     len: 6,
 };
 
-struct HttpPartParser<'buffer> {
-    scanner: Scanner<'buffer>,
+// Output of `HttpPartParser`, which makes parsed parts from borrowed strings.
+trait Output<'buffer> {
+    type Part;
+    type Field;
+
+    fn part(base: &'buffer [u8], s: &'buffer str) -> Self::Part;
+
+    fn field(name: Self::Part, value: Self::Part) -> Self::Field;
 }
 
-impl<'buffer> HttpPartParser<'buffer> {
+// Outputs borrowed strings as they are.
+struct Borrowed;
+
+impl<'buffer> Output<'buffer> for Borrowed {
+    type Part = &'buffer str;
+    type Field = HeaderField<'buffer>;
+
     #[inline]
-    fn new(buf: &'buffer [u8]) -> HttpPartParser {
-        HttpPartParser {
-            scanner: Scanner::new(buf),
-        }
+    fn part(_base: &'buffer [u8], s: &'buffer str) -> &'buffer str {
+        s
     }
 
     #[inline]
-    fn len(&self) -> usize {
-        self.scanner.len()
+    fn field(name: &'buffer str, value: &'buffer str) -> HeaderField<'buffer> {
+        HeaderField { name, value }
+    }
+}
+
+type RequestHead<'headers, P, F> = (P, P, u8, &'headers [F]);
+
+type ResponseHead<'headers, P, F> = (u8, u16, P, &'headers [F]);
+
+struct HttpPartParser<'buffer, O = Borrowed> {
+    base: &'buffer [u8],
+    scanner: Scanner<'buffer>,
+    output: PhantomData<O>,
+}
+
+impl<'buffer> HttpPartParser<'buffer> {
+    #[inline]
+    fn new(buf: &'buffer [u8]) -> HttpPartParser<'buffer> {
+        HttpPartParser::with_output(buf)
     }
 
     #[inline]
@@ -357,12 +479,12 @@ impl<'buffer> HttpPartParser<'buffer> {
     where
         Headers: HeaderFieldCollection<'buffer>,
     {
-        complete!(self.skip_empty_lines()?);
+        let (method, target, minor_version, headers) = complete!(self.parse_request_head(headers)?);
         Ok(Complete(Request::<'headers, 'buffer> {
-            method: complete!(self.parse_request_method()?),
-            target: complete!(self.parse_request_target()?),
-            minor_version: complete!(self.parse_request_http_version()?),
-            headers: complete!(self.parse_headers(headers)?),
+            method,
+            target,
+            minor_version,
+            headers,
         }))
     }
 
@@ -374,14 +496,74 @@ impl<'buffer> HttpPartParser<'buffer> {
     where
         Headers: HeaderFieldCollection<'buffer>,
     {
-        complete!(self.skip_empty_lines()?);
+        let (minor_version, status, reason, headers) = complete!(self.parse_response_head(headers)?);
         Ok(Complete(Response::<'headers, 'buffer> {
-            minor_version: complete!(self.parse_response_http_version()?),
-            status: complete!(self.parse_response_status_code()?),
-            reason: complete!(self.parse_response_reason_phrase()?),
-            headers: complete!(self.parse_headers(headers)?),
+            minor_version,
+            status,
+            reason,
+            headers,
         }))
     }
+}
+
+impl<'buffer, O: Output<'buffer>> HttpPartParser<'buffer, O> {
+    #[inline]
+    fn with_output(buf: &'buffer [u8]) -> HttpPartParser<'buffer, O> {
+        HttpPartParser {
+            base: buf,
+            scanner: Scanner::new(buf),
+            output: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.scanner.len()
+    }
+
+    #[inline]
+    fn part(&self, s: &'buffer str) -> O::Part {
+        O::part(self.base, s)
+    }
+
+    #[inline]
+    fn parse_request_head<'headers, Headers>(
+        &mut self,
+        headers: &'headers mut Headers,
+    ) -> Result<Status<RequestHead<'headers, O::Part, O::Field>>>
+    where
+        Headers: HeaderFieldCollection<'buffer, O::Field>,
+    {
+        complete!(self.skip_empty_lines()?);
+        let method = complete!(self.parse_request_method()?);
+        let target = complete!(self.parse_request_target()?);
+        Ok(Complete((
+            self.part(method),
+            self.part(target),
+            complete!(self.parse_request_http_version()?),
+            complete!(self.parse_headers(headers)?),
+        )))
+    }
+
+    #[inline]
+    fn parse_response_head<'headers, Headers>(
+        &mut self,
+        headers: &'headers mut Headers,
+    ) -> Result<Status<ResponseHead<'headers, O::Part, O::Field>>>
+    where
+        Headers: HeaderFieldCollection<'buffer, O::Field>,
+    {
+        complete!(self.skip_empty_lines()?);
+        let minor_version = complete!(self.parse_response_http_version()?);
+        let status = complete!(self.parse_response_status_code()?);
+        let reason = complete!(self.parse_response_reason_phrase()?);
+        Ok(Complete((
+            minor_version,
+            status,
+            self.part(reason),
+            complete!(self.parse_headers(headers)?),
+        )))
+    }
 
     #[inline]
     fn eof(&mut self) -> bool {
@@ -517,9 +699,9 @@ impl<'buffer> HttpPartParser<'buffer> {
     fn parse_headers<'headers, Headers>(
         &mut self,
         result: &'headers mut Headers,
-    ) -> Result<Status<(&'headers [HeaderField<'buffer>])>>
+    ) -> Result<Status<&'headers [O::Field]>>
     where
-        Headers: HeaderFieldCollection<'buffer>,
+        Headers: HeaderFieldCollection<'buffer, O::Field>,
     {
         loop {
             if complete!(self.skip_eol()?) {
@@ -534,11 +716,10 @@ impl<'buffer> HttpPartParser<'buffer> {
     }
 
     #[inline]
-    fn parse_header_field(&mut self) -> Result<Status<HeaderField<'buffer>>> {
-        Ok(Complete(HeaderField::<'buffer> {
-            name: complete!(self.parse_field_name()?),
-            value: complete!(self.parse_field_value()?),
-        }))
+    fn parse_header_field(&mut self) -> Result<Status<O::Field>> {
+        let name = complete!(self.parse_field_name()?);
+        let value = complete!(self.parse_field_value()?);
+        Ok(Complete(O::field(self.part(name), self.part(value))))
     }
 
     #[inline]
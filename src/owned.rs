@@ -0,0 +1,333 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::errors::*;
+use crate::{HeaderField, HeaderFieldCollection, Request, Response};
+
+// Strings of a message concatenated in a buffer with the end offset of each
+// string.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Parts {
+    buf: Box<str>,
+    ends: Box<[usize]>,
+}
+
+impl Parts {
+    fn new<'a, I>(strs: I, headers: &[HeaderField]) -> Parts
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut buf = String::new();
+        let mut ends = Vec::new();
+        let mut push = |s: &str| {
+            buf.push_str(s);
+            ends.push(buf.len());
+        };
+        for s in strs {
+            push(s);
+        }
+        for header in headers {
+            push(header.name);
+            push(header.value);
+        }
+        Parts {
+            buf: buf.into_boxed_str(),
+            ends: ends.into_boxed_slice(),
+        }
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> &str {
+        let start = if index == 0 { 0 } else { self.ends[index - 1] };
+        &self.buf[start..self.ends[index]]
+    }
+
+    #[inline]
+    fn headers(&self, offset: usize) -> OwnedHeaders<'_> {
+        OwnedHeaders {
+            parts: self,
+            index: offset,
+        }
+    }
+}
+
+/// An iterator over header fields of an owned request or response.
+#[derive(Clone, Debug)]
+pub struct OwnedHeaders<'a> {
+    parts: &'a Parts,
+    index: usize,
+}
+
+impl<'a> Iterator for OwnedHeaders<'a> {
+    type Item = HeaderField<'a>;
+
+    fn next(&mut self) -> Option<HeaderField<'a>> {
+        if self.index + 1 >= self.parts.ends.len() {
+            return None;
+        }
+        let header = HeaderField {
+            name: self.parts.get(self.index),
+            value: self.parts.get(self.index + 1),
+        };
+        self.index += 2;
+        Some(header)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.parts.ends.len() - self.index) / 2;
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for OwnedHeaders<'a> {}
+
+/// An owned request.
+///
+/// It holds all strings in a single buffer, so it outlives the parsed
+/// buffer and can be sent across threads.
+///
+/// ## Example
+///
+/// ```
+/// let owned = {
+///     let buf = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec();
+///     let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+///     let (req, _) = thhp::Request::parse(&buf, &mut headers).unwrap().unwrap();
+///     req.to_owned()
+/// };
+/// assert_eq!(owned.method(), "GET");
+///
+/// let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+/// let req = owned.as_request(&mut headers).unwrap();
+/// assert_eq!(req.headers[0].value, "example.com");
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OwnedRequest {
+    minor_version: u8,
+    parts: Parts,
+}
+
+impl OwnedRequest {
+    /// Returns the request method.
+    pub fn method(&self) -> &str {
+        self.parts.get(0)
+    }
+
+    /// Returns the request target.
+    pub fn target(&self) -> &str {
+        self.parts.get(1)
+    }
+
+    /// Returns the http minor version.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns an iterator over the header fields.
+    pub fn headers(&self) -> OwnedHeaders<'_> {
+        self.parts.headers(2)
+    }
+
+    /// Returns a borrowed request, pushing header fields into `headers`.
+    pub fn as_request<'headers, 'buffer, Headers>(
+        &'buffer self,
+        headers: &'headers mut Headers,
+    ) -> Result<Request<'headers, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        for header in self.headers() {
+            headers.push(header)?;
+        }
+        Ok(Request {
+            method: self.method(),
+            target: self.target(),
+            minor_version: self.minor_version,
+            headers,
+        })
+    }
+}
+
+/// An owned response.
+///
+/// It holds all strings in a single buffer, so it outlives the parsed
+/// buffer and can be sent across threads.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OwnedResponse {
+    minor_version: u8,
+    status: u16,
+    parts: Parts,
+}
+
+impl OwnedResponse {
+    /// Returns the http minor version.
+    pub fn minor_version(&self) -> u8 {
+        self.minor_version
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase.
+    pub fn reason(&self) -> &str {
+        self.parts.get(0)
+    }
+
+    /// Returns an iterator over the header fields.
+    pub fn headers(&self) -> OwnedHeaders<'_> {
+        self.parts.headers(1)
+    }
+
+    /// Returns a borrowed response, pushing header fields into `headers`.
+    pub fn as_response<'headers, 'buffer, Headers>(
+        &'buffer self,
+        headers: &'headers mut Headers,
+    ) -> Result<Response<'headers, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        for header in self.headers() {
+            headers.push(header)?;
+        }
+        Ok(Response {
+            minor_version: self.minor_version,
+            status: self.status,
+            reason: self.reason(),
+            headers,
+        })
+    }
+}
+
+impl<'headers, 'buffer: 'headers> Request<'headers, 'buffer> {
+    /// Copies the request into an `OwnedRequest`.
+    pub fn into_owned(self) -> OwnedRequest {
+        self.to_owned()
+    }
+
+    /// Copies the request into an `OwnedRequest` without consuming it.
+    ///
+    /// It is the same as `into_owned`.
+    pub fn to_owned(&self) -> OwnedRequest {
+        OwnedRequest {
+            minor_version: self.minor_version,
+            parts: Parts::new([self.method, self.target], self.headers),
+        }
+    }
+}
+
+impl<'headers, 'buffer: 'headers> Response<'headers, 'buffer> {
+    /// Copies the response into an `OwnedResponse`.
+    pub fn into_owned(self) -> OwnedResponse {
+        self.to_owned()
+    }
+
+    /// Copies the response into an `OwnedResponse` without consuming it.
+    ///
+    /// It is the same as `into_owned`.
+    pub fn to_owned(&self) -> OwnedResponse {
+        OwnedResponse {
+            minor_version: self.minor_version,
+            status: self.status,
+            parts: Parts::new(Some(self.reason), self.headers),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::*;
+
+    #[test]
+    fn test_owned_request() {
+        let owned = {
+            let buf =
+                b"POST /a?b HTTP/1.0\r\nHost: example.com\r\nX-Empty:\r\nA: b c\r\n\r\n".to_vec();
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let (req, _) = Request::parse(&buf, &mut headers).unwrap().unwrap();
+            req.to_owned()
+        };
+
+        assert_eq!(owned.method(), "POST");
+        assert_eq!(owned.target(), "/a?b");
+        assert_eq!(owned.minor_version(), 0);
+        assert_eq!(owned.headers().len(), 3);
+        assert_eq!(
+            owned.headers().collect::<Vec<_>>(),
+            [
+                HeaderField {
+                    name: "Host",
+                    value: "example.com"
+                },
+                HeaderField {
+                    name: "X-Empty",
+                    value: ""
+                },
+                HeaderField {
+                    name: "A",
+                    value: "b c"
+                },
+            ]
+        );
+
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let req = owned.as_request(&mut headers).unwrap();
+        assert_eq!(req.to_owned(), owned);
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.headers.len(), 3);
+        assert_eq!(req.into_owned(), owned);
+
+        let mut headers = Vec::<HeaderField>::with_capacity(2);
+        assert_eq!(owned.as_request(&mut headers), Err(OutOfCapacity));
+    }
+
+    #[test]
+    fn test_owned_response() {
+        let owned = {
+            let buf = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec();
+            let mut headers = Vec::<HeaderField>::with_capacity(4);
+            let (res, _) = Response::parse(&buf, &mut headers).unwrap().unwrap();
+            res.to_owned()
+        };
+
+        assert_eq!(owned.minor_version(), 1);
+        assert_eq!(owned.status(), 404);
+        assert_eq!(owned.reason(), "Not Found");
+
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let res = owned.as_response(&mut headers).unwrap();
+        assert_eq!(res.headers[0].name, "Content-Length");
+        assert_eq!(res.headers[0].value, "0");
+        assert_eq!(res.to_owned(), owned);
+        assert_eq!(res.into_owned(), owned);
+    }
+
+    #[test]
+    fn test_owned_without_headers() {
+        let res = Response {
+            minor_version: 1,
+            status: 204,
+            reason: "",
+            headers: &[],
+        };
+        let owned = res.to_owned();
+        assert_eq!(owned.reason(), "");
+        assert_eq!(owned.headers().next(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_owned_is_send() {
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<OwnedRequest>();
+        assert_send::<OwnedResponse>();
+    }
+}
@@ -0,0 +1,205 @@
+//! Parsing pipelined requests in a single buffer.
+//!
+//! `Pipeline` parses requests one after another, stepping over each body by
+//! its `Content-Length`. Header fields of each request are stored in a slice
+//! given for the request, so the same storage is reused for every request
+//! without allocation.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::pipeline::Pipeline;
+//!
+//! let buf = b"POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\nhiGET /b HTTP/1.1\r\n\r\nGET /c HT";
+//! let mut headers = [thhp::HeaderField { name: "", value: "" }; 16];
+//! let mut pipeline = Pipeline::new(buf);
+//!
+//! let (req, body) = pipeline.next_request(&mut headers).unwrap().unwrap();
+//! assert_eq!(req.target, "/a");
+//! assert_eq!(&buf[body], b"hi");
+//!
+//! let (req, body) = pipeline.next_request(&mut headers).unwrap().unwrap();
+//! assert_eq!(req.target, "/b");
+//! assert!(body.is_empty());
+//!
+//! assert!(pipeline.next_request(&mut headers).unwrap().is_incomplete());
+//! // The rest is kept to parse again with more data.
+//! assert_eq!(pipeline.consumed(), 60);
+//! ```
+use std::ops::{Deref, Range};
+
+use crate::body::BodyLength;
+use crate::errors::*;
+use crate::{Complete, HeaderField, HeaderFieldCollection, Incomplete, Request, Status};
+
+// A collection of header fields stored in a slice.
+struct SliceHeaders<'a, 'buffer: 'a> {
+    slice: &'a mut [HeaderField<'buffer>],
+    len: usize,
+}
+
+impl<'a, 'buffer> Deref for SliceHeaders<'a, 'buffer> {
+    type Target = [HeaderField<'buffer>];
+
+    fn deref(&self) -> &[HeaderField<'buffer>] {
+        &self.slice[..self.len]
+    }
+}
+
+impl<'a, 'buffer> HeaderFieldCollection<'buffer> for SliceHeaders<'a, 'buffer> {
+    fn push(&mut self, header_field: HeaderField<'buffer>) -> Result<()> {
+        match self.slice.get_mut(self.len) {
+            Some(slot) => {
+                *slot = header_field;
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(OutOfCapacity),
+        }
+    }
+}
+
+/// A parser of pipelined requests in a buffer.
+///
+/// Request bodies are delimited by `Content-Length`. A request in the
+/// chunked transfer coding is an error, because its body cannot be stepped
+/// over without decoding it: parse the rest with a `body::BodyDecoder`
+/// instead.
+#[derive(Clone, Debug)]
+pub struct Pipeline<'buffer> {
+    buf: &'buffer [u8],
+    pos: usize,
+}
+
+impl<'buffer> Pipeline<'buffer> {
+    /// Creates a parser of requests at the start of `buf`.
+    pub fn new(buf: &'buffer [u8]) -> Pipeline<'buffer> {
+        Pipeline { buf, pos: 0 }
+    }
+
+    /// Parses the next request, storing its header fields in `headers`.
+    ///
+    /// Returns the request and the range of its body in the buffer, or
+    /// `Incomplete` if the buffer ends within the request or its body.
+    pub fn next_request<'headers>(
+        &mut self,
+        headers: &'headers mut [HeaderField<'buffer>],
+    ) -> Result<Status<(Request<'headers, 'buffer>, Range<usize>)>> {
+        let buf = &self.buf[self.pos..];
+        let (method, target, minor_version, len, head_len) = {
+            let mut collection = SliceHeaders {
+                slice: &mut *headers,
+                len: 0,
+            };
+            let (req, head_len) = match Request::parse(buf, &mut collection)? {
+                Complete(v) => v,
+                Incomplete => return Ok(Incomplete),
+            };
+            (
+                req.method,
+                req.target,
+                req.minor_version,
+                req.headers.len(),
+                head_len,
+            )
+        };
+        let headers: &'headers [HeaderField<'buffer>] = headers;
+        let headers = &headers[..len];
+
+        let body_len = match BodyLength::of_request(headers)? {
+            BodyLength::Length(len) => len,
+            _ => return Err(InvalidTransferEncoding),
+        };
+        if body_len > (buf.len() - head_len) as u64 {
+            return Ok(Incomplete);
+        }
+        let start = self.pos + head_len;
+        let end = start + body_len as usize;
+        self.pos = end;
+        let req = Request {
+            method,
+            target,
+            minor_version,
+            headers,
+        };
+        Ok(Complete((req, start..end)))
+    }
+
+    /// Returns the number of bytes of the requests parsed so far, which can
+    /// be discarded from the buffer.
+    pub fn consumed(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the bytes after the requests parsed so far.
+    pub fn rest(&self) -> &'buffer [u8] {
+        &self.buf[self.pos..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::*;
+
+    const EMPTY: HeaderField<'static> = HeaderField {
+        name: "",
+        value: "",
+    };
+
+    #[test]
+    fn test_pipeline() {
+        let buf = b"GET /a HTTP/1.1\r\nHost: a\r\nAccept: */*\r\n\r\n\
+                    PUT /b HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc\
+                    GET /c HTTP/1.1\r\n\r\n";
+        let mut headers = [EMPTY; 4];
+        let mut pipeline = Pipeline::new(buf);
+
+        let (req, body) = pipeline.next_request(&mut headers).unwrap().unwrap();
+        assert_eq!(req.target, "/a");
+        assert_eq!(req.headers.len(), 2);
+        assert_eq!(body, 41..41);
+
+        let (req, body) = pipeline.next_request(&mut headers).unwrap().unwrap();
+        assert_eq!(req.method, "PUT");
+        assert_eq!(req.headers.len(), 1);
+        assert_eq!(&buf[body], b"abc");
+
+        let (req, body) = pipeline.next_request(&mut headers).unwrap().unwrap();
+        assert_eq!(req.target, "/c");
+        assert_eq!(req.headers.len(), 0);
+        assert_eq!(body.end, buf.len());
+
+        assert!(pipeline.next_request(&mut headers).unwrap().is_incomplete());
+        assert_eq!(pipeline.consumed(), buf.len());
+        assert!(pipeline.rest().is_empty());
+    }
+
+    #[test]
+    fn test_incomplete_body() {
+        let buf = b"GET /a HTTP/1.1\r\n\r\nPOST /b HTTP/1.1\r\nContent-Length: 5\r\n\r\nab";
+        let mut headers = [EMPTY; 4];
+        let mut pipeline = Pipeline::new(buf);
+        pipeline.next_request(&mut headers).unwrap().unwrap();
+        assert!(pipeline.next_request(&mut headers).unwrap().is_incomplete());
+        assert_eq!(pipeline.consumed(), 19);
+        assert_eq!(pipeline.rest(), &buf[19..]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut headers = [EMPTY; 1];
+        let buf = b"GET / HTTP/1.1\r\nA: b\r\nC: d\r\n\r\n";
+        assert_eq!(
+            Pipeline::new(buf).next_request(&mut headers),
+            Err(OutOfCapacity)
+        );
+
+        let buf = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        let mut pipeline = Pipeline::new(buf);
+        assert_eq!(
+            pipeline.next_request(&mut headers),
+            Err(InvalidTransferEncoding)
+        );
+        assert_eq!(pipeline.consumed(), 0);
+    }
+}
@@ -0,0 +1,705 @@
+//! The PROXY protocol of HAProxy, versions 1 and 2.
+//!
+//! A load balancer sends a PROXY protocol header at the start of a
+//! connection to pass the addresses of the original connection.
+//! `ProxyHeader::parse` parses it and returns its length, so the rest of the
+//! buffer can be parsed as requests.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::proxy_protocol::ProxyHeader;
+//!
+//! let buf = b"PROXY TCP4 192.0.2.1 198.51.100.2 56324 443\r\nGET / HTTP/1.1\r\n\r\n";
+//! let (header, len) = ProxyHeader::parse(buf).unwrap().unwrap();
+//! assert_eq!(header.source(), Some("192.0.2.1:56324".parse().unwrap()));
+//! assert_eq!(header.destination(), Some("198.51.100.2:443".parse().unwrap()));
+//!
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(&buf[len..], &mut headers).unwrap().unwrap();
+//! assert_eq!(req.target, "/");
+//! ```
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::str;
+
+use crate::errors::*;
+use crate::{Complete, Incomplete, Status};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+// The longest line of version 1 including CRLF.
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_HEADER_LEN: usize = 16;
+// The length of a path of a UNIX socket in version 2.
+const UNIX_PATH_LEN: usize = 108;
+
+/// The type of `ALPN` TLV.
+pub const PP2_TYPE_ALPN: u8 = 0x01;
+/// The type of `AUTHORITY` TLV.
+pub const PP2_TYPE_AUTHORITY: u8 = 0x02;
+/// The type of `CRC32C` TLV.
+pub const PP2_TYPE_CRC32C: u8 = 0x03;
+/// The type of `NOOP` TLV.
+pub const PP2_TYPE_NOOP: u8 = 0x04;
+/// The type of `UNIQUE_ID` TLV.
+pub const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+/// The type of `SSL` TLV.
+pub const PP2_TYPE_SSL: u8 = 0x20;
+/// The type of `SSL_VERSION` sub-TLV of `SSL`.
+pub const PP2_SUBTYPE_SSL_VERSION: u8 = 0x21;
+/// The type of `SSL_CN` sub-TLV of `SSL`.
+pub const PP2_SUBTYPE_SSL_CN: u8 = 0x22;
+/// The type of `SSL_CIPHER` sub-TLV of `SSL`.
+pub const PP2_SUBTYPE_SSL_CIPHER: u8 = 0x23;
+/// The type of `SSL_SIG_ALG` sub-TLV of `SSL`.
+pub const PP2_SUBTYPE_SSL_SIG_ALG: u8 = 0x24;
+/// The type of `SSL_KEY_ALG` sub-TLV of `SSL`.
+pub const PP2_SUBTYPE_SSL_KEY_ALG: u8 = 0x25;
+/// The type of `NETNS` TLV.
+pub const PP2_TYPE_NETNS: u8 = 0x30;
+
+/// The command of a PROXY protocol header.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Command {
+    /// The connection is made by the proxy itself, for example for health
+    /// checks. The addresses of the connection are used.
+    Local,
+    /// The connection is relayed for a client.
+    Proxy,
+}
+
+/// The transport protocol of the original connection.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Transport {
+    /// Unknown or unspecified.
+    Unspecified,
+    /// A stream, such as TCP.
+    Stream,
+    /// Datagrams, such as UDP.
+    Datagram,
+}
+
+/// The addresses of the original connection.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Addresses<'buffer> {
+    /// Unknown or unspecified.
+    Unspecified,
+    /// IPv4 addresses.
+    Ipv4 {
+        /// The address of the client.
+        source: SocketAddrV4,
+        /// The address the client connected to.
+        destination: SocketAddrV4,
+    },
+    /// IPv6 addresses.
+    Ipv6 {
+        /// The address of the client.
+        source: SocketAddrV6,
+        /// The address the client connected to.
+        destination: SocketAddrV6,
+    },
+    /// Paths of UNIX sockets, without trailing NULs.
+    Unix {
+        /// The path of the client.
+        source: &'buffer [u8],
+        /// The path the client connected to.
+        destination: &'buffer [u8],
+    },
+}
+
+/// A TLV (type-length-value) of a version 2 header.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Tlv<'buffer> {
+    /// The type.
+    pub kind: u8,
+    /// The value.
+    pub value: &'buffer [u8],
+}
+
+/// An iterator over TLVs.
+#[derive(Clone, Debug)]
+pub struct Tlvs<'buffer> {
+    rest: &'buffer [u8],
+}
+
+impl<'buffer> Tlvs<'buffer> {
+    // Validates TLVs in `buf`.
+    fn new(buf: &'buffer [u8]) -> Result<Tlvs<'buffer>> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let (tlv, len) = next_tlv(rest).ok_or(InvalidProxyHeader)?;
+            if tlv.kind == PP2_TYPE_SSL {
+                Ssl::new(tlv.value)?;
+            }
+            rest = &rest[len..];
+        }
+        Ok(Tlvs { rest: buf })
+    }
+}
+
+fn next_tlv(buf: &[u8]) -> Option<(Tlv<'_>, usize)> {
+    if buf.len() < 3 {
+        return None;
+    }
+    let len = 3 + u16::from_be_bytes([buf[1], buf[2]]) as usize;
+    if buf.len() < len {
+        return None;
+    }
+    Some((
+        Tlv {
+            kind: buf[0],
+            value: &buf[3..len],
+        },
+        len,
+    ))
+}
+
+impl<'buffer> Iterator for Tlvs<'buffer> {
+    type Item = Tlv<'buffer>;
+
+    fn next(&mut self) -> Option<Tlv<'buffer>> {
+        // TLVs are already validated.
+        let (tlv, len) = next_tlv(self.rest)?;
+        self.rest = &self.rest[len..];
+        Some(tlv)
+    }
+}
+
+/// The value of `SSL` TLV.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Ssl<'buffer> {
+    /// The flags of the client connection.
+    pub client: u8,
+    /// Zero if the client presented a certificate which was verified.
+    pub verify: u32,
+    tlvs: &'buffer [u8],
+}
+
+impl<'buffer> Ssl<'buffer> {
+    /// The client connected over SSL/TLS.
+    pub const CLIENT_SSL: u8 = 0x01;
+    /// The client presented a certificate over the connection.
+    pub const CLIENT_CERT_CONN: u8 = 0x02;
+    /// The client presented a certificate at least once over the session.
+    pub const CLIENT_CERT_SESS: u8 = 0x04;
+
+    fn new(value: &'buffer [u8]) -> Result<Ssl<'buffer>> {
+        if value.len() < 5 {
+            return Err(InvalidProxyHeader);
+        }
+        let tlvs = &value[5..];
+        let mut rest = tlvs;
+        while !rest.is_empty() {
+            let (_, len) = next_tlv(rest).ok_or(InvalidProxyHeader)?;
+            rest = &rest[len..];
+        }
+        Ok(Ssl {
+            client: value[0],
+            verify: u32::from_be_bytes([value[1], value[2], value[3], value[4]]),
+            tlvs,
+        })
+    }
+
+    /// Returns `true` if the client connected over SSL/TLS.
+    pub fn is_ssl(&self) -> bool {
+        self.client & Ssl::CLIENT_SSL != 0
+    }
+
+    /// Returns `true` if the client presented a certificate which was
+    /// verified.
+    pub fn is_verified(&self) -> bool {
+        self.client & (Ssl::CLIENT_CERT_CONN | Ssl::CLIENT_CERT_SESS) != 0 && self.verify == 0
+    }
+
+    /// Returns an iterator over the sub-TLVs.
+    pub fn tlvs(&self) -> Tlvs<'buffer> {
+        Tlvs { rest: self.tlvs }
+    }
+
+    fn find_str(&self, kind: u8) -> Option<&'buffer str> {
+        self.tlvs()
+            .find(|tlv| tlv.kind == kind)
+            .and_then(|tlv| str::from_utf8(tlv.value).ok())
+    }
+
+    /// Returns the version of SSL/TLS, such as `TLSv1.3`.
+    pub fn version(&self) -> Option<&'buffer str> {
+        self.find_str(PP2_SUBTYPE_SSL_VERSION)
+    }
+
+    /// Returns the common name of the subject of the client certificate.
+    pub fn common_name(&self) -> Option<&'buffer str> {
+        self.find_str(PP2_SUBTYPE_SSL_CN)
+    }
+
+    /// Returns the name of the cipher, such as `ECDHE-RSA-AES128-GCM-SHA256`.
+    pub fn cipher(&self) -> Option<&'buffer str> {
+        self.find_str(PP2_SUBTYPE_SSL_CIPHER)
+    }
+
+    /// Returns the signature algorithm of the server certificate.
+    pub fn signature_algorithm(&self) -> Option<&'buffer str> {
+        self.find_str(PP2_SUBTYPE_SSL_SIG_ALG)
+    }
+
+    /// Returns the key algorithm of the server certificate.
+    pub fn key_algorithm(&self) -> Option<&'buffer str> {
+        self.find_str(PP2_SUBTYPE_SSL_KEY_ALG)
+    }
+}
+
+/// A PROXY protocol header.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ProxyHeader<'buffer> {
+    /// The version of the protocol, 1 or 2.
+    pub version: u8,
+    /// The command.
+    pub command: Command,
+    /// The transport protocol.
+    pub transport: Transport,
+    /// The addresses.
+    pub addresses: Addresses<'buffer>,
+    tlvs: &'buffer [u8],
+}
+
+impl<'buffer> ProxyHeader<'buffer> {
+    /// Parses a PROXY protocol header of either version at the start of
+    /// `buf`.
+    ///
+    /// Returns the header and its length, or `Incomplete` if the buffer ends
+    /// within the header. The header is not optional: anything else is
+    /// `InvalidProxyHeader`.
+    pub fn parse(buf: &'buffer [u8]) -> Result<Status<(ProxyHeader<'buffer>, usize)>> {
+        if buf.starts_with(V2_SIGNATURE) {
+            parse_v2(buf)
+        } else if buf.starts_with(V1_PREFIX) {
+            parse_v1(buf)
+        } else if V2_SIGNATURE.starts_with(buf) || V1_PREFIX.starts_with(buf) {
+            Ok(Incomplete)
+        } else {
+            Err(InvalidProxyHeader)
+        }
+    }
+
+    /// Returns the address of the client, or `None` unless it is an IP
+    /// address.
+    pub fn source(&self) -> Option<SocketAddr> {
+        match self.addresses {
+            Addresses::Ipv4 { source, .. } => Some(SocketAddr::V4(source)),
+            Addresses::Ipv6 { source, .. } => Some(SocketAddr::V6(source)),
+            _ => None,
+        }
+    }
+
+    /// Returns the address the client connected to, or `None` unless it is
+    /// an IP address.
+    pub fn destination(&self) -> Option<SocketAddr> {
+        match self.addresses {
+            Addresses::Ipv4 { destination, .. } => Some(SocketAddr::V4(destination)),
+            Addresses::Ipv6 { destination, .. } => Some(SocketAddr::V6(destination)),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the TLVs, which are only in version 2.
+    pub fn tlvs(&self) -> Tlvs<'buffer> {
+        Tlvs { rest: self.tlvs }
+    }
+
+    fn find(&self, kind: u8) -> Option<&'buffer [u8]> {
+        self.tlvs()
+            .find(|tlv| tlv.kind == kind)
+            .map(|tlv| tlv.value)
+    }
+
+    /// Returns the protocol negotiated by ALPN.
+    pub fn alpn(&self) -> Option<&'buffer [u8]> {
+        self.find(PP2_TYPE_ALPN)
+    }
+
+    /// Returns the host name sent by the client, usually by SNI.
+    pub fn authority(&self) -> Option<&'buffer str> {
+        self.find(PP2_TYPE_AUTHORITY)
+            .and_then(|v| str::from_utf8(v).ok())
+    }
+
+    /// Returns the information of SSL/TLS.
+    pub fn ssl(&self) -> Option<Ssl<'buffer>> {
+        // `SSL` TLV is already validated.
+        self.find(PP2_TYPE_SSL).and_then(|v| Ssl::new(v).ok())
+    }
+}
+
+fn parse_v1(buf: &[u8]) -> Result<Status<(ProxyHeader<'_>, usize)>> {
+    let limit = buf.len().min(V1_MAX_LEN);
+    let end = match buf[..limit].iter().position(|&b| b == b'\n') {
+        Some(i) if buf[i - 1] == b'\r' => i - 1,
+        Some(_) => return Err(InvalidProxyHeader),
+        None if limit < V1_MAX_LEN => return Ok(Incomplete),
+        None => return Err(InvalidProxyHeader),
+    };
+    let line = str::from_utf8(&buf[V1_PREFIX.len()..end]).map_err(|_| InvalidProxyHeader)?;
+
+    let mut parts = line.split(' ');
+    let addresses = match parts.next() {
+        // The rest of the line is ignored.
+        Some("UNKNOWN") => Addresses::Unspecified,
+        Some("TCP4") => {
+            let (src, dst, src_port, dst_port) = v1_addresses::<Ipv4Addr>(&mut parts)?;
+            Addresses::Ipv4 {
+                source: SocketAddrV4::new(src, src_port),
+                destination: SocketAddrV4::new(dst, dst_port),
+            }
+        }
+        Some("TCP6") => {
+            let (src, dst, src_port, dst_port) = v1_addresses::<Ipv6Addr>(&mut parts)?;
+            Addresses::Ipv6 {
+                source: SocketAddrV6::new(src, src_port, 0, 0),
+                destination: SocketAddrV6::new(dst, dst_port, 0, 0),
+            }
+        }
+        _ => return Err(InvalidProxyHeader),
+    };
+    let transport = match addresses {
+        Addresses::Unspecified => Transport::Unspecified,
+        _ => Transport::Stream,
+    };
+    let header = ProxyHeader {
+        version: 1,
+        command: Command::Proxy,
+        transport,
+        addresses,
+        tlvs: &[],
+    };
+    Ok(Complete((header, end + 2)))
+}
+
+fn v1_addresses<'a, A>(parts: &mut str::Split<'a, char>) -> Result<(A, A, u16, u16)>
+where
+    A: str::FromStr,
+{
+    let mut next = || parts.next().ok_or(InvalidProxyHeader);
+    let src = next()?.parse().map_err(|_| InvalidProxyHeader)?;
+    let dst = next()?.parse().map_err(|_| InvalidProxyHeader)?;
+    let src_port = v1_port(next()?)?;
+    let dst_port = v1_port(next()?)?;
+    if parts.next().is_some() {
+        return Err(InvalidProxyHeader);
+    }
+    Ok((src, dst, src_port, dst_port))
+}
+
+fn v1_port(s: &str) -> Result<u16> {
+    if s.is_empty() || s.len() > 5 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(InvalidProxyHeader);
+    }
+    s.parse().map_err(|_| InvalidProxyHeader)
+}
+
+fn parse_v2(buf: &[u8]) -> Result<Status<(ProxyHeader<'_>, usize)>> {
+    if buf.len() < V2_HEADER_LEN {
+        return Ok(Incomplete);
+    }
+    let len = V2_HEADER_LEN + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < len {
+        return Ok(Incomplete);
+    }
+    let block = &buf[V2_HEADER_LEN..len];
+
+    if buf[12] >> 4 != 2 {
+        return Err(InvalidProxyHeader);
+    }
+    let command = match buf[12] & 0x0F {
+        0 => Command::Local,
+        1 => Command::Proxy,
+        _ => return Err(InvalidProxyHeader),
+    };
+    // The family, the transport and the block of a `LOCAL` connection are
+    // discarded without validation.
+    if command == Command::Local {
+        let header = ProxyHeader {
+            version: 2,
+            command,
+            transport: Transport::Unspecified,
+            addresses: Addresses::Unspecified,
+            tlvs: &[],
+        };
+        return Ok(Complete((header, len)));
+    }
+    let transport = match buf[13] & 0x0F {
+        0 => Transport::Unspecified,
+        1 => Transport::Stream,
+        2 => Transport::Datagram,
+        _ => return Err(InvalidProxyHeader),
+    };
+    let (addresses, addresses_len) = match buf[13] >> 4 {
+        0 => (Addresses::Unspecified, 0),
+        1 if block.len() >= 12 => {
+            let ip = |i: usize| Ipv4Addr::new(block[i], block[i + 1], block[i + 2], block[i + 3]);
+            let addresses = Addresses::Ipv4 {
+                source: SocketAddrV4::new(ip(0), port(block, 8)),
+                destination: SocketAddrV4::new(ip(4), port(block, 10)),
+            };
+            (addresses, 12)
+        }
+        2 if block.len() >= 36 => {
+            let ip = |i: usize| {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&block[i..i + 16]);
+                Ipv6Addr::from(octets)
+            };
+            let addresses = Addresses::Ipv6 {
+                source: SocketAddrV6::new(ip(0), port(block, 32), 0, 0),
+                destination: SocketAddrV6::new(ip(16), port(block, 34), 0, 0),
+            };
+            (addresses, 36)
+        }
+        3 if block.len() >= UNIX_PATH_LEN * 2 => {
+            let addresses = Addresses::Unix {
+                source: unix_path(&block[..UNIX_PATH_LEN]),
+                destination: unix_path(&block[UNIX_PATH_LEN..UNIX_PATH_LEN * 2]),
+            };
+            (addresses, UNIX_PATH_LEN * 2)
+        }
+        _ => return Err(InvalidProxyHeader),
+    };
+
+    let header = ProxyHeader {
+        version: 2,
+        command,
+        transport,
+        addresses,
+        tlvs: Tlvs::new(&block[addresses_len..])?.rest,
+    };
+    Ok(Complete((header, len)))
+}
+
+fn port(block: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([block[i], block[i + 1]])
+}
+
+// Returns a path of a UNIX socket up to the first NUL.
+fn unix_path(path: &[u8]) -> &[u8] {
+    let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+    &path[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::proxy_protocol::*;
+
+    fn v2(command: u8, family: u8, block: &[u8]) -> Vec<u8> {
+        let mut buf = V2_SIGNATURE.to_vec();
+        buf.push(0x20 | command);
+        buf.push(family);
+        buf.extend_from_slice(&(block.len() as u16).to_be_bytes());
+        buf.extend_from_slice(block);
+        buf
+    }
+
+    fn tlv(kind: u8, value: &[u8]) -> Vec<u8> {
+        let mut buf = [kind].to_vec();
+        buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        buf.extend_from_slice(value);
+        buf
+    }
+
+    #[test]
+    fn test_v1() {
+        let buf = b"PROXY TCP4 255.255.255.255 10.0.0.1 65535 80\r\nGET";
+        let (header, len) = ProxyHeader::parse(buf).unwrap().unwrap();
+        assert_eq!(&buf[len..], b"GET");
+        assert_eq!(header.version, 1);
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.transport, Transport::Stream);
+        assert_eq!(
+            header.addresses,
+            Addresses::Ipv4 {
+                source: SocketAddrV4::new(Ipv4Addr::new(255, 255, 255, 255), 65535),
+                destination: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80),
+            }
+        );
+        assert_eq!(header.tlvs().count(), 0);
+
+        let buf = b"PROXY TCP6 2001:db8::1 ::1 1 2\r\n";
+        let (header, len) = ProxyHeader::parse(buf).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(header.source(), Some("[2001:db8::1]:1".parse().unwrap()));
+        assert_eq!(header.destination(), Some("[::1]:2".parse().unwrap()));
+
+        let buf = b"PROXY UNKNOWN ffff:f...f:ffff 65535 65535\r\n";
+        let (header, len) = ProxyHeader::parse(buf).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(header.addresses, Addresses::Unspecified);
+        assert_eq!(header.source(), None);
+    }
+
+    #[test]
+    fn test_v1_incomplete() {
+        let buf = b"PROXY TCP4 192.0.2.1 192.0.2.2 1 2\r\n";
+        for i in 0..buf.len() {
+            assert_eq!(ProxyHeader::parse(&buf[..i]), Ok(Incomplete), "{}", i);
+        }
+    }
+
+    #[test]
+    fn test_v1_errors() {
+        for buf in &[
+            &b"PROXY TCP4 192.0.2.1 192.0.2.2 1\r\n"[..],
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 1 2 3\r\n",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 1 65536\r\n",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 +1 2\r\n",
+            b"PROXY TCP4 192.0.2.1  192.0.2.2 1 2\r\n",
+            b"PROXY TCP4 ::1 ::1 1 2\r\n",
+            b"PROXY TCP6 192.0.2.1 192.0.2.2 1 2\r\n",
+            b"PROXY UDP4 192.0.2.1 192.0.2.2 1 2\r\n",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 1 2\n",
+            b"GET / HTTP/1.1\r\n\r\n",
+        ] {
+            assert_eq!(
+                ProxyHeader::parse(buf),
+                Err(InvalidProxyHeader),
+                "{:?}",
+                str::from_utf8(buf)
+            );
+        }
+
+        // The line is too long.
+        let mut buf = b"PROXY UNKNOWN ".to_vec();
+        buf.resize(V1_MAX_LEN, b'x');
+        assert_eq!(ProxyHeader::parse(&buf), Err(InvalidProxyHeader));
+        buf.truncate(V1_MAX_LEN - 2);
+        buf.extend_from_slice(b"\r\n");
+        assert!(ProxyHeader::parse(&buf).unwrap().is_complete());
+    }
+
+    #[test]
+    fn test_v2_ipv4_with_tlvs() {
+        let mut ssl = [0x07, 0, 0, 0, 0].to_vec();
+        ssl.extend(tlv(PP2_SUBTYPE_SSL_VERSION, b"TLSv1.3"));
+        ssl.extend(tlv(PP2_SUBTYPE_SSL_CN, b"client.example"));
+        let mut block = [192, 0, 2, 1, 198, 51, 100, 2, 0xDC, 0x04, 0x01, 0xBB].to_vec();
+        block.extend(tlv(PP2_TYPE_ALPN, b"h2"));
+        block.extend(tlv(PP2_TYPE_AUTHORITY, b"example.com"));
+        block.extend(tlv(PP2_TYPE_NOOP, b""));
+        block.extend(tlv(PP2_TYPE_SSL, &ssl));
+        let mut buf = v2(1, 0x11, &block);
+        let len = buf.len();
+        buf.extend_from_slice(b"GET");
+
+        let (header, consumed) = ProxyHeader::parse(&buf).unwrap().unwrap();
+        assert_eq!(consumed, len);
+        assert_eq!(header.version, 2);
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.transport, Transport::Stream);
+        assert_eq!(header.source(), Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(
+            header.destination(),
+            Some("198.51.100.2:443".parse().unwrap())
+        );
+        assert_eq!(header.alpn(), Some(&b"h2"[..]));
+        assert_eq!(header.authority(), Some("example.com"));
+        assert_eq!(
+            header.tlvs().map(|tlv| tlv.kind).collect::<Vec<_>>(),
+            [
+                PP2_TYPE_ALPN,
+                PP2_TYPE_AUTHORITY,
+                PP2_TYPE_NOOP,
+                PP2_TYPE_SSL
+            ]
+        );
+
+        let ssl = header.ssl().unwrap();
+        assert!(ssl.is_ssl());
+        assert!(ssl.is_verified());
+        assert_eq!(ssl.version(), Some("TLSv1.3"));
+        assert_eq!(ssl.common_name(), Some("client.example"));
+        assert_eq!(ssl.cipher(), None);
+
+        for i in 0..len {
+            assert_eq!(ProxyHeader::parse(&buf[..i]), Ok(Incomplete), "{}", i);
+        }
+    }
+
+    #[test]
+    fn test_v2_families() {
+        let mut block = [0u8; 36];
+        block[15] = 1;
+        block[31] = 2;
+        block[33] = 80;
+        let buf = v2(1, 0x22, &block);
+        let (header, _) = ProxyHeader::parse(&buf).unwrap().unwrap();
+        assert_eq!(header.transport, Transport::Datagram);
+        assert_eq!(header.source(), Some("[::1]:80".parse().unwrap()));
+        assert_eq!(header.destination(), Some("[::2]:0".parse().unwrap()));
+
+        let mut block = [0u8; 216];
+        block[..4].copy_from_slice(b"/src");
+        block[108..112].copy_from_slice(b"/dst");
+        let buf = v2(1, 0x31, &block);
+        let (header, _) = ProxyHeader::parse(&buf).unwrap().unwrap();
+        assert_eq!(
+            header.addresses,
+            Addresses::Unix {
+                source: b"/src",
+                destination: b"/dst",
+            }
+        );
+        assert_eq!(header.source(), None);
+
+        let buf = v2(1, 0x00, b"");
+        let (header, _) = ProxyHeader::parse(&buf).unwrap().unwrap();
+        assert_eq!(header.addresses, Addresses::Unspecified);
+
+        // The block of `LOCAL` is discarded.
+        let buf = v2(0, 0x11, &[0xFF; 20]);
+        let (header, len) = ProxyHeader::parse(&buf).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(header.command, Command::Local);
+        assert_eq!(header.addresses, Addresses::Unspecified);
+        assert_eq!(header.tlvs().count(), 0);
+
+        // So are an unknown family and transport, and a short block.
+        for buf in &[v2(0, 0xFF, &[0; 4]), v2(0, 0x11, &[0; 4])] {
+            let (header, len) = ProxyHeader::parse(buf).unwrap().unwrap();
+            assert_eq!(len, buf.len());
+            assert_eq!(header.command, Command::Local);
+            assert_eq!(header.transport, Transport::Unspecified);
+        }
+    }
+
+    #[test]
+    fn test_v2_errors() {
+        let ipv4 = [0u8; 12];
+        let mut bad_version = v2(1, 0x11, &ipv4);
+        bad_version[12] = 0x11;
+        let mut truncated_tlv = ipv4.to_vec();
+        truncated_tlv.extend_from_slice(&[PP2_TYPE_ALPN, 0, 3, b'h']);
+        let short_ssl = tlv(PP2_TYPE_SSL, &[1, 0, 0, 0]);
+        let mut bad_ssl = [0, 0, 0, 0, 0].to_vec();
+        bad_ssl.extend_from_slice(&[PP2_SUBTYPE_SSL_VERSION, 0]);
+        let bad_ssl = tlv(PP2_TYPE_SSL, &bad_ssl);
+
+        for buf in &[
+            bad_version,
+            v2(2, 0x11, &ipv4),
+            v2(1, 0x41, &ipv4),
+            v2(1, 0x13, &ipv4),
+            v2(1, 0x11, &ipv4[..11]),
+            v2(1, 0x21, &ipv4),
+            v2(1, 0x11, &truncated_tlv),
+            v2(1, 0x00, &short_ssl),
+            v2(1, 0x00, &bad_ssl),
+        ] {
+            assert_eq!(
+                ProxyHeader::parse(buf),
+                Err(InvalidProxyHeader),
+                "{:?}",
+                buf
+            );
+        }
+    }
+}
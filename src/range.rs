@@ -0,0 +1,457 @@
+//! Range requests.
+//!
+//! Parses `Range` and `Content-Range` header fields and resolves requested
+//! byte ranges against the length of a selected representation.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::range::{Range, Resolution};
+//!
+//! let range = Range::parse("bytes=0-99, 50-149, -10").unwrap();
+//! match range.resolve(1000, 4) {
+//!     Resolution::Partial(ranges) => assert_eq!(ranges, [0..150, 990..1000]),
+//!     _ => unreachable!(),
+//! }
+//! ```
+use std::cmp;
+use std::fmt;
+use std::ops;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::errors::*;
+use crate::fields;
+use crate::{Request, Response};
+
+/// A byte range specifier in a `Range` header field.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ByteRangeSpec {
+    /// `first-last`, both positions are inclusive.
+    FromTo(u64, u64),
+    /// `first-`, from the position to the end.
+    From(u64),
+    /// `-length`, the last `length` bytes.
+    Suffix(u64),
+}
+
+impl ByteRangeSpec {
+    fn parse(s: &str) -> Result<ByteRangeSpec> {
+        let dash = s.find('-').ok_or(InvalidRange)?;
+        let (first, last) = (&s[..dash], &s[dash + 1..]);
+        match (first.is_empty(), last.is_empty()) {
+            (true, true) => Err(InvalidRange),
+            (true, false) => fields::parse_u64(last)
+                .map(ByteRangeSpec::Suffix)
+                .ok_or(InvalidRange),
+            (false, true) => fields::parse_u64(first)
+                .map(ByteRangeSpec::From)
+                .ok_or(InvalidRange),
+            (false, false) => {
+                let first = fields::parse_u64(first).ok_or(InvalidRange)?;
+                let last = fields::parse_u64(last).ok_or(InvalidRange)?;
+                if first <= last {
+                    Ok(ByteRangeSpec::FromTo(first, last))
+                } else {
+                    Err(InvalidRange)
+                }
+            }
+        }
+    }
+
+    /// Resolves the specifier against a representation of `len` bytes.
+    ///
+    /// Returns `None` if the range is not satisfiable.
+    pub fn resolve(&self, len: u64) -> Option<ops::Range<u64>> {
+        match *self {
+            ByteRangeSpec::FromTo(first, _) | ByteRangeSpec::From(first) if first >= len => None,
+            ByteRangeSpec::FromTo(first, last) => Some(first..cmp::min(last, len - 1) + 1),
+            ByteRangeSpec::From(first) => Some(first..len),
+            ByteRangeSpec::Suffix(n) if n == 0 || len == 0 => None,
+            ByteRangeSpec::Suffix(n) => Some(len.saturating_sub(n)..len),
+        }
+    }
+}
+
+/// A parsed `Range` header field.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Range<'buffer> {
+    unit: &'buffer str,
+    set: &'buffer str,
+}
+
+impl<'buffer> Range<'buffer> {
+    /// Parse a `Range` header field value.
+    ///
+    /// Every byte range specifier is validated when the unit is `bytes`.
+    /// Other range units are accepted as opaque values.
+    pub fn parse(value: &'buffer str) -> Result<Range<'buffer>> {
+        let value = fields::trim_ows(value);
+        let eq = value.find('=').ok_or(InvalidRange)?;
+        let (unit, set) = (&value[..eq], &value[eq + 1..]);
+        if !fields::is_token(unit) || set.is_empty() {
+            return Err(InvalidRange);
+        }
+
+        let range = Range { unit, set };
+        if range.is_bytes() {
+            let mut count = 0;
+            for spec in fields::ListIter::new(set) {
+                ByteRangeSpec::parse(spec)?;
+                count += 1;
+            }
+            if count == 0 {
+                return Err(InvalidRange);
+            }
+        } else if !set.bytes().all(crate::is_vchar) {
+            return Err(InvalidRange);
+        }
+
+        Ok(range)
+    }
+
+    /// Finds and parses the `Range` header field of the request.
+    pub fn from_request(req: &Request<'_, 'buffer>) -> Result<Option<Range<'buffer>>> {
+        match fields::find(req.headers, "range") {
+            Some(value) => Range::parse(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The range unit.
+    pub fn unit(&self) -> &'buffer str {
+        self.unit
+    }
+
+    /// Returns `true` if the range unit is `bytes`.
+    pub fn is_bytes(&self) -> bool {
+        self.unit.eq_ignore_ascii_case("bytes")
+    }
+
+    /// Iterates the byte range specifiers.
+    ///
+    /// Yields nothing if the range unit is not `bytes`.
+    pub fn specs(&self) -> ByteRangeSpecs<'buffer> {
+        ByteRangeSpecs {
+            iter: fields::ListIter::new(if self.is_bytes() { self.set } else { "" }),
+        }
+    }
+
+    /// Resolves the requested ranges against a representation of `len` bytes.
+    ///
+    /// Satisfiable ranges are sorted and overlapping or adjacent ones are
+    /// coalesced. The range request is ignored when the unit is not `bytes` or
+    /// more than `max_ranges` ranges remain after coalescing.
+    pub fn resolve(&self, len: u64, max_ranges: usize) -> Resolution {
+        if !self.is_bytes() {
+            return Resolution::Full;
+        }
+
+        let mut ranges: Vec<ops::Range<u64>> =
+            self.specs().filter_map(|s| s.resolve(len)).collect();
+        if ranges.is_empty() {
+            return Resolution::NotSatisfiable;
+        }
+
+        ranges.sort_by_key(|r| r.start);
+        let mut merged = 0;
+        for i in 1..ranges.len() {
+            if ranges[i].start <= ranges[merged].end {
+                ranges[merged].end = cmp::max(ranges[merged].end, ranges[i].end);
+            } else {
+                merged += 1;
+                ranges[merged] = ranges[i].clone();
+            }
+        }
+        ranges.truncate(merged + 1);
+
+        if ranges.len() > max_ranges {
+            Resolution::Full
+        } else {
+            Resolution::Partial(ranges)
+        }
+    }
+}
+
+/// An iterator over byte range specifiers in a `Range` header field.
+pub struct ByteRangeSpecs<'buffer> {
+    iter: fields::ListIter<'buffer>,
+}
+
+impl<'buffer> Iterator for ByteRangeSpecs<'buffer> {
+    type Item = ByteRangeSpec;
+
+    fn next(&mut self) -> Option<ByteRangeSpec> {
+        self.iter.next().and_then(|s| ByteRangeSpec::parse(s).ok())
+    }
+}
+
+/// A result of resolving a `Range` header field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    /// The range request is ignored; send the whole representation (`200 OK`).
+    Full,
+    /// Send the ranges (`206 Partial Content`).
+    Partial(Vec<ops::Range<u64>>),
+    /// None of the ranges is satisfiable (`416 Range Not Satisfiable`).
+    NotSatisfiable,
+}
+
+/// A parsed `Content-Range` header field with `bytes` unit.
+///
+/// It is formatted by `Display` as a header field value.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ContentRange {
+    /// `bytes first-last/length`, `length` is `None` for `*`.
+    Bytes {
+        /// The first position, inclusive.
+        first: u64,
+        /// The last position, inclusive.
+        last: u64,
+        /// The complete length of the representation.
+        complete_length: Option<u64>,
+    },
+    /// `bytes */length`, used with `416 Range Not Satisfiable`.
+    Unsatisfied {
+        /// The complete length of the representation.
+        complete_length: u64,
+    },
+}
+
+impl ContentRange {
+    /// Makes a `Content-Range` for a resolved range.
+    ///
+    /// Returns `None` if the range is empty or ends after `complete_length`,
+    /// which no `Content-Range` can describe.
+    pub fn new(range: &ops::Range<u64>, complete_length: u64) -> Option<ContentRange> {
+        if range.start >= range.end || range.end > complete_length {
+            return None;
+        }
+        Some(ContentRange::Bytes {
+            first: range.start,
+            last: range.end - 1,
+            complete_length: Some(complete_length),
+        })
+    }
+
+    /// Parse a `Content-Range` header field value.
+    pub fn parse(value: &str) -> Result<ContentRange> {
+        let value = fields::trim_ows(value);
+        let sp = value.find(' ').ok_or(InvalidContentRange)?;
+        if !value[..sp].eq_ignore_ascii_case("bytes") {
+            return Err(InvalidContentRange);
+        }
+
+        let resp = &value[sp + 1..];
+        let slash = resp.find('/').ok_or(InvalidContentRange)?;
+        let (range, length) = (&resp[..slash], &resp[slash + 1..]);
+
+        if range == "*" {
+            return fields::parse_u64(length)
+                .map(|complete_length| ContentRange::Unsatisfied { complete_length })
+                .ok_or(InvalidContentRange);
+        }
+
+        let complete_length = if length == "*" {
+            None
+        } else {
+            Some(fields::parse_u64(length).ok_or(InvalidContentRange)?)
+        };
+        match ByteRangeSpec::parse(range) {
+            Ok(ByteRangeSpec::FromTo(first, last))
+                if complete_length.map_or(true, |l| last < l) =>
+            {
+                Ok(ContentRange::Bytes {
+                    first,
+                    last,
+                    complete_length,
+                })
+            }
+            _ => Err(InvalidContentRange),
+        }
+    }
+
+    /// Finds and parses the `Content-Range` header field of the response.
+    pub fn from_response(res: &Response) -> Result<Option<ContentRange>> {
+        match fields::find(res.headers, "content-range") {
+            Some(value) => ContentRange::parse(value).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for ContentRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ContentRange::Bytes {
+                first,
+                last,
+                complete_length: Some(len),
+            } => write!(f, "bytes {}-{}/{}", first, last, len),
+            ContentRange::Bytes { first, last, .. } => write!(f, "bytes {}-{}/*", first, last),
+            ContentRange::Unsatisfied { complete_length } => {
+                write!(f, "bytes */{}", complete_length)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::ToString;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::range::*;
+
+    macro_rules! partial {
+        [$($start:expr => $end:expr),*] => {
+            Resolution::Partial(vec![$(($start, $end)),*].into_iter().map(|(s, e)| s..e).collect())
+        };
+    }
+
+    macro_rules! resolve {
+        ($value:expr, $len:expr) => {
+            Range::parse($value).unwrap().resolve($len, 8)
+        };
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let r = Range::parse("bytes=0-499, 500-, -200").unwrap();
+        assert_eq!(r.unit(), "bytes");
+        assert_eq!(
+            r.specs().collect::<Vec<_>>(),
+            [
+                ByteRangeSpec::FromTo(0, 499),
+                ByteRangeSpec::From(500),
+                ByteRangeSpec::Suffix(200)
+            ]
+        );
+
+        let r = Range::parse("items=a..b").unwrap();
+        assert_eq!(r.unit(), "items");
+        assert_eq!(r.specs().next(), None);
+    }
+
+    #[test]
+    fn test_parse_invalid_range() {
+        assert_eq!(Range::parse("bytes"), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes="), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes=,"), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes=-"), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes=5-1"), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes=1-2,x"), Err(InvalidRange));
+        assert_eq!(Range::parse("bytes=+1-2"), Err(InvalidRange));
+        assert_eq!(Range::parse("by tes=1-2"), Err(InvalidRange));
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(resolve!("bytes=0-499", 1000), partial![0 => 500]);
+        assert_eq!(resolve!("bytes=500-", 1000), partial![500 => 1000]);
+        assert_eq!(resolve!("bytes=-200", 1000), partial![800 => 1000]);
+        assert_eq!(resolve!("bytes=-2000", 1000), partial![0 => 1000]);
+        assert_eq!(resolve!("bytes=900-2000", 1000), partial![900 => 1000]);
+        assert_eq!(
+            resolve!("bytes=0-0,-1", 1000),
+            Resolution::Partial(vec![0..1, 999..1000])
+        );
+        assert_eq!(resolve!("items=0-1", 1000), Resolution::Full);
+    }
+
+    #[test]
+    fn test_resolve_coalesces_ranges() {
+        assert_eq!(
+            resolve!("bytes=500-599,0-99,50-149,150-199", 1000),
+            Resolution::Partial(vec![0..200, 500..600])
+        );
+        assert_eq!(
+            resolve!("bytes=0-,0-,0-,0-,0-,0-,0-,0-,0-,0-", 1000),
+            partial![0 => 1000]
+        );
+    }
+
+    #[test]
+    fn test_resolve_too_many_ranges() {
+        let r = Range::parse("bytes=0-0,2-2,4-4").unwrap();
+        assert_eq!(r.resolve(10, 2), Resolution::Full);
+        assert_eq!(
+            r.resolve(10, 3),
+            Resolution::Partial(vec![0..1, 2..3, 4..5])
+        );
+    }
+
+    #[test]
+    fn test_resolve_not_satisfiable() {
+        assert_eq!(resolve!("bytes=1000-", 1000), Resolution::NotSatisfiable);
+        assert_eq!(
+            resolve!("bytes=1000-2000", 1000),
+            Resolution::NotSatisfiable
+        );
+        assert_eq!(resolve!("bytes=-0", 1000), Resolution::NotSatisfiable);
+        assert_eq!(resolve!("bytes=-10", 0), Resolution::NotSatisfiable);
+        assert_eq!(resolve!("bytes=2000-,10-20", 1000), partial![10 => 21]);
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-499/1234"),
+            Ok(ContentRange::Bytes {
+                first: 0,
+                last: 499,
+                complete_length: Some(1234)
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 0-499/*"),
+            Ok(ContentRange::Bytes {
+                first: 0,
+                last: 499,
+                complete_length: None
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes */1234"),
+            Ok(ContentRange::Unsatisfied {
+                complete_length: 1234
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 0-1234/1234"),
+            Err(InvalidContentRange)
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 5-4/10"),
+            Err(InvalidContentRange)
+        );
+        assert_eq!(ContentRange::parse("bytes 0-/10"), Err(InvalidContentRange));
+        assert_eq!(
+            ContentRange::parse("items 0-1/10"),
+            Err(InvalidContentRange)
+        );
+        assert_eq!(ContentRange::parse("bytes */*"), Err(InvalidContentRange));
+    }
+
+    #[test]
+    fn test_format_content_range() {
+        assert_eq!(
+            ContentRange::new(&(0..500), 1234).unwrap().to_string(),
+            "bytes 0-499/1234"
+        );
+        assert_eq!(ContentRange::new(&(5..5), 10), None);
+        assert_eq!(ContentRange::new(&(0..11), 10), None);
+        assert_eq!(
+            ContentRange::Unsatisfied {
+                complete_length: 10
+            }
+            .to_string(),
+            "bytes */10"
+        );
+    }
+}
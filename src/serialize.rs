@@ -0,0 +1,723 @@
+//! Writes requests and responses in the wire format.
+//!
+//! Every part is validated against the same character tables as the parser
+//! before anything is written, so a message that is written can be parsed
+//! back as it is and no header field can be injected through a value.
+use std::fmt;
+#[cfg(feature = "std")]
+use std::io;
+use std::result;
+use std::str;
+
+use crate::errors::*;
+use crate::fields::is_token;
+use crate::{HeaderField, Request, Response};
+
+const DIGITS: &str = "0123456789";
+
+#[inline]
+fn digit(v: usize) -> &'static str {
+    &DIGITS[v..v + 1]
+}
+
+#[inline]
+fn is_field_value(s: &str) -> bool {
+    match s.as_bytes().first() {
+        Some(&b' ') | Some(&b'\t') => false,
+        _ => s.bytes().all(crate::is_field_value_char),
+    }
+}
+
+pub fn validate_headers(headers: &[HeaderField]) -> Result<()> {
+    for header in headers {
+        if !is_token(header.name) {
+            return Err(InvalidFieldName);
+        }
+        if !is_field_value(header.value) {
+            return Err(InvalidFieldValue);
+        }
+    }
+    Ok(())
+}
+
+/// Slices of a message in the wire format.
+///
+/// It yields the start line, header fields and separators in order. Parts
+/// of the message borrow the strings of the message, so unchanged header
+/// fields of a parsed message point into the parsed buffer and nothing is
+/// copied. It is suitable for vectored writes.
+///
+/// By default it yields four slices per header field. Given the parsed
+/// buffer with [`with_buffer`](#method.with_buffer), runs of slices which
+/// match the buffer are merged into one slice of it, so an unchanged head
+/// stays within a few slices regardless of the number of header fields.
+///
+/// ## Example
+///
+/// ```
+/// use std::io::{IoSlice, Write};
+///
+/// let buf = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n";
+/// let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+/// let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+///
+/// // Drop `Connection` and add `Via`.
+/// let mut forwarded: Vec<_> = req
+///     .headers
+///     .iter()
+///     .filter(|h| !h.name.eq_ignore_ascii_case("connection"))
+///     .cloned()
+///     .collect();
+/// forwarded.push(thhp::HeaderField { name: "Via", value: "1.1 proxy" });
+/// let req = thhp::Request { headers: &forwarded, ..req };
+///
+/// let slices = req.encode_slices().unwrap().with_buffer(buf);
+/// let len = slices.encoded_len();
+/// let io_slices: Vec<_> = slices.map(IoSlice::new).collect();
+/// assert_eq!(io_slices.len(), 6);
+///
+/// let mut out = Vec::new();
+/// assert_eq!(out.write_vectored(&io_slices).unwrap(), len);
+/// assert_eq!(out, &b"GET / HTTP/1.1\r\nHost: example.com\r\nVia: 1.1 proxy\r\n\r\n"[..]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct EncodedSlices<'a> {
+    start_line: [&'a str; 9],
+    start_line_len: usize,
+    headers: &'a [HeaderField<'a>],
+    index: usize,
+    buffer: &'a [u8],
+}
+
+impl<'a> EncodedSlices<'a> {
+    fn new(start_line: &[&'a str], headers: &'a [HeaderField<'a>]) -> EncodedSlices<'a> {
+        let mut pieces = [""; 9];
+        pieces[..start_line.len()].copy_from_slice(start_line);
+        EncodedSlices {
+            start_line: pieces,
+            start_line_len: start_line.len(),
+            headers,
+            index: 0,
+            buffer: &[],
+        }
+    }
+
+    /// Merges runs of slices which match `buf` into slices of `buf`.
+    ///
+    /// A run starts at a slice which points into `buf`, and continues while
+    /// the following slices equal the bytes after it, so `buf` is usually
+    /// the buffer the message was parsed from. The bytes yielded are the
+    /// same either way.
+    pub fn with_buffer(self, buf: &'a [u8]) -> EncodedSlices<'a> {
+        EncodedSlices {
+            buffer: buf,
+            ..self
+        }
+    }
+
+    /// Returns the total number of bytes of the remaining slices.
+    pub fn encoded_len(&self) -> usize {
+        self.clone().map(|s| s.len()).sum()
+    }
+
+    fn piece(&self, index: usize) -> Option<&'a str> {
+        if index < self.start_line_len {
+            return Some(self.start_line[index]);
+        }
+        let index = index - self.start_line_len;
+        match self.headers.get(index / 4) {
+            Some(header) => Some(match index % 4 {
+                0 => header.name,
+                1 => ": ",
+                2 => header.value,
+                _ => "\r\n",
+            }),
+            None if index == self.headers.len() * 4 => Some("\r\n"),
+            None => None,
+        }
+    }
+
+    fn next_str(&mut self) -> Option<&'a str> {
+        let piece = self.piece(self.index)?;
+        self.index += 1;
+        Some(piece)
+    }
+
+    fn remaining(&self) -> usize {
+        self.start_line_len + self.headers.len() * 4 + 1 - self.index
+    }
+
+    fn offset_in_buffer(&self, s: &[u8]) -> Option<usize> {
+        let start = (s.as_ptr() as usize).checked_sub(self.buffer.as_ptr() as usize)?;
+        if start < self.buffer.len() && s.len() <= self.buffer.len() - start {
+            Some(start)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Iterator for EncodedSlices<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let first = self.next_str()?.as_bytes();
+        let start = match self.offset_in_buffer(first) {
+            Some(start) => start,
+            None => return Some(first),
+        };
+        let mut end = start + first.len();
+        while let Some(piece) = self.piece(self.index) {
+            let piece = piece.as_bytes();
+            if self.buffer.get(end..end + piece.len()) != Some(piece) {
+                break;
+            }
+            end += piece.len();
+            self.index += 1;
+        }
+        Some(&self.buffer[start..end])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.remaining();
+        if self.buffer.is_empty() {
+            (len, Some(len))
+        } else {
+            (len.min(1), Some(len))
+        }
+    }
+}
+
+fn write_all<E, F>(mut slices: EncodedSlices, mut put: F) -> result::Result<(), E>
+where
+    F: FnMut(&str) -> result::Result<(), E>,
+{
+    while let Some(s) = slices.next_str() {
+        put(s)?;
+    }
+    Ok(())
+}
+
+fn encode(slices: EncodedSlices, buf: &mut [u8]) -> Result<usize> {
+    let len = slices.encoded_len();
+    if buf.len() < len {
+        return Err(BufferTooSmall);
+    }
+    let mut pos = 0;
+    for s in slices {
+        buf[pos..pos + s.len()].copy_from_slice(s);
+        pos += s.len();
+    }
+    Ok(len)
+}
+
+fn encode_fmt<W: fmt::Write>(slices: EncodedSlices, w: &mut W) -> Result<()> {
+    write_all(slices, |s| w.write_str(s)).or(Err(WriteFailed))
+}
+
+#[cfg(feature = "std")]
+fn encode_io<W: io::Write>(slices: EncodedSlices, w: &mut W) -> io::Result<()> {
+    write_all(slices, |s| w.write_all(s.as_bytes()))
+}
+
+impl<'headers, 'buffer: 'headers> Request<'headers, 'buffer> {
+    /// Validates the request can be written.
+    ///
+    /// The method and header field names must be tokens, the target must
+    /// consist of visible characters, the minor version must be a digit and
+    /// header field values must not contain CR, LF or leading whitespace.
+    pub fn validate(&self) -> Result<()> {
+        if !is_token(self.method) {
+            return Err(InvalidMethod);
+        }
+        if self.target.is_empty() || !self.target.bytes().all(crate::is_vchar) {
+            return Err(InvalidPath);
+        }
+        if self.minor_version > 9 {
+            return Err(InvalidVersion);
+        }
+        validate_headers(self.headers)
+    }
+
+    /// Returns the number of bytes written by `encode`.
+    pub fn encoded_len(&self) -> usize {
+        self.slices().encoded_len()
+    }
+
+    /// Writes the request into the buffer, returns the number of written
+    /// bytes.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let headers = [thhp::HeaderField { name: "Host", value: "example.com" }];
+    /// let req = thhp::Request {
+    ///     method: "GET",
+    ///     target: "/",
+    ///     minor_version: 1,
+    ///     headers: &headers,
+    /// };
+    /// let mut buf = [0u8; 64];
+    /// let len = req.encode(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..]);
+    /// ```
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize> {
+        self.validate()?;
+        encode(self.slices(), buf)
+    }
+
+    /// Writes the request into a `fmt::Write`.
+    pub fn encode_fmt<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
+        self.validate()?;
+        encode_fmt(self.slices(), w)
+    }
+
+    /// Writes the request into an `io::Write`.
+    ///
+    /// A validation error is returned as an `InvalidInput` I/O error.
+    #[cfg(feature = "std")]
+    pub fn encode_io<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.validate()?;
+        encode_io(self.slices(), w)
+    }
+
+    /// Returns the slices of the request in the wire format.
+    pub fn encode_slices(&self) -> Result<EncodedSlices<'_>> {
+        self.validate()?;
+        Ok(self.slices())
+    }
+
+    #[inline]
+    fn slices(&self) -> EncodedSlices<'_> {
+        EncodedSlices::new(&self.start_line(), self.headers)
+    }
+
+    #[inline]
+    fn start_line(&self) -> [&'buffer str; 6] {
+        [
+            self.method,
+            " ",
+            self.target,
+            " HTTP/1.",
+            digit(self.minor_version as usize % 10),
+            "\r\n",
+        ]
+    }
+}
+
+impl<'headers, 'buffer: 'headers> Response<'headers, 'buffer> {
+    /// Validates the response can be written.
+    ///
+    /// The status code must have three digits, the reason phrase must not
+    /// contain control characters except HTAB, the minor version must be a
+    /// digit and header fields are validated as for requests.
+    pub fn validate(&self) -> Result<()> {
+        if self.minor_version > 9 {
+            return Err(InvalidVersion);
+        }
+        if self.status < 100 || self.status > 999 {
+            return Err(InvalidStatusCode);
+        }
+        if !self.reason.bytes().all(crate::is_reason_char) {
+            return Err(InvalidReasonPhrase);
+        }
+        validate_headers(self.headers)
+    }
+
+    /// Returns the number of bytes written by `encode`.
+    pub fn encoded_len(&self) -> usize {
+        self.slices().encoded_len()
+    }
+
+    /// Writes the response into the buffer, returns the number of written
+    /// bytes.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let headers = [thhp::HeaderField { name: "Content-Length", value: "0" }];
+    /// let res = thhp::Response {
+    ///     minor_version: 1,
+    ///     status: 204,
+    ///     reason: "No Content",
+    ///     headers: &headers,
+    /// };
+    /// let mut buf = [0u8; 64];
+    /// let len = res.encode(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], &b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n"[..]);
+    /// ```
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize> {
+        self.validate()?;
+        encode(self.slices(), buf)
+    }
+
+    /// Writes the response into a `fmt::Write`.
+    pub fn encode_fmt<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
+        self.validate()?;
+        encode_fmt(self.slices(), w)
+    }
+
+    /// Writes the response into an `io::Write`.
+    ///
+    /// A validation error is returned as an `InvalidInput` I/O error.
+    #[cfg(feature = "std")]
+    pub fn encode_io<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.validate()?;
+        encode_io(self.slices(), w)
+    }
+
+    /// Returns the slices of the response in the wire format.
+    pub fn encode_slices(&self) -> Result<EncodedSlices<'_>> {
+        self.validate()?;
+        Ok(self.slices())
+    }
+
+    #[inline]
+    fn slices(&self) -> EncodedSlices<'_> {
+        EncodedSlices::new(&self.start_line(), self.headers)
+    }
+
+    #[inline]
+    fn start_line(&self) -> [&'buffer str; 9] {
+        let status = self.status as usize;
+        [
+            "HTTP/1.",
+            digit(self.minor_version as usize % 10),
+            " ",
+            digit(status / 100 % 10),
+            digit(status / 10 % 10),
+            digit(status % 10),
+            " ",
+            self.reason,
+            "\r\n",
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::string::String;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use std::fmt;
+
+    use crate::*;
+
+    macro_rules! header {
+        ($name:expr, $value:expr) => {
+            HeaderField {
+                name: $name,
+                value: $value,
+            }
+        };
+    }
+
+    fn request<'a>(
+        method: &'a str,
+        target: &'a str,
+        headers: &'a [HeaderField<'a>],
+    ) -> Request<'a, 'a> {
+        Request {
+            method,
+            target,
+            minor_version: 1,
+            headers,
+        }
+    }
+
+    fn response<'a>(
+        status: u16,
+        reason: &'a str,
+        headers: &'a [HeaderField<'a>],
+    ) -> Response<'a, 'a> {
+        Response {
+            minor_version: 1,
+            status,
+            reason,
+            headers,
+        }
+    }
+
+    #[test]
+    fn test_request_round_trip() {
+        let headers = [
+            header!("Host", "example.com"),
+            header!("Accept", "text/html, */*;q=0.8"),
+            header!("X-Empty", ""),
+            header!("X-Tab", "a\tb "),
+        ];
+        let req = request("POST", "/a?b=c", &headers);
+
+        let mut buf = [0u8; 256];
+        let len = req.encode(&mut buf).unwrap();
+        assert_eq!(len, req.encoded_len());
+
+        let mut parsed_headers = Vec::<HeaderField>::with_capacity(8);
+        let (parsed, n) = Request::parse(&buf[..len], &mut parsed_headers)
+            .unwrap()
+            .unwrap();
+        assert_eq!(n, len);
+        assert_eq!(parsed, req);
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let headers = [header!("Content-Length", "5")];
+        for res in &[
+            response(200, "OK", &headers),
+            response(404, "", &[]),
+            response(999, "Custom\tReason", &headers),
+            Response {
+                minor_version: 0,
+                ..response(100, "Continue", &[])
+            },
+        ] {
+            let mut buf = [0u8; 256];
+            let len = res.encode(&mut buf).unwrap();
+            assert_eq!(len, res.encoded_len());
+
+            let mut parsed_headers = Vec::<HeaderField>::with_capacity(8);
+            let (parsed, n) = Response::parse(&buf[..len], &mut parsed_headers)
+                .unwrap()
+                .unwrap();
+            assert_eq!(n, len);
+            assert_eq!(&parsed, res);
+        }
+    }
+
+    #[test]
+    fn test_encode_fmt() {
+        let headers = [header!("Host", "a")];
+        let mut s = String::new();
+        request("GET", "*", &headers).encode_fmt(&mut s).unwrap();
+        assert_eq!(s, "GET * HTTP/1.1\r\nHost: a\r\n\r\n");
+
+        let mut s = String::new();
+        response(301, "Moved Permanently", &[])
+            .encode_fmt(&mut s)
+            .unwrap();
+        assert_eq!(s, "HTTP/1.1 301 Moved Permanently\r\n\r\n");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_encode_io() {
+        let headers = [header!("Host", "a")];
+        let mut out = Vec::new();
+        request("GET", "/", &headers).encode_io(&mut out).unwrap();
+        assert_eq!(out, b"GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+
+        let headers = [header!("Host", "a\r\nX: b")];
+        let err = request("GET", "/", &headers)
+            .encode_io(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        let req = request("GET", "/", &[]);
+        let mut buf = [0u8; 18];
+        assert_eq!(req.encode(&mut buf[..17]), Err(BufferTooSmall));
+        assert_eq!(req.encode(&mut buf), Ok(18));
+    }
+
+    #[test]
+    fn test_write_failed() {
+        struct Full;
+
+        impl fmt::Write for Full {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+
+        assert_eq!(
+            request("GET", "/", &[]).encode_fmt(&mut Full),
+            Err(WriteFailed)
+        );
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        let mut buf = [0u8; 256];
+        assert_eq!(request("", "/", &[]).encode(&mut buf), Err(InvalidMethod));
+        assert_eq!(
+            request("G T", "/", &[]).encode(&mut buf),
+            Err(InvalidMethod)
+        );
+        assert_eq!(request("GET", "", &[]).encode(&mut buf), Err(InvalidPath));
+        assert_eq!(
+            request("GET", "/ HTTP/1.1\r\nX:", &[]).encode(&mut buf),
+            Err(InvalidPath)
+        );
+        assert_eq!(
+            Request {
+                minor_version: 10,
+                ..request("GET", "/", &[])
+            }
+            .encode(&mut buf),
+            Err(InvalidVersion)
+        );
+
+        let headers = [header!("Bad Name", "v")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldName)
+        );
+        let headers = [header!("Bad:", "v")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldName)
+        );
+        let headers = [header!("", "v")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldName)
+        );
+        let headers = [header!("X", "a\r\nInjected: 1")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldValue)
+        );
+        let headers = [header!("X", "a\nb")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldValue)
+        );
+        let headers = [header!("X", " a")];
+        assert_eq!(
+            request("GET", "/", &headers).encode(&mut buf),
+            Err(InvalidFieldValue)
+        );
+    }
+
+    #[test]
+    fn test_invalid_response() {
+        let mut buf = [0u8; 256];
+        assert_eq!(
+            response(99, "", &[]).encode(&mut buf),
+            Err(InvalidStatusCode)
+        );
+        assert_eq!(
+            response(1000, "", &[]).encode(&mut buf),
+            Err(InvalidStatusCode)
+        );
+        assert_eq!(
+            response(200, "OK\r\nX: 1", &[]).encode(&mut buf),
+            Err(InvalidReasonPhrase)
+        );
+        let headers = [header!("X", "\u{e9}")];
+        assert_eq!(
+            response(200, "OK", &headers).encode(&mut buf),
+            Err(InvalidFieldValue)
+        );
+
+        let mut s = String::new();
+        assert_eq!(
+            response(200, "\r", &[]).encode_fmt(&mut s),
+            Err(InvalidReasonPhrase)
+        );
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_encode_slices_borrows_parsed_buffer() {
+        let buf = b"GET /a HTTP/1.1\r\nHost:  example.com\r\nConnection: close\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(8);
+        let (req, _) = Request::parse(buf, &mut headers).unwrap().unwrap();
+
+        let forwarded = [req.headers[0], header!("Via", "1.1 proxy")];
+        let req = Request {
+            headers: &forwarded,
+            ..req
+        };
+        let slices = req.encode_slices().unwrap();
+        assert_eq!(slices.size_hint(), (6 + 4 * 2 + 1, Some(6 + 4 * 2 + 1)));
+        assert_eq!(slices.encoded_len(), req.encoded_len());
+
+        let slices: Vec<&[u8]> = slices.collect();
+        let in_buffer = |s: &[u8]| buf.as_ptr_range().contains(&s.as_ptr());
+        assert!(in_buffer(slices[0]));
+        assert!(in_buffer(slices[2]));
+        assert!(in_buffer(slices[6]));
+        assert!(in_buffer(slices[8]));
+        assert!(!in_buffer(slices[12]));
+        assert_eq!(
+            slices.concat(),
+            &b"GET /a HTTP/1.1\r\nHost: example.com\r\nVia: 1.1 proxy\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_encode_slices() {
+        let headers = [header!("Content-Length", "0")];
+        let res = response(200, "OK", &headers);
+        let mut slices = res.encode_slices().unwrap();
+        assert_eq!(slices.size_hint(), (9 + 4 + 1, Some(9 + 4 + 1)));
+        assert_eq!(slices.next(), Some(&b"HTTP/1."[..]));
+        assert_eq!(slices.size_hint(), (9 + 4, Some(9 + 4)));
+        assert_eq!(slices.encoded_len(), res.encoded_len() - 7);
+        assert_eq!(
+            slices.collect::<Vec<_>>().concat(),
+            &b"1 200 OK\r\nContent-Length: 0\r\n\r\n"[..]
+        );
+
+        let headers = [header!("X", "a\r\nY: b")];
+        assert_eq!(
+            response(200, "OK", &headers).encode_slices().err(),
+            Some(InvalidFieldValue)
+        );
+        assert_eq!(
+            request("GET", "/\r\n", &[]).encode_slices().err(),
+            Some(InvalidPath)
+        );
+    }
+
+    #[test]
+    fn test_encode_slices_with_buffer() {
+        let buf =
+            b"GET /a HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\nConnection: close\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(8);
+        let (req, _) = Request::parse(buf, &mut headers).unwrap().unwrap();
+
+        let slices = req.encode_slices().unwrap().with_buffer(buf);
+        assert_eq!(slices.encoded_len(), buf.len());
+        let slices: Vec<&[u8]> = slices.collect();
+        assert_eq!(slices, [&buf[..]]);
+
+        let forwarded = [
+            req.headers[0],
+            req.headers[1],
+            header!("Via", "1.1 proxy"),
+            req.headers[2],
+        ];
+        let req = Request {
+            headers: &forwarded,
+            ..req
+        };
+        let slices: Vec<&[u8]> = req.encode_slices().unwrap().with_buffer(buf).collect();
+        assert_eq!(slices.len(), 1 + 4 + 1);
+        assert_eq!(slices[0], &buf[..49]);
+        assert_eq!(slices[1], b"Via");
+        assert_eq!(slices[5], &buf[49..]);
+        assert_eq!(
+            slices.concat(),
+            &b"GET /a HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\nVia: 1.1 proxy\r\nConnection: close\r\n\r\n"[..]
+        );
+
+        // Whitespace which differs from the canonical form splits the run.
+        let buf = b"GET / HTTP/1.1\r\nHost:  example.com\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(8);
+        let (req, _) = Request::parse(buf, &mut headers).unwrap().unwrap();
+        let slices: Vec<&[u8]> = req.encode_slices().unwrap().with_buffer(buf).collect();
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices[0], b"GET / HTTP/1.1\r\nHost: ");
+        assert_eq!(
+            slices.concat(),
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..]
+        );
+    }
+}
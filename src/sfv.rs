@@ -0,0 +1,790 @@
+//! Structured Field Values (RFC 8941).
+//!
+//! Parses Lists, Dictionaries and Items from header field values and
+//! serializes them back. Parsing follows the strict failure rules of the
+//! RFC; any error fails the whole field value. As the RFC recommends, byte
+//! sequences without padding or with non-zero pad bits are accepted.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::sfv::{self, BareItem, ListEntry};
+//!
+//! let dict = sfv::parse_dictionary("u=1, i").unwrap();
+//! assert_eq!(dict[0].0, "u");
+//! match dict[0].1 {
+//!     ListEntry::Item(ref item) => assert_eq!(item.bare_item, BareItem::Integer(1)),
+//!     _ => unreachable!(),
+//! }
+//! assert_eq!(sfv::serialize_dictionary(&dict).unwrap(), "u=1, i");
+//! ```
+use std::fmt;
+use std::str;
+
+#[cfg(not(feature = "std"))]
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::borrow::Cow;
+
+use crate::base64;
+use crate::errors::*;
+
+const MAX_INTEGER: i64 = 999_999_999_999_999;
+
+/// A decimal number with three fractional digits.
+///
+/// It is formatted by `Display` in the serialized form.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Decimal {
+    thousandths: i64,
+}
+
+impl Decimal {
+    /// Makes a decimal from a number of thousandths, e.g. `1500` for `1.5`.
+    pub fn from_thousandths(thousandths: i64) -> Decimal {
+        Decimal { thousandths }
+    }
+
+    /// Returns the number of thousandths.
+    pub fn thousandths(&self) -> i64 {
+        self.thousandths
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let abs = self.thousandths.unsigned_abs();
+        if self.thousandths < 0 {
+            f.write_str("-")?;
+        }
+        let frac = abs % 1000;
+        match (frac % 10 == 0, frac % 100 == 0) {
+            (_, true) => write!(f, "{}.{}", abs / 1000, frac / 100),
+            (true, false) => write!(f, "{}.{:02}", abs / 1000, frac / 10),
+            _ => write!(f, "{}.{:03}", abs / 1000, frac),
+        }
+    }
+}
+
+/// A bare item.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum BareItem<'a> {
+    /// An integer in the range of ±999,999,999,999,999.
+    Integer(i64),
+    /// A decimal.
+    Decimal(Decimal),
+    /// A string, escapes are removed.
+    String(Cow<'a, str>),
+    /// A token.
+    Token(&'a str),
+    /// A byte sequence, decoded from base64.
+    ByteSequence(Vec<u8>),
+    /// A boolean.
+    Boolean(bool),
+}
+
+/// Parameters, an ordered map of keys to bare items.
+pub type Parameters<'a> = Vec<(&'a str, BareItem<'a>)>;
+
+/// An item with parameters.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Item<'a> {
+    /// The bare item.
+    pub bare_item: BareItem<'a>,
+    /// The parameters.
+    pub params: Parameters<'a>,
+}
+
+impl<'a> Item<'a> {
+    /// Makes an item without parameters.
+    pub fn new(bare_item: BareItem<'a>) -> Item<'a> {
+        Item {
+            bare_item,
+            params: Vec::new(),
+        }
+    }
+}
+
+/// An inner list with parameters.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InnerList<'a> {
+    /// The items.
+    pub items: Vec<Item<'a>>,
+    /// The parameters.
+    pub params: Parameters<'a>,
+}
+
+/// A member of a List or a Dictionary.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ListEntry<'a> {
+    /// An item.
+    Item(Item<'a>),
+    /// An inner list.
+    InnerList(InnerList<'a>),
+}
+
+/// A List.
+pub type List<'a> = Vec<ListEntry<'a>>;
+
+/// A Dictionary, an ordered map of keys to members.
+pub type Dictionary<'a> = Vec<(&'a str, ListEntry<'a>)>;
+
+/// Parse a header field value as a List.
+pub fn parse_list(value: &str) -> Result<List<'_>> {
+    Parser::new(value).parse(Parser::parse_list)
+}
+
+/// Parse a header field value as a Dictionary.
+pub fn parse_dictionary(value: &str) -> Result<Dictionary<'_>> {
+    Parser::new(value).parse(Parser::parse_dictionary)
+}
+
+/// Parse a header field value as an Item.
+pub fn parse_item(value: &str) -> Result<Item<'_>> {
+    Parser::new(value).parse(Parser::parse_item)
+}
+
+fn insert<'a, V>(map: &mut Vec<(&'a str, V)>, key: &'a str, value: V) {
+    match map.iter_mut().find(|e| e.0 == key) {
+        Some(e) => e.1 = value,
+        None => map.push((key, value)),
+    }
+}
+
+#[inline]
+fn is_lcalpha(c: u8) -> bool {
+    c.is_ascii_lowercase()
+}
+
+#[inline]
+fn is_key_char(c: u8) -> bool {
+    is_lcalpha(c) || c.is_ascii_digit() || c == b'_' || c == b'-' || c == b'.' || c == b'*'
+}
+
+#[inline]
+fn is_token_char(c: u8) -> bool {
+    crate::is_tchar(c) || c == b':' || c == b'/'
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser { input, pos: 0 }
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).cloned()
+    }
+
+    #[inline]
+    fn eof(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    #[inline]
+    fn skip_sp(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.pos += 1;
+        }
+    }
+
+    #[inline]
+    fn skip_ows(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    #[inline]
+    fn take_while<F: Fn(u8) -> bool>(&mut self, f: F) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse<T, F>(mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T>,
+    {
+        self.skip_sp();
+        let v = f(&mut self)?;
+        self.skip_sp();
+        if self.eof() {
+            Ok(v)
+        } else {
+            Err(InvalidStructuredField)
+        }
+    }
+
+    // Parses members separated by commas.
+    fn parse_members<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut Parser<'a>) -> Result<()>,
+    {
+        while !self.eof() {
+            f(self)?;
+            self.skip_ows();
+            if self.eof() {
+                break;
+            }
+            if self.peek() != Some(b',') {
+                return Err(InvalidStructuredField);
+            }
+            self.pos += 1;
+            self.skip_ows();
+            if self.eof() {
+                return Err(InvalidStructuredField);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_list(&mut self) -> Result<List<'a>> {
+        let mut list = Vec::new();
+        self.parse_members(|p| {
+            list.push(p.parse_item_or_inner_list()?);
+            Ok(())
+        })?;
+        Ok(list)
+    }
+
+    fn parse_dictionary(&mut self) -> Result<Dictionary<'a>> {
+        let mut dict = Vec::new();
+        self.parse_members(|p| {
+            let key = p.parse_key()?;
+            let member = if p.peek() == Some(b'=') {
+                p.pos += 1;
+                p.parse_item_or_inner_list()?
+            } else {
+                ListEntry::Item(Item {
+                    bare_item: BareItem::Boolean(true),
+                    params: p.parse_parameters()?,
+                })
+            };
+            insert(&mut dict, key, member);
+            Ok(())
+        })?;
+        Ok(dict)
+    }
+
+    fn parse_item_or_inner_list(&mut self) -> Result<ListEntry<'a>> {
+        if self.peek() == Some(b'(') {
+            self.parse_inner_list().map(ListEntry::InnerList)
+        } else {
+            self.parse_item().map(ListEntry::Item)
+        }
+    }
+
+    fn parse_inner_list(&mut self) -> Result<InnerList<'a>> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_sp();
+            match self.peek() {
+                Some(b')') => {
+                    self.pos += 1;
+                    return Ok(InnerList {
+                        items,
+                        params: self.parse_parameters()?,
+                    });
+                }
+                Some(_) => {
+                    items.push(self.parse_item()?);
+                    match self.peek() {
+                        Some(b' ') | Some(b')') => (),
+                        _ => return Err(InvalidStructuredField),
+                    }
+                }
+                None => return Err(InvalidStructuredField),
+            }
+        }
+    }
+
+    fn parse_item(&mut self) -> Result<Item<'a>> {
+        Ok(Item {
+            bare_item: self.parse_bare_item()?,
+            params: self.parse_parameters()?,
+        })
+    }
+
+    fn parse_parameters(&mut self) -> Result<Parameters<'a>> {
+        let mut params = Vec::new();
+        while self.peek() == Some(b';') {
+            self.pos += 1;
+            self.skip_sp();
+            let key = self.parse_key()?;
+            let value = if self.peek() == Some(b'=') {
+                self.pos += 1;
+                self.parse_bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+            insert(&mut params, key, value);
+        }
+        Ok(params)
+    }
+
+    fn parse_key(&mut self) -> Result<&'a str> {
+        match self.peek() {
+            Some(c) if is_lcalpha(c) || c == b'*' => Ok(self.take_while(is_key_char)),
+            _ => Err(InvalidStructuredField),
+        }
+    }
+
+    fn parse_bare_item(&mut self) -> Result<BareItem<'a>> {
+        match self.peek() {
+            Some(c) if c == b'-' || c.is_ascii_digit() => self.parse_number(),
+            Some(b'"') => self.parse_string(),
+            Some(c) if c.is_ascii_alphabetic() || c == b'*' => {
+                Ok(BareItem::Token(self.take_while(is_token_char)))
+            }
+            Some(b':') => self.parse_byte_sequence(),
+            Some(b'?') => self.parse_boolean(),
+            _ => Err(InvalidStructuredField),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<BareItem<'a>> {
+        let negative = self.peek() == Some(b'-');
+        if negative {
+            self.pos += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return Err(InvalidStructuredField);
+        }
+
+        let start = self.pos;
+        let mut dot = None;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.pos += 1;
+            } else if dot.is_none() && c == b'.' {
+                if self.pos - start > 12 {
+                    return Err(InvalidStructuredField);
+                }
+                dot = Some(self.pos);
+                self.pos += 1;
+            } else {
+                break;
+            }
+            let len = self.pos - start;
+            if (dot.is_none() && len > 15) || len > 16 {
+                return Err(InvalidStructuredField);
+            }
+        }
+
+        let sign = if negative { -1 } else { 1 };
+        let digits = |s: &str| {
+            s.bytes()
+                .fold(0i64, |acc, c| acc * 10 + i64::from(c - b'0'))
+        };
+        match dot {
+            None => Ok(BareItem::Integer(
+                sign * digits(&self.input[start..self.pos]),
+            )),
+            Some(dot) => {
+                let frac = &self.input[dot + 1..self.pos];
+                if frac.is_empty() || frac.len() > 3 {
+                    return Err(InvalidStructuredField);
+                }
+                let scale = [100, 10, 1][frac.len() - 1];
+                let int = digits(&self.input[start..dot]);
+                Ok(BareItem::Decimal(Decimal::from_thousandths(
+                    sign * (int * 1000 + digits(frac) * scale),
+                )))
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<BareItem<'a>> {
+        self.pos += 1;
+        let start = self.pos;
+        let mut owned: Option<String> = None;
+        loop {
+            let c = self.peek().ok_or(InvalidStructuredField)?;
+            self.pos += 1;
+            match c {
+                b'"' => {
+                    return Ok(BareItem::String(match owned {
+                        Some(s) => Cow::Owned(s),
+                        None => Cow::Borrowed(&self.input[start..self.pos - 1]),
+                    }));
+                }
+                b'\\' => {
+                    let next = self.peek().ok_or(InvalidStructuredField)?;
+                    if next != b'"' && next != b'\\' {
+                        return Err(InvalidStructuredField);
+                    }
+                    self.pos += 1;
+                    owned
+                        .get_or_insert_with(|| String::from(&self.input[start..self.pos - 2]))
+                        .push(char::from(next));
+                }
+                0x20..=0x7E => {
+                    if let Some(ref mut s) = owned {
+                        s.push(char::from(c));
+                    }
+                }
+                _ => return Err(InvalidStructuredField),
+            }
+        }
+    }
+
+    fn parse_byte_sequence(&mut self) -> Result<BareItem<'a>> {
+        self.pos += 1;
+        let encoded =
+            self.take_while(|c| c.is_ascii_alphanumeric() || c == b'+' || c == b'/' || c == b'=');
+        if self.peek() != Some(b':') {
+            return Err(InvalidStructuredField);
+        }
+        self.pos += 1;
+
+        let mut buf = vec![0; base64::decoded_len(encoded.len())];
+        let len =
+            base64::decode_lenient(encoded.as_bytes(), &mut buf).ok_or(InvalidStructuredField)?;
+        buf.truncate(len);
+        Ok(BareItem::ByteSequence(buf))
+    }
+
+    fn parse_boolean(&mut self) -> Result<BareItem<'a>> {
+        self.pos += 1;
+        let v = match self.peek() {
+            Some(b'1') => true,
+            Some(b'0') => false,
+            _ => return Err(InvalidStructuredField),
+        };
+        self.pos += 1;
+        Ok(BareItem::Boolean(v))
+    }
+}
+
+/// Serialize a List.
+///
+/// An empty List is serialized as an empty string; the header field should
+/// be omitted then.
+pub fn serialize_list(list: &[ListEntry]) -> Result<String> {
+    let mut out = String::new();
+    for (i, member) in list.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_list_entry(&mut out, member)?;
+    }
+    Ok(out)
+}
+
+/// Serialize a Dictionary.
+///
+/// An empty Dictionary is serialized as an empty string; the header field
+/// should be omitted then.
+pub fn serialize_dictionary(dict: &[(&str, ListEntry)]) -> Result<String> {
+    let mut out = String::new();
+    for (i, &(key, ref member)) in dict.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_key(&mut out, key)?;
+        match *member {
+            ListEntry::Item(Item {
+                bare_item: BareItem::Boolean(true),
+                ref params,
+            }) => write_parameters(&mut out, params)?,
+            _ => {
+                out.push('=');
+                write_list_entry(&mut out, member)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Serialize an Item.
+pub fn serialize_item(item: &Item) -> Result<String> {
+    let mut out = String::new();
+    write_item(&mut out, item)?;
+    Ok(out)
+}
+
+fn write_list_entry(out: &mut String, member: &ListEntry) -> Result<()> {
+    match *member {
+        ListEntry::Item(ref item) => write_item(out, item),
+        ListEntry::InnerList(ref inner) => {
+            out.push('(');
+            for (i, item) in inner.items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_item(out, item)?;
+            }
+            out.push(')');
+            write_parameters(out, &inner.params)
+        }
+    }
+}
+
+fn write_item(out: &mut String, item: &Item) -> Result<()> {
+    write_bare_item(out, &item.bare_item)?;
+    write_parameters(out, &item.params)
+}
+
+fn write_parameters(out: &mut String, params: &[(&str, BareItem)]) -> Result<()> {
+    for &(key, ref value) in params {
+        out.push(';');
+        write_key(out, key)?;
+        if *value != BareItem::Boolean(true) {
+            out.push('=');
+            write_bare_item(out, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_key(out: &mut String, key: &str) -> Result<()> {
+    match key.as_bytes().first() {
+        Some(&c) if (is_lcalpha(c) || c == b'*') && key.bytes().all(is_key_char) => {
+            out.push_str(key);
+            Ok(())
+        }
+        _ => Err(InvalidStructuredField),
+    }
+}
+
+fn write_bare_item(out: &mut String, item: &BareItem) -> Result<()> {
+    use std::fmt::Write;
+
+    match *item {
+        BareItem::Integer(v) if (-MAX_INTEGER..=MAX_INTEGER).contains(&v) => {
+            write!(out, "{}", v).or(Err(InvalidStructuredField))
+        }
+        BareItem::Decimal(v) if v.thousandths().unsigned_abs() / 1000 <= 999_999_999_999 => {
+            write!(out, "{}", v).or(Err(InvalidStructuredField))
+        }
+        BareItem::String(ref s) => {
+            out.push('"');
+            for c in s.bytes() {
+                match c {
+                    b'"' | b'\\' => {
+                        out.push('\\');
+                        out.push(char::from(c));
+                    }
+                    0x20..=0x7E => out.push(char::from(c)),
+                    _ => return Err(InvalidStructuredField),
+                }
+            }
+            out.push('"');
+            Ok(())
+        }
+        BareItem::Token(t) => match t.as_bytes().first() {
+            Some(&c) if (c.is_ascii_alphabetic() || c == b'*') && t.bytes().all(is_token_char) => {
+                out.push_str(t);
+                Ok(())
+            }
+            _ => Err(InvalidStructuredField),
+        },
+        BareItem::ByteSequence(ref bytes) => {
+            let mut buf = vec![0; base64::encoded_len(bytes.len())];
+            base64::encode(bytes, &mut buf);
+            out.push(':');
+            out.push_str(str::from_utf8(&buf).or(Err(InvalidStructuredField))?);
+            out.push(':');
+            Ok(())
+        }
+        BareItem::Boolean(v) => {
+            out.push_str(if v { "?1" } else { "?0" });
+            Ok(())
+        }
+        _ => Err(InvalidStructuredField),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sfv::*;
+
+    fn token(t: &str) -> ListEntry<'_> {
+        ListEntry::Item(Item::new(BareItem::Token(t)))
+    }
+
+    #[test]
+    fn test_parse_item() {
+        assert_eq!(parse_item("42").unwrap().bare_item, BareItem::Integer(42));
+        assert_eq!(
+            parse_item("-042").unwrap().bare_item,
+            BareItem::Integer(-42)
+        );
+        assert_eq!(
+            parse_item("4.5").unwrap().bare_item,
+            BareItem::Decimal(Decimal::from_thousandths(4500))
+        );
+        assert_eq!(
+            parse_item("-0.125").unwrap().bare_item,
+            BareItem::Decimal(Decimal::from_thousandths(-125))
+        );
+        assert_eq!(
+            parse_item("\"a \\\"b\\\\\"").unwrap().bare_item,
+            BareItem::String(Cow::Borrowed("a \"b\\"))
+        );
+        assert_eq!(
+            parse_item("a/b:c").unwrap().bare_item,
+            BareItem::Token("a/b:c")
+        );
+        assert_eq!(
+            parse_item(":aGVsbG8=:").unwrap().bare_item,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+        // Missing padding and non-zero pad bits are accepted.
+        assert_eq!(
+            parse_item(":aGVsbG8:").unwrap().bare_item,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+        assert_eq!(
+            parse_item(":aGVsbG9=:").unwrap().bare_item,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+        assert_eq!(
+            parse_item("?0").unwrap().bare_item,
+            BareItem::Boolean(false)
+        );
+
+        let item = parse_item("  abc;a=1;b;a=2  ").unwrap();
+        assert_eq!(
+            item.params,
+            [("a", BareItem::Integer(2)), ("b", BareItem::Boolean(true))]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_item() {
+        for v in &[
+            "",
+            "1234567890123456",
+            "1234567890123.0",
+            "1.",
+            "1.1234",
+            "--1",
+            "\"abc",
+            "\"\\a\"",
+            "\"\t\"",
+            ":aGVsbG8=",
+            ":aGVsbG8",
+            "?2",
+            "abc;A=1",
+            "abc ;a",
+            "\tabc",
+            "a b",
+            "\u{e9}",
+        ] {
+            assert_eq!(parse_item(v), Err(InvalidStructuredField), "{:?}", v);
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let list = parse_list("a, (b c);x, d").unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0], token("a"));
+        assert_eq!(
+            list[1],
+            ListEntry::InnerList(InnerList {
+                items: vec![
+                    Item::new(BareItem::Token("b")),
+                    Item::new(BareItem::Token("c"))
+                ],
+                params: vec![("x", BareItem::Boolean(true))],
+            })
+        );
+
+        assert_eq!(parse_list("").unwrap(), []);
+        assert_eq!(parse_list("( )").unwrap().len(), 1);
+        assert_eq!(parse_list("a,\tb").unwrap().len(), 2);
+        assert_eq!(parse_list("a,"), Err(InvalidStructuredField));
+        assert_eq!(parse_list(",a"), Err(InvalidStructuredField));
+        assert_eq!(parse_list("a b"), Err(InvalidStructuredField));
+        assert_eq!(parse_list("(a b"), Err(InvalidStructuredField));
+        assert_eq!(parse_list("(a,b)"), Err(InvalidStructuredField));
+    }
+
+    #[test]
+    fn test_parse_dictionary() {
+        let dict = parse_dictionary("a=1, b;x, c=(1 2), a=3").unwrap();
+        assert_eq!(dict.len(), 3);
+        assert_eq!(
+            dict[0],
+            ("a", ListEntry::Item(Item::new(BareItem::Integer(3))))
+        );
+        assert_eq!(
+            dict[1],
+            (
+                "b",
+                ListEntry::Item(Item {
+                    bare_item: BareItem::Boolean(true),
+                    params: vec![("x", BareItem::Boolean(true))],
+                })
+            )
+        );
+        assert_eq!(dict[2].0, "c");
+
+        assert_eq!(parse_dictionary("A=1"), Err(InvalidStructuredField));
+        assert_eq!(parse_dictionary("a=1,"), Err(InvalidStructuredField));
+        assert_eq!(parse_dictionary("a=1 b=2"), Err(InvalidStructuredField));
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serialize_list(&parse_list("a;q=0.50,  (\"x\"  :AQI=:);y=?0 ,?1").unwrap()).unwrap(),
+            "a;q=0.5, (\"x\" :AQI=:);y=?0, ?1"
+        );
+        assert_eq!(
+            serialize_dictionary(&parse_dictionary("a=?1;p, b=?0, c=-1.250").unwrap()).unwrap(),
+            "a;p, b=?0, c=-1.25"
+        );
+        assert_eq!(
+            serialize_item(&parse_item("2.000").unwrap()).unwrap(),
+            "2.0"
+        );
+        assert_eq!(
+            serialize_item(&parse_item("\"a\\\\b\"").unwrap()).unwrap(),
+            "\"a\\\\b\""
+        );
+        assert_eq!(serialize_list(&[]).unwrap(), "");
+    }
+
+    #[test]
+    fn test_serialize_invalid() {
+        macro_rules! fail {
+            ($bare:expr) => {
+                assert_eq!(
+                    serialize_item(&Item::new($bare)),
+                    Err(InvalidStructuredField)
+                );
+            };
+        }
+
+        fail!(BareItem::Integer(1_000_000_000_000_000));
+        fail!(BareItem::Decimal(Decimal::from_thousandths(
+            1_000_000_000_000_000
+        )));
+        fail!(BareItem::String(Cow::Borrowed("a\r\nb")));
+        fail!(BareItem::Token("1a"));
+        fail!(BareItem::Token("a b"));
+        assert_eq!(serialize_list(&[token("")]), Err(InvalidStructuredField));
+        assert_eq!(
+            serialize_dictionary(&[("Key", token("a"))]),
+            Err(InvalidStructuredField)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+// SHA-1 for the WebSocket handshake. It is not for security.
+
+/// A SHA-1 hasher.
+pub struct Sha1 {
+    state: [u32; 5],
+    block: [u8; 64],
+    len: u64,
+}
+
+impl Sha1 {
+    pub fn new() -> Sha1 {
+        Sha1 {
+            state: [
+                0x6745_2301,
+                0xEFCD_AB89,
+                0x98BA_DCFE,
+                0x1032_5476,
+                0xC3D2_E1F0,
+            ],
+            block: [0; 64],
+            len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let pos = (self.len % 64) as usize;
+            let n = (64 - pos).min(data.len());
+            self.block[pos..pos + n].copy_from_slice(&data[..n]);
+            self.len += n as u64;
+            data = &data[n..];
+            if pos + n == 64 {
+                let block = self.block;
+                self.compress(&block);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> [u8; 20] {
+        let bits = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.len % 64 != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0; 20];
+        for (dst, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            dst.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sha1::*;
+
+    fn digest(data: &[u8]) -> [u8; 20] {
+        let mut sha1 = Sha1::new();
+        sha1.update(data);
+        sha1.finish()
+    }
+
+    fn hex(digest: &[u8; 20]) -> [u8; 40] {
+        let mut out = [0; 40];
+        for (i, b) in digest.iter().enumerate() {
+            out[i * 2] = b"0123456789abcdef"[(b >> 4) as usize];
+            out[i * 2 + 1] = b"0123456789abcdef"[(b & 0xF) as usize];
+        }
+        out
+    }
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            &hex(&digest(b""))[..],
+            &b"da39a3ee5e6b4b0d3255bfef95601890afd80709"[..]
+        );
+        assert_eq!(
+            &hex(&digest(b"abc"))[..],
+            &b"a9993e364706816aba3e25717850c26c9cd0d89d"[..]
+        );
+        assert_eq!(
+            &hex(&digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ))[..],
+            &b"84983e441c3bd26ebaae4aa1f95129e5e54670f1"[..]
+        );
+    }
+
+    #[test]
+    fn test_update_in_parts() {
+        let mut sha1 = Sha1::new();
+        for _ in 0..1000 {
+            sha1.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            &hex(&sha1.finish())[..],
+            &b"34aa973cd4c4daa4f61eeb2bdbad27316534016f"[..]
+        );
+    }
+}
@@ -0,0 +1,440 @@
+//! Span-based parse results.
+//!
+//! Parts are recorded as byte ranges in the parsed buffer instead of
+//! borrowed strings, so the buffer can be grown or moved after parsing and
+//! the results are rebased by adding an offset.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::spans::{HeaderFieldSpan, RequestSpans};
+//!
+//! let mut buf = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec();
+//! let mut headers = Vec::<HeaderFieldSpan>::with_capacity(16);
+//! let (req, len) = RequestSpans::parse(&buf, &mut headers).unwrap().unwrap();
+//! assert_eq!(req.method, 0..3);
+//!
+//! // The buffer can be modified while the spans are kept.
+//! buf.extend_from_slice(b"body");
+//! assert_eq!(req.headers[0].value(&buf), Some("example.com"));
+//! assert_eq!(&buf[len..], b"body");
+//! ```
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use std::ops::Range;
+use std::str;
+
+use crate::errors::*;
+use crate::{
+    Complete, HeaderField, HeaderFieldCollection, HttpPartParser, Incomplete, Output, Request,
+    Response, Status,
+};
+
+// Outputs ranges relative to the start of the buffer.
+struct Spans;
+
+impl<'buffer> Output<'buffer> for Spans {
+    type Part = Range<u32>;
+    type Field = HeaderFieldSpan;
+
+    #[inline]
+    fn part(base: &'buffer [u8], s: &'buffer str) -> Range<u32> {
+        let start = s.as_ptr() as usize - base.as_ptr() as usize;
+        start as u32..(start + s.len()) as u32
+    }
+
+    #[inline]
+    fn field(name: Range<u32>, value: Range<u32>) -> HeaderFieldSpan {
+        HeaderFieldSpan { name, value }
+    }
+}
+
+// Spans are `u32`, so only the first 4 GiB of a buffer is parsed and a
+// longer message is incomplete.
+#[inline]
+fn limit(buf: &[u8]) -> &[u8] {
+    &buf[..buf.len().min(u32::MAX as usize)]
+}
+
+#[inline]
+fn get<'buffer>(buf: &'buffer [u8], span: &Range<u32>) -> Option<&'buffer str> {
+    let bytes = buf.get(span.start as usize..span.end as usize)?;
+    str::from_utf8(bytes).ok()
+}
+
+#[inline]
+fn shift(span: &Range<u32>, offset: u32) -> Option<Range<u32>> {
+    Some(span.start.checked_add(offset)?..span.end.checked_add(offset)?)
+}
+
+/// A header field recorded as byte ranges.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct HeaderFieldSpan {
+    /// The range of the header field name.
+    pub name: Range<u32>,
+    /// The range of the header field value.
+    pub value: Range<u32>,
+}
+
+impl HeaderFieldSpan {
+    /// Returns the header field name in `buf`.
+    ///
+    /// Returns `None` if the range is out of `buf` or not UTF-8.
+    pub fn name<'buffer>(&self, buf: &'buffer [u8]) -> Option<&'buffer str> {
+        get(buf, &self.name)
+    }
+
+    /// Returns the header field value in `buf`.
+    ///
+    /// Returns `None` if the range is out of `buf` or not UTF-8.
+    pub fn value<'buffer>(&self, buf: &'buffer [u8]) -> Option<&'buffer str> {
+        get(buf, &self.value)
+    }
+
+    /// Returns the header field in `buf`.
+    pub fn get<'buffer>(&self, buf: &'buffer [u8]) -> Option<HeaderField<'buffer>> {
+        Some(HeaderField {
+            name: self.name(buf)?,
+            value: self.value(buf)?,
+        })
+    }
+
+    /// Returns the header field with ranges moved by `offset`.
+    ///
+    /// Returns `None` if a range overflows.
+    pub fn rebase(&self, offset: u32) -> Option<HeaderFieldSpan> {
+        Some(HeaderFieldSpan {
+            name: shift(&self.name, offset)?,
+            value: shift(&self.value, offset)?,
+        })
+    }
+}
+
+/// A basic implementation of `HeaderFieldCollection` for spans.
+impl<'buffer> HeaderFieldCollection<'buffer, HeaderFieldSpan> for Vec<HeaderFieldSpan> {
+    fn push(&mut self, header_field: HeaderFieldSpan) -> Result<()> {
+        if self.len() == self.capacity() {
+            Err(OutOfCapacity)
+        } else {
+            self.push(header_field);
+            Ok(())
+        }
+    }
+}
+
+fn rebase_fields<'buffer, Headers>(
+    spans: &[HeaderFieldSpan],
+    offset: u32,
+    headers: &mut Headers,
+) -> Option<()>
+where
+    Headers: HeaderFieldCollection<'buffer, HeaderFieldSpan>,
+{
+    for span in spans {
+        headers.push(span.rebase(offset)?).ok()?;
+    }
+    Some(())
+}
+
+fn push_fields<'buffer, Headers>(
+    buf: &'buffer [u8],
+    spans: &[HeaderFieldSpan],
+    headers: &mut Headers,
+) -> Result<()>
+where
+    Headers: HeaderFieldCollection<'buffer>,
+{
+    for span in spans {
+        let name = span.name(buf).ok_or(InvalidFieldName)?;
+        let value = span.value(buf).ok_or(InvalidFieldValue)?;
+        headers.push(HeaderField { name, value })?;
+    }
+    Ok(())
+}
+
+/// A parsed request recorded as byte ranges.
+#[derive(Debug, PartialEq)]
+pub struct RequestSpans<'headers> {
+    /// The range of the request method.
+    pub method: Range<u32>,
+    /// The range of the request target.
+    pub target: Range<u32>,
+    /// The http minor version.
+    pub minor_version: u8,
+    /// The request header fields.
+    pub headers: &'headers [HeaderFieldSpan],
+}
+
+impl<'headers> RequestSpans<'headers> {
+    /// Parse the buffer as http request.
+    pub fn parse<'buffer, Headers>(
+        buf: &'buffer [u8],
+        headers: &'headers mut Headers,
+    ) -> Result<Status<(Self, usize)>>
+    where
+        Headers: HeaderFieldCollection<'buffer, HeaderFieldSpan>,
+    {
+        let buf = limit(buf);
+        let mut parser = HttpPartParser::<Spans>::with_output(buf);
+        let (method, target, minor_version, headers) = match parser.parse_request_head(headers)? {
+            Complete(head) => head,
+            Incomplete => return Ok(Incomplete),
+        };
+        Ok(Complete((
+            RequestSpans {
+                method,
+                target,
+                minor_version,
+                headers,
+            },
+            buf.len() - parser.len(),
+        )))
+    }
+
+    /// Returns a borrowed request in `buf`, pushing header fields into
+    /// `headers`.
+    ///
+    /// `buf` must start where the parsed buffer started.
+    pub fn to_request<'a, 'buffer, Headers>(
+        &self,
+        buf: &'buffer [u8],
+        headers: &'a mut Headers,
+    ) -> Result<Request<'a, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        let method = get(buf, &self.method).ok_or(InvalidMethod)?;
+        let target = get(buf, &self.target).ok_or(InvalidPath)?;
+        push_fields(buf, self.headers, headers)?;
+        Ok(Request {
+            method,
+            target,
+            minor_version: self.minor_version,
+            headers,
+        })
+    }
+
+    /// Returns the request with ranges moved by `offset`, pushing the moved
+    /// header fields into `headers`.
+    ///
+    /// Returns `None` if a range overflows or `headers` is full.
+    pub fn rebase<'a, 'buffer, Headers>(
+        &self,
+        offset: u32,
+        headers: &'a mut Headers,
+    ) -> Option<RequestSpans<'a>>
+    where
+        Headers: HeaderFieldCollection<'buffer, HeaderFieldSpan>,
+    {
+        let method = shift(&self.method, offset)?;
+        let target = shift(&self.target, offset)?;
+        rebase_fields(self.headers, offset, headers)?;
+        Some(RequestSpans {
+            method,
+            target,
+            minor_version: self.minor_version,
+            headers,
+        })
+    }
+}
+
+/// A parsed response recorded as byte ranges.
+#[derive(Debug, PartialEq)]
+pub struct ResponseSpans<'headers> {
+    /// The http minor version.
+    pub minor_version: u8,
+    /// The status code.
+    pub status: u16,
+    /// The range of the reason phrase.
+    pub reason: Range<u32>,
+    /// The response header fields.
+    pub headers: &'headers [HeaderFieldSpan],
+}
+
+impl<'headers> ResponseSpans<'headers> {
+    /// Parse the buffer as http response.
+    pub fn parse<'buffer, Headers>(
+        buf: &'buffer [u8],
+        headers: &'headers mut Headers,
+    ) -> Result<Status<(Self, usize)>>
+    where
+        Headers: HeaderFieldCollection<'buffer, HeaderFieldSpan>,
+    {
+        let buf = limit(buf);
+        let mut parser = HttpPartParser::<Spans>::with_output(buf);
+        let (minor_version, status, reason, headers) = match parser.parse_response_head(headers)? {
+            Complete(head) => head,
+            Incomplete => return Ok(Incomplete),
+        };
+        Ok(Complete((
+            ResponseSpans {
+                minor_version,
+                status,
+                reason,
+                headers,
+            },
+            buf.len() - parser.len(),
+        )))
+    }
+
+    /// Returns a borrowed response in `buf`, pushing header fields into
+    /// `headers`.
+    ///
+    /// `buf` must start where the parsed buffer started.
+    pub fn to_response<'a, 'buffer, Headers>(
+        &self,
+        buf: &'buffer [u8],
+        headers: &'a mut Headers,
+    ) -> Result<Response<'a, 'buffer>>
+    where
+        Headers: HeaderFieldCollection<'buffer>,
+    {
+        let reason = get(buf, &self.reason).ok_or(InvalidReasonPhrase)?;
+        push_fields(buf, self.headers, headers)?;
+        Ok(Response {
+            minor_version: self.minor_version,
+            status: self.status,
+            reason,
+            headers,
+        })
+    }
+
+    /// Returns the response with ranges moved by `offset`, pushing the moved
+    /// header fields into `headers`.
+    ///
+    /// Returns `None` if a range overflows or `headers` is full.
+    pub fn rebase<'a, 'buffer, Headers>(
+        &self,
+        offset: u32,
+        headers: &'a mut Headers,
+    ) -> Option<ResponseSpans<'a>>
+    where
+        Headers: HeaderFieldCollection<'buffer, HeaderFieldSpan>,
+    {
+        let reason = shift(&self.reason, offset)?;
+        rebase_fields(self.headers, offset, headers)?;
+        Some(ResponseSpans {
+            minor_version: self.minor_version,
+            status: self.status,
+            reason,
+            headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::spans::*;
+
+    #[test]
+    fn test_request_spans() {
+        let buf = b"\r\nPOST /index.html HTTP/1.0\r\nHost: example.com\r\nX-Empty:\r\n\r\nbody";
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let (req, len) = RequestSpans::parse(buf, &mut headers).unwrap().unwrap();
+        assert_eq!(len, buf.len() - 4);
+        assert_eq!(req.method, 2..6);
+        assert_eq!(req.target, 7..18);
+        assert_eq!(req.minor_version, 0);
+        assert_eq!(req.headers.len(), 2);
+        assert_eq!(req.headers[0].name(buf), Some("Host"));
+        assert_eq!(req.headers[0].value(buf), Some("example.com"));
+        assert_eq!(req.headers[1].value(buf), Some(""));
+
+        let mut borrowed_headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = req.to_request(buf, &mut borrowed_headers).unwrap();
+
+        let mut expected_headers = Vec::<HeaderField>::with_capacity(4);
+        let (expected, _) = Request::parse(buf, &mut expected_headers).unwrap().unwrap();
+        assert_eq!(borrowed, expected);
+    }
+
+    #[test]
+    fn test_response_spans() {
+        let buf = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let (res, len) = ResponseSpans::parse(buf, &mut headers).unwrap().unwrap();
+        assert_eq!(len, buf.len());
+        assert_eq!(res.status, 404);
+        assert_eq!(res.reason, 13..22);
+
+        let mut borrowed_headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = res.to_response(buf, &mut borrowed_headers).unwrap();
+        assert_eq!(borrowed.reason, "Not Found");
+        assert_eq!(borrowed.headers[0].name, "Content-Length");
+    }
+
+    #[test]
+    fn test_rebase_spans() {
+        let buf = b"GET / HTTP/1.1\r\nA: b\r\n\r\n";
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let (req, _) = RequestSpans::parse(buf, &mut headers).unwrap().unwrap();
+
+        let mut moved = b"...".to_vec();
+        moved.extend_from_slice(buf);
+        let field = req.headers[0].rebase(3).unwrap();
+        assert_eq!(
+            field.get(&moved),
+            Some(HeaderField {
+                name: "A",
+                value: "b"
+            })
+        );
+        assert_eq!(req.headers[0].get(&moved[..4]), None);
+        assert_eq!(req.headers[0].rebase(u32::MAX), None);
+
+        let mut rebased_headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let rebased = req.rebase(3, &mut rebased_headers).unwrap();
+        let mut borrowed_headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = rebased.to_request(&moved, &mut borrowed_headers).unwrap();
+        assert_eq!(borrowed.method, "GET");
+        assert_eq!(borrowed.headers[0].value, "b");
+        let mut rebased_headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        assert_eq!(req.rebase(u32::MAX - 1, &mut rebased_headers), None);
+        let mut rebased_headers = Vec::<HeaderFieldSpan>::with_capacity(0);
+        assert_eq!(req.rebase(3, &mut rebased_headers), None);
+
+        let buf = b"HTTP/1.1 200 OK\r\nA: b\r\n\r\n";
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let (res, _) = ResponseSpans::parse(buf, &mut headers).unwrap().unwrap();
+        let mut moved = b"...".to_vec();
+        moved.extend_from_slice(buf);
+        let mut rebased_headers = Vec::<HeaderFieldSpan>::with_capacity(4);
+        let rebased = res.rebase(3, &mut rebased_headers).unwrap();
+        let mut borrowed_headers = Vec::<HeaderField>::with_capacity(4);
+        let borrowed = rebased.to_response(&moved, &mut borrowed_headers).unwrap();
+        assert_eq!(borrowed.reason, "OK");
+        assert_eq!(borrowed.headers[0].name, "A");
+    }
+
+    #[test]
+    fn test_spans_errors() {
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(1);
+        assert_eq!(
+            RequestSpans::parse(b"GET / HTTP/1.1\r\nA: b", &mut headers),
+            Ok(Incomplete)
+        );
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(1);
+        assert_eq!(
+            RequestSpans::parse(b"GET / HTTP/1.1\r\nA: b\r\nC: d\r\n\r\n", &mut headers),
+            Err(OutOfCapacity)
+        );
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(1);
+        assert_eq!(
+            ResponseSpans::parse(b"HTTP/1.1 2000 OK\r\n\r\n", &mut headers),
+            Err(InvalidStatusCode)
+        );
+
+        let buf = b"GET / HTTP/1.1\r\n\r\n";
+        let mut headers = Vec::<HeaderFieldSpan>::with_capacity(1);
+        let (req, _) = RequestSpans::parse(buf, &mut headers).unwrap().unwrap();
+        let mut borrowed_headers = Vec::<HeaderField>::with_capacity(1);
+        assert_eq!(
+            req.to_request(b"GE", &mut borrowed_headers).err(),
+            Some(InvalidMethod)
+        );
+    }
+}
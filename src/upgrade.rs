@@ -0,0 +1,254 @@
+//! Protocol upgrades and `CONNECT` tunnels.
+//!
+//! A request asks to switch protocols with `Connection: upgrade` and the
+//! `Upgrade` header field, and a `101 Switching Protocols` response accepts
+//! it. A 2xx response to `CONNECT` makes the connection a tunnel. In both
+//! cases, the bytes after the response head belong to the new protocol.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::upgrade::{self, Upgrade};
+//!
+//! let buf = b"HTTP/1.1 101 Switching Protocols\r\nConnection: upgrade\r\nUpgrade: websocket\r\n\r\n\x81\x05hello";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (res, len) = thhp::Response::parse(buf, &mut headers).unwrap().unwrap();
+//! match upgrade::handoff("GET", &res, buf, len).unwrap() {
+//!     Some((Upgrade::Protocol(protocol), rest)) => {
+//!         assert_eq!(protocol.name, "websocket");
+//!         assert_eq!(rest, b"\x81\x05hello");
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+use crate::errors::*;
+use crate::fields::{has_token, is_token, ListIter};
+use crate::{HeaderField, Request, Response};
+
+/// A protocol in the `Upgrade` header field.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Protocol<'buffer> {
+    /// The protocol name.
+    pub name: &'buffer str,
+    /// The protocol version.
+    pub version: Option<&'buffer str>,
+}
+
+impl<'buffer> Protocol<'buffer> {
+    fn parse(s: &'buffer str) -> Option<Protocol<'buffer>> {
+        let (name, version) = match s.find('/') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        if !is_token(name) || version.is_some_and(|v| !is_token(v)) {
+            return None;
+        }
+        Some(Protocol { name, version })
+    }
+}
+
+/// An iterator over the protocols in `Upgrade` header fields.
+///
+/// Invalid elements are skipped.
+#[derive(Clone, Debug)]
+pub struct Protocols<'a, 'buffer: 'a> {
+    headers: &'a [HeaderField<'buffer>],
+    values: ListIter<'buffer>,
+}
+
+impl<'a, 'buffer> Iterator for Protocols<'a, 'buffer> {
+    type Item = Protocol<'buffer>;
+
+    fn next(&mut self) -> Option<Protocol<'buffer>> {
+        loop {
+            if let Some(value) = self.values.next() {
+                if let Some(protocol) = Protocol::parse(value) {
+                    return Some(protocol);
+                }
+                continue;
+            }
+            let (header, rest) = self.headers.split_first()?;
+            self.headers = rest;
+            if header.name.eq_ignore_ascii_case("Upgrade") {
+                self.values = ListIter::new(header.value);
+            }
+        }
+    }
+}
+
+fn protocols<'a, 'buffer>(headers: &'a [HeaderField<'buffer>]) -> Protocols<'a, 'buffer> {
+    Protocols {
+        headers,
+        values: ListIter::new(""),
+    }
+}
+
+/// How a connection switches protocols.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Upgrade<'buffer> {
+    /// The connection switches to a protocol by `101 Switching Protocols`.
+    Protocol(Protocol<'buffer>),
+    /// The connection becomes a tunnel by a 2xx response to `CONNECT`.
+    Tunnel,
+}
+
+impl<'headers, 'buffer: 'headers> Request<'headers, 'buffer> {
+    /// Returns the protocols the request asks to upgrade to, in descending
+    /// order of preference.
+    ///
+    /// There are none unless `Connection` has `upgrade`. `Upgrade` is
+    /// ignored in HTTP/1.0 requests.
+    pub fn upgrade_protocols(&self) -> Protocols<'headers, 'buffer> {
+        if self.minor_version >= 1 && has_token(self.headers, "Connection", "upgrade") {
+            protocols(self.headers)
+        } else {
+            protocols(&[])
+        }
+    }
+
+    /// Returns `true` if a response may switch the connection to another
+    /// protocol, that is, the request is `CONNECT` or asks to upgrade.
+    pub fn is_upgrade(&self) -> bool {
+        self.method == "CONNECT" || self.upgrade_protocols().next().is_some()
+    }
+}
+
+impl<'headers, 'buffer: 'headers> Response<'headers, 'buffer> {
+    /// Returns how the response to a request with `method` switches the
+    /// connection to another protocol, or `None` if it doesn't.
+    ///
+    /// The protocol of a `101 Switching Protocols` response is the first one
+    /// in `Upgrade`. The response is invalid without it, and `None` is
+    /// returned, which `handoff` reports as `InvalidUpgrade`.
+    pub fn upgrade(&self, method: &str) -> Option<Upgrade<'buffer>> {
+        if method == "CONNECT" && self.status / 100 == 2 {
+            return Some(Upgrade::Tunnel);
+        }
+        if self.status != 101 {
+            return None;
+        }
+        protocols(self.headers).next().map(Upgrade::Protocol)
+    }
+}
+
+/// Returns how `res`, parsed from `buf` as a response of `len` bytes to a
+/// request with `method`, switches protocols, with the bytes after the head
+/// which belong to the new protocol.
+///
+/// Returns `None` if the connection stays HTTP/1, or `BufferTooSmall` if
+/// `len` exceeds `buf`. A `101 Switching Protocols` response without
+/// `Upgrade` switches to an unknown protocol, so it is `InvalidUpgrade`.
+pub fn handoff<'buffer>(
+    method: &str,
+    res: &Response<'_, 'buffer>,
+    buf: &'buffer [u8],
+    len: usize,
+) -> Result<Option<(Upgrade<'buffer>, &'buffer [u8])>> {
+    let rest = buf.get(len..).ok_or(BufferTooSmall)?;
+    match res.upgrade(method) {
+        Some(upgrade) => Ok(Some((upgrade, rest))),
+        None if res.status == 101 => Err(InvalidUpgrade),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::field;
+    use crate::upgrade::*;
+
+    fn request<'a>(method: &'a str, headers: &'a [HeaderField<'a>]) -> Request<'a, 'a> {
+        Request {
+            method,
+            target: "/",
+            minor_version: 1,
+            headers,
+        }
+    }
+
+    fn response<'a>(status: u16, headers: &'a [HeaderField<'a>]) -> Response<'a, 'a> {
+        Response {
+            minor_version: 1,
+            status,
+            reason: "",
+            headers,
+        }
+    }
+
+    #[test]
+    fn test_upgrade_protocols() {
+        let headers = [
+            field("Upgrade", "HTTP/2.0, websocket"),
+            field("Connection", "keep-alive, Upgrade"),
+            field("upgrade", "bad/, /1, IRC/6.9"),
+        ];
+        let req = request("GET", &headers);
+        let protocols: Vec<_> = req
+            .upgrade_protocols()
+            .map(|p| (p.name, p.version))
+            .collect();
+        assert_eq!(
+            protocols,
+            [
+                ("HTTP", Some("2.0")),
+                ("websocket", None),
+                ("IRC", Some("6.9"))
+            ]
+        );
+        assert!(req.is_upgrade());
+
+        // `Connection: upgrade` is required.
+        let req = request("GET", &headers[..1]);
+        assert_eq!(req.upgrade_protocols().next(), None);
+        assert!(!req.is_upgrade());
+
+        let req = Request {
+            minor_version: 0,
+            ..request("GET", &headers)
+        };
+        assert!(!req.is_upgrade());
+
+        assert!(request("CONNECT", &[]).is_upgrade());
+    }
+
+    #[test]
+    fn test_response_upgrade() {
+        let headers = [field("Connection", "upgrade"), field("Upgrade", "h2c")];
+        assert_eq!(
+            response(101, &headers).upgrade("GET"),
+            Some(Upgrade::Protocol(Protocol {
+                name: "h2c",
+                version: None
+            }))
+        );
+        assert_eq!(response(101, &headers[..1]).upgrade("GET"), None);
+        assert_eq!(response(200, &headers).upgrade("GET"), None);
+
+        assert_eq!(response(200, &[]).upgrade("CONNECT"), Some(Upgrade::Tunnel));
+        assert_eq!(response(407, &[]).upgrade("CONNECT"), None);
+    }
+
+    #[test]
+    fn test_handoff() {
+        let buf = b"HTTP/1.1 200 Connection Established\r\n\r\n\x16\x03\x01";
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let (res, len) = Response::parse(buf, &mut headers).unwrap().unwrap();
+        assert_eq!(
+            handoff("CONNECT", &res, buf, len),
+            Ok(Some((Upgrade::Tunnel, &b"\x16\x03\x01"[..])))
+        );
+        assert_eq!(handoff("GET", &res, buf, len), Ok(None));
+        assert_eq!(
+            handoff("CONNECT", &res, buf, buf.len() + 1),
+            Err(BufferTooSmall)
+        );
+
+        let buf = b"HTTP/1.1 101 Switching Protocols\r\nConnection: upgrade\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(4);
+        let (res, len) = Response::parse(buf, &mut headers).unwrap().unwrap();
+        assert_eq!(handoff("GET", &res, buf, len), Err(InvalidUpgrade));
+    }
+}
@@ -0,0 +1,444 @@
+//! The WebSocket opening handshake (RFC 6455).
+//!
+//! `ClientHandshake` validates a handshake request and makes the
+//! `101 Switching Protocols` response with `Sec-WebSocket-Accept`.
+//! `verify_response` checks the response on the client side. SHA-1 and
+//! base64 are built in, so no allocation is needed.
+//!
+//! ## Example
+//!
+//! ```
+//! use thhp::websocket::ClientHandshake;
+//!
+//! let buf = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\n\
+//!             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+//!             Sec-WebSocket-Protocol: chat, superchat\r\nSec-WebSocket-Version: 13\r\n\r\n";
+//! let mut headers = Vec::<thhp::HeaderField>::with_capacity(16);
+//! let (req, _) = thhp::Request::parse(buf, &mut headers).unwrap().unwrap();
+//!
+//! let handshake = ClientHandshake::from_request(&req).unwrap();
+//! let protocol = handshake.select_protocol(&["superchat"]);
+//! assert_eq!(protocol, Some("superchat"));
+//!
+//! let mut out = [0u8; 256];
+//! let len = handshake.encode_response(protocol, None, &mut out).unwrap();
+//! assert_eq!(
+//!     &out[..len],
+//!     &b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+//!        Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+//!        Sec-WebSocket-Protocol: superchat\r\n\r\n"[..]
+//! );
+//! ```
+use std::str;
+
+use crate::base64;
+use crate::errors::*;
+use crate::fields::{find, find_all, has_token, trim_ows, ListIter};
+use crate::sha1::Sha1;
+use crate::{HeaderField, Request, Response};
+
+const GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A value of `Sec-WebSocket-Key`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Key([u8; 24]);
+
+impl Key {
+    /// Makes a key from a nonce, which must be randomly selected for each
+    /// connection.
+    pub fn from_nonce(nonce: &[u8; 16]) -> Key {
+        let mut key = [0; 24];
+        base64::encode(nonce, &mut key);
+        Key(key)
+    }
+
+    /// Returns the key as a string.
+    pub fn as_str(&self) -> &str {
+        // Base64 is ASCII.
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+/// A value of `Sec-WebSocket-Accept`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct AcceptKey([u8; 28]);
+
+impl AcceptKey {
+    /// Computes the value accepting `key`.
+    pub fn new(key: &str) -> AcceptKey {
+        let mut sha1 = Sha1::new();
+        sha1.update(key.as_bytes());
+        sha1.update(GUID);
+        let mut accept = [0; 28];
+        base64::encode(&sha1.finish(), &mut accept);
+        AcceptKey(accept)
+    }
+
+    /// Returns the value as a string.
+    pub fn as_str(&self) -> &str {
+        // Base64 is ASCII.
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+/// An extension in `Sec-WebSocket-Extensions`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Extension<'buffer> {
+    /// The extension name.
+    pub name: &'buffer str,
+    params: &'buffer str,
+}
+
+impl<'buffer> Extension<'buffer> {
+    fn parse(s: &'buffer str) -> Extension<'buffer> {
+        match s.find(';') {
+            Some(i) => Extension {
+                name: trim_ows(&s[..i]),
+                params: &s[i + 1..],
+            },
+            None => Extension {
+                name: s,
+                params: "",
+            },
+        }
+    }
+
+    /// Returns an iterator over the parameters with optional values.
+    ///
+    /// Quotes around a value are removed.
+    pub fn params(&self) -> impl Iterator<Item = (&'buffer str, Option<&'buffer str>)> {
+        self.params
+            .split(';')
+            .map(trim_ows)
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.find('=') {
+                Some(i) => {
+                    let value = trim_ows(&p[i + 1..]);
+                    let value =
+                        if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                            &value[1..value.len() - 1]
+                        } else {
+                            value
+                        };
+                    (trim_ows(&p[..i]), Some(value))
+                }
+                None => (p, None),
+            })
+    }
+}
+
+/// A validated client handshake request.
+#[derive(Copy, Clone, Debug)]
+pub struct ClientHandshake<'headers, 'buffer: 'headers> {
+    key: &'buffer str,
+    headers: &'headers [HeaderField<'buffer>],
+}
+
+impl<'headers, 'buffer: 'headers> ClientHandshake<'headers, 'buffer> {
+    /// Validates a handshake request.
+    ///
+    /// It is a `GET` request with `Host`, `Connection: upgrade`,
+    /// `Upgrade: websocket`, a `Sec-WebSocket-Key` of 16 bytes and
+    /// `Sec-WebSocket-Version: 13`. A server should respond to a request
+    /// with another version by `426 Upgrade Required` with
+    /// `Sec-WebSocket-Version: 13`.
+    pub fn from_request(
+        req: &Request<'headers, 'buffer>,
+    ) -> Result<ClientHandshake<'headers, 'buffer>> {
+        let headers = req.headers;
+        if req.method != "GET"
+            || req.minor_version == 0
+            || find(headers, "Host").is_none()
+            || !has_token(headers, "Connection", "upgrade")
+            || !has_token(headers, "Upgrade", "websocket")
+            || find(headers, "Sec-WebSocket-Version").map(trim_ows) != Some("13")
+        {
+            return Err(InvalidHandshake);
+        }
+
+        let mut keys = find_all(headers, "Sec-WebSocket-Key");
+        let key = match (keys.next(), keys.next()) {
+            (Some(key), None) => trim_ows(key),
+            _ => return Err(InvalidHandshake),
+        };
+        let mut nonce = [0; 18];
+        if key.len() != 24 || base64::decode(key.as_bytes(), &mut nonce) != Some(16) {
+            return Err(InvalidHandshake);
+        }
+        Ok(ClientHandshake { key, headers })
+    }
+
+    /// Returns the value of `Sec-WebSocket-Key`.
+    pub fn key(&self) -> &'buffer str {
+        self.key
+    }
+
+    /// Returns the value of `Sec-WebSocket-Accept` for the response.
+    pub fn accept_key(&self) -> AcceptKey {
+        AcceptKey::new(self.key)
+    }
+
+    /// Returns an iterator over the subprotocols in `Sec-WebSocket-Protocol`,
+    /// in descending order of preference.
+    pub fn protocols(&self) -> impl Iterator<Item = &'buffer str> + 'headers {
+        find_all(self.headers, "Sec-WebSocket-Protocol").flat_map(ListIter::new)
+    }
+
+    /// Returns the most preferred subprotocol in `supported`.
+    pub fn select_protocol(&self, supported: &[&str]) -> Option<&'buffer str> {
+        self.protocols().find(|p| supported.contains(p))
+    }
+
+    /// Returns an iterator over the extensions in `Sec-WebSocket-Extensions`,
+    /// in descending order of preference.
+    pub fn extensions(&self) -> impl Iterator<Item = Extension<'buffer>> + 'headers {
+        find_all(self.headers, "Sec-WebSocket-Extensions")
+            .flat_map(ListIter::new)
+            .map(Extension::parse)
+    }
+
+    /// Calls `f` with the `101 Switching Protocols` response selecting
+    /// `protocol` and `extensions`, given as a value of
+    /// `Sec-WebSocket-Extensions`.
+    pub fn with_response<F, T>(&self, protocol: Option<&str>, extensions: Option<&str>, f: F) -> T
+    where
+        F: FnOnce(&Response) -> T,
+    {
+        let accept = self.accept_key();
+        let mut headers = [
+            HeaderField {
+                name: "Upgrade",
+                value: "websocket",
+            },
+            HeaderField {
+                name: "Connection",
+                value: "Upgrade",
+            },
+            HeaderField {
+                name: "Sec-WebSocket-Accept",
+                value: accept.as_str(),
+            },
+            HeaderField {
+                name: "",
+                value: "",
+            },
+            HeaderField {
+                name: "",
+                value: "",
+            },
+        ];
+        let mut len = 3;
+        if let Some(protocol) = protocol {
+            headers[len] = HeaderField {
+                name: "Sec-WebSocket-Protocol",
+                value: protocol,
+            };
+            len += 1;
+        }
+        if let Some(extensions) = extensions {
+            headers[len] = HeaderField {
+                name: "Sec-WebSocket-Extensions",
+                value: extensions,
+            };
+            len += 1;
+        }
+        f(&Response {
+            minor_version: 1,
+            status: 101,
+            reason: "Switching Protocols",
+            headers: &headers[..len],
+        })
+    }
+
+    /// Writes the `101 Switching Protocols` response into `buf`, returns the
+    /// number of written bytes.
+    pub fn encode_response(
+        &self,
+        protocol: Option<&str>,
+        extensions: Option<&str>,
+        buf: &mut [u8],
+    ) -> Result<usize> {
+        self.with_response(protocol, extensions, |res| res.encode(buf))
+    }
+}
+
+/// Verifies the handshake response to a request with `key` offering
+/// `protocols`.
+///
+/// Returns the subprotocol selected by the server. Extensions in the
+/// response must be checked by the caller.
+pub fn verify_response<'buffer>(
+    res: &Response<'_, 'buffer>,
+    key: &str,
+    protocols: &[&str],
+) -> Result<Option<&'buffer str>> {
+    let headers = res.headers;
+    if res.status != 101
+        || !has_token(headers, "Connection", "upgrade")
+        || !has_token(headers, "Upgrade", "websocket")
+        || find(headers, "Sec-WebSocket-Accept").map(trim_ows) != Some(AcceptKey::new(key).as_str())
+    {
+        return Err(InvalidHandshake);
+    }
+    match find(headers, "Sec-WebSocket-Protocol").map(trim_ows) {
+        Some(protocol) if protocols.contains(&protocol) => Ok(Some(protocol)),
+        Some(_) => Err(InvalidHandshake),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use crate::websocket::*;
+    use crate::Complete;
+
+    const REQUEST: &[u8] = b"GET /chat HTTP/1.1\r\n\
+                             Host: server.example.com\r\n\
+                             Upgrade: websocket\r\n\
+                             Connection: keep-alive, Upgrade\r\n\
+                             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                             Origin: http://example.com\r\n\
+                             Sec-WebSocket-Protocol: chat, superchat\r\n\
+                             Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits, x-foo\r\n\
+                             Sec-WebSocket-Version: 13\r\n\r\n";
+
+    fn with_request<F>(buf: &[u8], f: F)
+    where
+        F: FnOnce(&Request),
+    {
+        let mut headers = Vec::<HeaderField>::with_capacity(16);
+        match Request::parse(buf, &mut headers) {
+            Ok(Complete((req, _))) => f(&req),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example in RFC 6455 section 1.3.
+        assert_eq!(
+            AcceptKey::new("dGhlIHNhbXBsZSBub25jZQ==").as_str(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        let key = Key::from_nonce(b"the sample nonce");
+        assert_eq!(key.as_str(), "dGhlIHNhbXBsZSBub25jZQ==");
+    }
+
+    #[test]
+    fn test_client_handshake() {
+        with_request(REQUEST, |req| {
+            let handshake = ClientHandshake::from_request(req).unwrap();
+            assert_eq!(handshake.key(), "dGhlIHNhbXBsZSBub25jZQ==");
+            assert_eq!(
+                handshake.protocols().collect::<Vec<_>>(),
+                ["chat", "superchat"]
+            );
+            assert_eq!(
+                handshake.select_protocol(&["superchat", "chat"]),
+                Some("chat")
+            );
+            assert_eq!(handshake.select_protocol(&["mqtt"]), None);
+
+            let extensions: Vec<_> = handshake.extensions().collect();
+            assert_eq!(extensions.len(), 2);
+            assert_eq!(extensions[0].name, "permessage-deflate");
+            assert_eq!(
+                extensions[0].params().collect::<Vec<_>>(),
+                [("client_max_window_bits", None)]
+            );
+            assert_eq!(extensions[1].name, "x-foo");
+            assert_eq!(extensions[1].params().next(), None);
+
+            let mut buf = [0u8; 256];
+            let len = handshake
+                .encode_response(None, Some("permessage-deflate"), &mut buf)
+                .unwrap();
+            assert_eq!(
+                &buf[..len],
+                &b"HTTP/1.1 101 Switching Protocols\r\n\
+                   Upgrade: websocket\r\n\
+                   Connection: Upgrade\r\n\
+                   Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                   Sec-WebSocket-Extensions: permessage-deflate\r\n\r\n"[..]
+            );
+            assert_eq!(
+                handshake.encode_response(None, None, &mut buf[..16]),
+                Err(BufferTooSmall)
+            );
+        });
+    }
+
+    #[test]
+    fn test_extension_params() {
+        let ext = Extension::parse("x; a=1 ;b = \"two\"; c");
+        assert_eq!(ext.name, "x");
+        assert_eq!(
+            ext.params().collect::<Vec<_>>(),
+            [("a", Some("1")), ("b", Some("two")), ("c", None)]
+        );
+    }
+
+    #[test]
+    fn test_invalid_client_handshake() {
+        let text = str::from_utf8(REQUEST).unwrap();
+        let cases = [
+            ("GET /chat", "POST /chat"),
+            ("HTTP/1.1", "HTTP/1.0"),
+            ("Host", "X-Host"),
+            ("keep-alive, Upgrade", "keep-alive"),
+            ("Upgrade: websocket", "Upgrade: h2c"),
+            ("Version: 13", "Version: 8"),
+            ("dGhlIHNhbXBsZSBub25jZQ==", "dGhlIHNhbXBsZSBub25j"),
+            ("dGhlIHNhbXBsZSBub25jZQ==", "dGhlIHNhbXBsZSBub25jZQ!="),
+            (
+                "Origin",
+                "Sec-WebSocket-Key: AAAAAAAAAAAAAAAAAAAAAA==\r\nOrigin",
+            ),
+        ];
+        for &(from, to) in cases.iter() {
+            let buf = text.replacen(from, to, 1);
+            with_request(buf.as_bytes(), |req| {
+                assert_eq!(
+                    ClientHandshake::from_request(req).map(|_| ()),
+                    Err(InvalidHandshake),
+                    "{}",
+                    to
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn test_verify_response() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let buf =
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                    Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                    Sec-WebSocket-Protocol: chat\r\n\r\n";
+        let mut headers = Vec::<HeaderField>::with_capacity(16);
+        let (res, _) = Response::parse(buf, &mut headers).unwrap().unwrap();
+        assert_eq!(verify_response(&res, key, &["chat"]), Ok(Some("chat")));
+        assert_eq!(verify_response(&res, key, &["mqtt"]), Err(InvalidHandshake));
+        assert_eq!(
+            verify_response(&res, "AAAAAAAAAAAAAAAAAAAAAA==", &["chat"]),
+            Err(InvalidHandshake)
+        );
+
+        let res = Response { status: 200, ..res };
+        assert_eq!(verify_response(&res, key, &["chat"]), Err(InvalidHandshake));
+
+        // A response made by the server side is accepted.
+        with_request(REQUEST, |req| {
+            let handshake = ClientHandshake::from_request(req).unwrap();
+            handshake.with_response(Some("superchat"), None, |res| {
+                assert_eq!(
+                    verify_response(res, key, &["chat", "superchat"]),
+                    Ok(Some("superchat"))
+                );
+            });
+        });
+    }
+}
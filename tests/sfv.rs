@@ -0,0 +1,192 @@
+//! Runs structured field test cases written in the JSON format of the
+//! structured-field-tests corpus (https://github.com/httpwg/structured-field-tests).
+//!
+//! Every JSON file under `tests/sfv` is run, including the corpus files
+//! vendored in `tests/sfv/upstream` as described in `tests/sfv/README.md`.
+#![cfg(feature = "alloc")]
+
+extern crate serde_json;
+extern crate thhp;
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+use thhp::sfv::*;
+
+fn base32_decode(s: &str) -> Vec<u8> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = Vec::new();
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes().filter(|c| *c != b'=') {
+        let v = ALPHABET.iter().position(|a| *a == c).expect("base32") as u32;
+        acc = (acc << 5) | v;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    out
+}
+
+fn bare_item_eq(expected: &Value, actual: &BareItem) -> bool {
+    match *actual {
+        BareItem::Integer(v) => expected.as_i64() == Some(v),
+        BareItem::Decimal(v) => {
+            expected.is_f64()
+                && (expected.as_f64().unwrap() * 1000.0).round() as i64 == v.thousandths()
+        }
+        BareItem::String(ref s) => expected.as_str() == Some(s),
+        BareItem::Token(t) => {
+            expected["__type"] == "token" && expected["value"].as_str() == Some(t)
+        }
+        BareItem::ByteSequence(ref b) => {
+            expected["__type"] == "binary"
+                && expected["value"].as_str().map(base32_decode).as_ref() == Some(b)
+        }
+        BareItem::Boolean(v) => expected.as_bool() == Some(v),
+    }
+}
+
+fn params_eq(expected: &Value, actual: &[(&str, BareItem)]) -> bool {
+    let expected = expected.as_array().unwrap();
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .all(|(e, a)| e[0].as_str() == Some(a.0) && bare_item_eq(&e[1], &a.1))
+}
+
+fn item_eq(expected: &Value, actual: &Item) -> bool {
+    bare_item_eq(&expected[0], &actual.bare_item) && params_eq(&expected[1], &actual.params)
+}
+
+fn entry_eq(expected: &Value, actual: &ListEntry) -> bool {
+    match *actual {
+        ListEntry::Item(ref item) => item_eq(expected, item),
+        ListEntry::InnerList(ref inner) => match expected[0].as_array() {
+            Some(items) => {
+                items.len() == inner.items.len()
+                    && items.iter().zip(&inner.items).all(|(e, a)| item_eq(e, a))
+                    && params_eq(&expected[1], &inner.params)
+            }
+            None => false,
+        },
+    }
+}
+
+fn list_eq(expected: &Value, actual: &[ListEntry]) -> bool {
+    let expected = expected.as_array().unwrap();
+    expected.len() == actual.len() && expected.iter().zip(actual).all(|(e, a)| entry_eq(e, a))
+}
+
+fn dictionary_eq(expected: &Value, actual: &[(&str, ListEntry)]) -> bool {
+    let expected = expected.as_array().unwrap();
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .all(|(e, a)| e[0].as_str() == Some(a.0) && entry_eq(&e[1], &a.1))
+}
+
+fn join(lines: &Value) -> String {
+    let lines: Vec<&str> = lines
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l.as_str().unwrap())
+        .collect();
+    lines.join(", ")
+}
+
+fn parses(header_type: &str, raw: &str) -> bool {
+    match header_type {
+        "item" => parse_item(raw).is_ok(),
+        "list" => parse_list(raw).is_ok(),
+        _ => parse_dictionary(raw).is_ok(),
+    }
+}
+
+// Returns whether the value parses, and checks the result if it does.
+fn run(case: &Value, raw: &str) -> bool {
+    if case["must_fail"] == true {
+        return parses(case["header_type"].as_str().unwrap(), raw);
+    }
+
+    let expected = &case["expected"];
+    let serialized = match case["header_type"].as_str().unwrap() {
+        "item" => match parse_item(raw) {
+            Ok(item) => {
+                assert!(item_eq(expected, &item), "{:?}", item);
+                serialize_item(&item)
+            }
+            Err(_) => return false,
+        },
+        "list" => match parse_list(raw) {
+            Ok(list) => {
+                assert!(list_eq(expected, &list), "{:?}", list);
+                serialize_list(&list)
+            }
+            Err(_) => return false,
+        },
+        "dictionary" => match parse_dictionary(raw) {
+            Ok(dict) => {
+                assert!(dictionary_eq(expected, &dict), "{:?}", dict);
+                serialize_dictionary(&dict)
+            }
+            Err(_) => return false,
+        },
+        t => panic!("unknown header_type {}", t),
+    };
+
+    let canonical = if case["canonical"].is_array() {
+        join(&case["canonical"])
+    } else {
+        String::from(raw)
+    };
+    assert_eq!(serialized.unwrap(), canonical);
+    true
+}
+
+// Runs every case of the JSON files under `dir`, returns the number of
+// cases.
+fn run_dir(dir: &Path) -> usize {
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            count += run_dir(&path);
+            continue;
+        }
+        if path.extension().map_or(true, |e| e != "json") {
+            continue;
+        }
+        let cases: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        for case in cases.as_array().unwrap() {
+            let name = format!("{}: {}", path.display(), case["name"]);
+            let raw = join(&case["raw"]);
+            let must_fail = case["must_fail"].as_bool().unwrap_or(false);
+            let can_fail = case["can_fail"].as_bool().unwrap_or(false);
+
+            let parsed = match std::panic::catch_unwind(|| run(case, &raw)) {
+                Ok(parsed) => parsed,
+                Err(_) => panic!("{}", name),
+            };
+            if must_fail {
+                assert!(!parsed, "{} must fail", name);
+            } else {
+                assert!(parsed || can_fail, "{} must not fail", name);
+            }
+            count += 1;
+        }
+    }
+    count
+}
+
+#[test]
+fn structured_field_tests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sfv");
+    assert!(run_dir(&dir) > 0);
+}
@@ -317,3 +317,57 @@ mod response {
         incomplete!(b"HTTP/1.1 200 OK\r\na:b\r\n\r");
     }
 }
+
+#[cfg(test)]
+mod round_trip {
+    use crate::*;
+
+    macro_rules! request {
+        ($buf:expr) => {{
+            let mut headers = Vec::<HeaderField>::with_capacity(10);
+            let (req, _) = Request::parse($buf, &mut headers).unwrap().unwrap();
+
+            let mut out = vec![0u8; req.encoded_len()];
+            assert_eq!(req.encode(&mut out), Ok(out.len()));
+            assert_eq!(&out[..], &$buf[..]);
+
+            let mut reparsed_headers = Vec::<HeaderField>::with_capacity(10);
+            let (reparsed, _) = Request::parse(&out, &mut reparsed_headers)
+                .unwrap()
+                .unwrap();
+            assert_eq!(reparsed, req);
+        }};
+    }
+
+    macro_rules! response {
+        ($buf:expr) => {{
+            let mut headers = Vec::<HeaderField>::with_capacity(10);
+            let (res, _) = Response::parse($buf, &mut headers).unwrap().unwrap();
+
+            let mut out = vec![0u8; res.encoded_len()];
+            assert_eq!(res.encode(&mut out), Ok(out.len()));
+            assert_eq!(&out[..], &$buf[..]);
+
+            let mut reparsed_headers = Vec::<HeaderField>::with_capacity(10);
+            let (reparsed, _) = Response::parse(&out, &mut reparsed_headers)
+                .unwrap()
+                .unwrap();
+            assert_eq!(reparsed, res);
+        }};
+    }
+
+    #[test]
+    fn round_trip_request() {
+        request!(b"GET / HTTP/1.1\r\n\r\n");
+        request!(b"GET /hoge HTTP/1.0\r\nHost: example.com\r\nCookie: \r\n\r\n");
+        request!(b"OPTIONS * HTTP/1.1\r\nUser-Agent: a\tb\r\nAccept: */*\r\n\r\n");
+        request!(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n");
+    }
+
+    #[test]
+    fn round_trip_response() {
+        response!(b"HTTP/1.1 200 OK\r\n\r\n");
+        response!(b"HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        response!(b"HTTP/1.1 500 \r\nServer: thhp\r\nConnection: close\r\n\r\n");
+    }
+}